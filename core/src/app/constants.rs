@@ -16,12 +16,31 @@ const fn const_max_u32(a: u32, b: u32) -> u32 {
 }
 
 // --- Timing ---
-pub const TARGET_FPS: u32 = 60;
+pub const TARGET_FPS: u32 = boba::emulator::FRAMES_PER_SECOND;
 pub const TARGET_FRAME_DURATION: Duration =
-    Duration::from_nanos((1_000_000_000u64 / TARGET_FPS as u64));
-pub const CPU_FREQ_HZ: f64 = 4_194_304.0; // Standard Game Boy CPU frequency
-// For 60 FPS and 4.194304 MHz, it's approx 69905 cycles.
-pub const CYCLES_PER_FRAME: u32 = 69905; // Pre-calculated approximate value
+    Duration::from_nanos(1_000_000_000u64 / TARGET_FPS as u64);
+
+/// Fixed fast-forward speeds the speed key cycles through, as multiples of `--clock`.
+pub const SPEED_STEPS: [f64; 3] = [1.0, 2.0, 4.0];
+/// Above this effective speed only every Nth frame is drawn, keeping draws near this
+/// multiple of TARGET_FPS; the emulation itself still runs every frame.
+pub const MAX_DRAWN_SPEED: f32 = 4.0;
+
+/// Draw every Nth emulated frame when running at `speed` times normal, so drawing stays
+/// at or below `MAX_DRAWN_SPEED` times TARGET_FPS.
+pub fn draw_interval(speed: f32) -> u32 {
+    (speed / MAX_DRAWN_SPEED).ceil().max(1.0) as u32
+}
+/// How often the FPS/speed shown in the window title is refreshed.
+pub const SPEED_DISPLAY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of save state slots (selected with the number keys, 0-9).
+pub const STATE_SLOTS: u8 = 10;
+
+/// Queued audio beyond this many bytes (~100 ms of stereo f32) is dropped rather than
+/// letting latency build up, e.g. when running faster than real time with --clock.
+pub const MAX_QUEUED_AUDIO_BYTES: u32 =
+    boba::apu::SAMPLE_RATE / 10 * 2 * std::mem::size_of::<f32>() as u32;
 
 // --- Screen & Scaling ---
 // GB_WIDTH and GB_HEIGHT are now imported from boba::ppu
@@ -61,11 +80,8 @@ pub const INPUT_DEBUG_AREA_WIDTH: u32 = DPAD_AREA_WIDTH + PADDING + BUTTONS_AREA
 pub const INPUT_DEBUG_AREA_HEIGHT: u32 = const_max_u32(DPAD_AREA_HEIGHT, BUTTONS_AREA_HEIGHT);
 
 // --- Disassembly Debug ---
-// <<< --- RECOMMEND using a relative path or embedding --- >>>
-// Example relative path (assumes an 'assets/fonts' directory next to 'src')
-pub const FONT_PATH: &str = "/home/ankan/GameboyEmulatorInRust/core/src/app/Roboto-Regular.ttf";
-// Your original absolute path:
-// pub const FONT_PATH: &str = "/home/ankan/GameBoy/core/src/app/Roboto-Regular.ttf";
+// Embedded so the binary doesn't depend on where it's run from
+pub const DEBUG_FONT: &[u8] = include_bytes!("Roboto-Regular.ttf");
 pub const DEBUG_FONT_SIZE: u16 = 14;
 pub const DISASM_LINES_BEFORE: usize = 5; // Lines to show before PC
 pub const DISASM_LINES_AFTER: usize = 10; // Lines to show after PC
@@ -77,13 +93,29 @@ pub const DEBUG_PC_COLOR: Color = Color::RGB(255, 255, 0); // Yellow for current
 pub const DEBUG_TEXT_COLOR: Color = Color::RGB(220, 220, 220); // Light Gray for text
 pub const MAX_INSTR_BYTES: usize = 3;
 
-// --- Palettes ---
-pub const PALETTE: [Color; 4] = [
-    Color::RGB(0x9B, 0xBC, 0x0F), // Lightest Green (Color 0 / White)
-    Color::RGB(0x8B, 0xAC, 0x0F), // Light Green   (Color 1 / Light Gray)
-    Color::RGB(0x30, 0x62, 0x30), // Dark Green    (Color 2 / Dark Gray)
-    Color::RGB(0x0F, 0x38, 0x0F), // Darkest Green (Color 3 / Black)
+// --- PPU Mode Indicator ---
+pub const PPU_MODE_BAR_WIDTH: u32 = 12; // Colored swatch to the left of the label
+pub const PPU_MODE_AREA_HEIGHT: u32 = DISASM_LINE_HEIGHT;
+pub const PPU_MODE_HBLANK_COLOR: Color = Color::RGB(70, 130, 180); // Steel Blue
+pub const PPU_MODE_VBLANK_COLOR: Color = Color::RGB(147, 112, 219); // Medium Purple
+pub const PPU_MODE_OAM_COLOR: Color = Color::RGB(255, 165, 0); // Orange
+pub const PPU_MODE_VRAM_COLOR: Color = Color::RGB(220, 20, 60); // Crimson
+
+// --- Memory Viewer ---
+pub const MEMORY_VIEW_ROWS: usize = 8; // Rows of 16 bytes; PageUp/PageDown scroll by this many
+// Spans the GB screen and disassembly columns, below both
+pub const MEMORY_AREA_WIDTH: u32 = GB_SCREEN_WIDTH + PADDING + DISASM_AREA_WIDTH;
+pub const MEMORY_AREA_HEIGHT: u32 = DISASM_LINE_HEIGHT * (MEMORY_VIEW_ROWS as u32 + 1); // Title + rows
+pub const MEMORY_WRITE_HIGHLIGHT_COLOR: Color = Color::RGB(140, 70, 0); // Behind the last written byte
+/// Regions the Home key cycles the memory viewer through.
+pub const MEMORY_VIEW_PRESETS: [(&str, u16); 4] = [
+    ("WRAM", 0xC000),
+    ("VRAM", 0x8000),
+    ("IO", 0xFF00),
+    ("OAM", 0xFE00),
 ];
+
+// --- Palettes ---
 // Palette used for the VRAM debug view (simple grayscale)
 pub const DEBUG_PALETTE: [Color; 4] = [
     Color::RGB(0xFF, 0xFF, 0xFF), // White
@@ -94,7 +126,11 @@ pub const DEBUG_PALETTE: [Color; 4] = [
 
 // --- Window Layout Calculations ---
 // This function calculates dimensions at RUNTIME using the const values defined above.
-pub fn calculate_window_dims() -> (u32, u32) {
+pub fn calculate_window_dims(show_debug: bool) -> (u32, u32) {
+    if !show_debug {
+        return (GB_SCREEN_WIDTH, GB_SCREEN_HEIGHT); // Just the scaled GB screen
+    }
+
     // Define the widths of the three main columns
     let col1_width = GB_SCREEN_WIDTH; // Scaled GB screen width
     let col2_width = DISASM_AREA_WIDTH; // Disassembly pane width
@@ -106,14 +142,16 @@ pub fn calculate_window_dims() -> (u32, u32) {
 
     // Calculate the heights needed for each column/area
     let col1_height = GB_SCREEN_HEIGHT; // Scaled GB screen height
-    let col2_height = DISASM_AREA_HEIGHT; // Disassembly pane height
+    let col2_height = DISASM_AREA_HEIGHT + PADDING + PPU_MODE_AREA_HEIGHT; // Disassembly pane + PPU mode bar
     // Column 3 height is Scaled VRAM + Padding + Input Debug height
     let col3_height = VRAM_VIEW_HEIGHT + PADDING + INPUT_DEBUG_AREA_HEIGHT;
 
+    // The memory viewer sits below the GB screen and disassembly columns
+    let memory_view_bottom = std::cmp::max(col1_height, col2_height) + PADDING + MEMORY_AREA_HEIGHT;
+
     // Total window height is the maximum height required by any of the effective vertical columns
-    // (GB Screen, Disassembly, VRAM+Input Stack)
-    let total_window_height: u32 =
-        std::cmp::max(col1_height, std::cmp::max(col2_height, col3_height));
+    // (GB Screen + Disassembly with the memory viewer below, VRAM+Input Stack)
+    let total_window_height: u32 = std::cmp::max(memory_view_bottom, col3_height);
 
     (total_window_width, total_window_height)
 }
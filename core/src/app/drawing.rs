@@ -1,123 +1,84 @@
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::{Point, Rect};
-use sdl2::render::{Canvas, TextureCreator, TextureQuery};
-use sdl2::ttf::Font;
+use sdl2::render::{Canvas, Texture, TextureCreator};
 use sdl2::video::Window;
 
 use super::constants; // Use constants from the same app module
+use super::text_cache::TextCache;
 use boba::cpu::Cpu;
 use boba::joypad::JoypadState;
 use boba::memory_bus::MemoryBus; // Assuming this holds button states
+use boba::ppu::{HBLANK_MODE, OAM_SCAN_MODE, VBLANK_MODE, VRAM_READ_MODE};
 
-/// Draws the scaled Game Boy screen content to the canvas.
+/// Creates a texture for `draw_gb_screen`/`draw_vram_debug` to stream frames into.
+/// RGBA32 is R, G, B, A in memory on every platform, matching `Emulator::render_into`.
+pub fn create_streaming_texture(
+    texture_creator: &TextureCreator<sdl2::video::WindowContext>,
+    width: u32,
+    height: u32,
+) -> Result<Texture<'_>, String> {
+    texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+        .map_err(|e| e.to_string())
+}
+
+/// Draws the Game Boy screen from a 160x144 RGBA frame (see `Emulator::render_into`),
+/// scaled into `dest`: one texture upload and one scaled copy per frame.
 pub fn draw_gb_screen(
     canvas: &mut Canvas<Window>,
-    frame_buffer: &[u8], // Expects buffer of palette indices (0-3)
-    x: i32,
-    y: i32,
+    texture: &mut Texture,
+    rgba_frame: &[u8],
+    dest: Rect,
 ) -> Result<(), String> {
-    // Ensure the buffer has the expected size
-    if frame_buffer.len() != (constants::GB_WIDTH * constants::GB_HEIGHT) as usize {
-        // This check might be too strict if the buffer format changes, but good for safety
-        // eprintln!( // Use eprintln for errors, not println
-        //     "Warning: Frame buffer size mismatch. Expected {}, got {}.",
-        //     constants::GB_WIDTH * constants::GB_HEIGHT,
-        //     frame_buffer.len()
-        // );
-        // Allow drawing anyway, but it might look wrong or panic if smaller
-    }
-
-    let scale = constants::GB_SCALE_FACTOR as i32;
-    let scaled_width = constants::GB_WIDTH as i32 * scale;
-    let scaled_height = constants::GB_HEIGHT as i32 * scale;
-
-    // Optional: Draw a background or border for the GB screen area
-    canvas.set_draw_color(Color::RGB(10, 10, 10));
-    canvas.fill_rect(Rect::new(x, y, scaled_width as u32, scaled_height as u32))?;
-
-    for py in 0..constants::GB_HEIGHT {
-        for px in 0..constants::GB_WIDTH {
-            let index = (py * constants::GB_WIDTH + px) as usize;
-            if index >= frame_buffer.len() {
-                continue;
-            } // Prevent out-of-bounds if buffer is too small
-
-            let color_index = frame_buffer[index];
-            let color = constants::PALETTE[color_index as usize % 4]; // Modulo 4 for safety
-
-            canvas.set_draw_color(color);
-
-            let dest_x = x + (px as i32 * scale);
-            let dest_y = y + (py as i32 * scale);
-            let dest_rect = Rect::new(dest_x, dest_y, scale as u32, scale as u32);
-
-            canvas.fill_rect(dest_rect)?;
-        }
-    }
-    Ok(())
+    texture
+        .update(None, rgba_frame, constants::GB_WIDTH * 4)
+        .map_err(|e| e.to_string())?;
+    canvas.copy(texture, None, dest)
 }
 
 /// Draws the VRAM tile data debug view.
 pub fn draw_vram_debug(
     canvas: &mut Canvas<Window>,
+    texture: &mut Texture,
     vram_buffer: &[u8], // Expects buffer of palette indices (0-3) for the debug view pixels
     x: i32,
     y: i32,
 ) -> Result<(), String> {
-    // Use the native dimensions from the PPU constants (if available) or recalculate
-    let native_width = constants::VRAM_VIEW_WIDTH / constants::VRAM_DEBUG_SCALE_FACTOR;
-    let native_height = constants::VRAM_VIEW_HEIGHT / constants::VRAM_DEBUG_SCALE_FACTOR;
-
-    if vram_buffer.len() != (native_width * native_height) as usize {
-        // eprintln!(
-        //     "Warning: VRAM debug buffer size mismatch. Expected {}, got {}.",
-        //      native_width * native_height,
-        //      vram_buffer.len()
-        // );
-        // Allow drawing anyway
-    }
-
-    let scale = constants::VRAM_DEBUG_SCALE_FACTOR as i32;
-
-    // Draw background for VRAM view area
-    canvas.set_draw_color(constants::DEBUG_BACKGROUND_COLOR);
-    canvas.fill_rect(Rect::new(
-        x,
-        y,
-        constants::VRAM_VIEW_WIDTH,
-        constants::VRAM_VIEW_HEIGHT,
-    ))?;
-
-    for py in 0..native_height {
-        for px in 0..native_width {
-            let index = (py * native_width + px) as usize;
-            if index >= vram_buffer.len() {
-                continue;
-            } // Prevent out-of-bounds
-
-            let color_index = vram_buffer[index];
-            let color = constants::DEBUG_PALETTE[color_index as usize % 4]; // Use debug palette
-
-            canvas.set_draw_color(color);
-
-            let dest_x = x + (px as i32 * scale);
-            let dest_y = y + (py as i32 * scale);
-            let dest_rect = Rect::new(dest_x, dest_y, scale as u32, scale as u32);
-
-            canvas.fill_rect(dest_rect)?;
+    let native_width = (constants::VRAM_VIEW_WIDTH / constants::VRAM_DEBUG_SCALE_FACTOR) as usize;
+
+    // Convert the shades straight into the texture, a row at a time since rows may be padded
+    texture.with_lock(None, |pixels, pitch| {
+        for (row, shades) in pixels
+            .chunks_mut(pitch)
+            .zip(vram_buffer.chunks(native_width))
+        {
+            for (pixel, &shade) in row.chunks_exact_mut(4).zip(shades) {
+                let color = constants::DEBUG_PALETTE[shade as usize % 4]; // Use debug palette
+                pixel.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+            }
         }
-    }
-
-    Ok(())
+    })?;
+    canvas.copy(
+        texture,
+        None,
+        Rect::new(
+            x,
+            y,
+            constants::VRAM_VIEW_WIDTH,
+            constants::VRAM_VIEW_HEIGHT,
+        ),
+    )
 }
 
-/// Draws the disassembly debug view around the current PC.
+/// Draws the disassembly debug view around the current PC. `anchor` is the address the
+/// view started at last time; keeping it while PC stays on screen means the lines don't
+/// jump around, and decoding forward from it keeps them on instruction boundaries.
 pub fn draw_disassembly_debug(
     canvas: &mut Canvas<Window>,
-    texture_creator: &TextureCreator<sdl2::video::WindowContext>,
-    font: &Font,
+    text: &mut TextCache,
     cpu: &Cpu,
     memory_bus: &MemoryBus,
+    anchor: &mut u16,
     pane_x: i32,
     pane_y: i32,
 ) -> Result<(), String> {
@@ -131,59 +92,11 @@ pub fn draw_disassembly_debug(
     ))?;
 
     let pc = cpu.pc();
-    let mut current_addr = pc; // We'll calculate the start address later
-
-    // --- Attempt to find a reasonable start address ---
-    // This is tricky without full backward disassembly. We'll iterate backwards
-    // a few times, disassembling each instruction to find a likely start point.
-    // This is better than guessing fixed offsets but still not perfect.
-    let mut start_addr = pc;
-    for _ in 0..constants::DISASM_LINES_BEFORE {
-        // To step back accurately, we'd ideally need to know the length of the *previous*
-        // instruction. This requires a more complex backward scan or heuristics.
-        // A simpler, less accurate approach is to guess an average length (e.g., 2)
-        // or try disassembling from addr-1, addr-2, addr-3 and see if they *end* at `start_addr`.
-        //
-        // Let's stick to a simpler (but still imperfect) fixed offset for now,
-        // as true backward disassembly is complex. The forward rendering below is correct.
-        start_addr = start_addr.saturating_sub(2); // Guess average 2 bytes/instruction backward
-                                                   // TODO: Implement more robust backward seeking if needed.
-    }
-    // Ensure start_addr doesn't go below 0 after subtractions
-    // The simple subtraction might land us mid-instruction. We accept this limitation for now.
-
-    current_addr = start_addr;
-
-    // --- Disassemble and Draw Lines Forward ---
-    for i in 0..constants::DISASM_TOTAL_LINES {
-        let line_addr = current_addr; // Address for this line
-
-        // Get the disassembled instruction and its actual length
-        let (mnemonic, instr_len_u8) = cpu.disassemble_instruction(line_addr, memory_bus);
-        let instr_len = instr_len_u8 as u16;
-
-        // Read the raw bytes for this instruction to display them
-        // let mut bytes_str = String::new();
-        // let mut raw_bytes: Vec<u8> = Vec::with_capacity(instr_len as usize);
-        // for offset in 0..instr_len {
-        //     // Ensure we don't wrap around address space excessively if near 0xFFFF
-        //     let byte_addr = line_addr.wrapping_add(offset);
-        //     let byte = memory_bus.read_byte(byte_addr);
-        //     raw_bytes.push(byte);
-        //     bytes_str.push_str(&format!("{:02X} ", byte));
-        // }
-
-        // Pad the byte string so the mnemonics align nicely
-        // Max standard instruction length is 3 bytes (XX XX XX ). CB prefix instructions are 2 bytes (CB XX ).
-        // We need space for up to 3 hex pairs + 3 spaces = 9 characters.
-        // let byte_display_width = constants::MAX_INSTR_BYTES * 3; // e.g., 3 bytes * (2 chars + 1 space) = 9
-        // while bytes_str.len() < byte_display_width {
-        //     bytes_str.push(' ');
-        // }
-        // Ensure it doesn't exceed the width if somehow instr_len was > MAX_INSTR_BYTES
-        // bytes_str.truncate(byte_display_width);
-
+    *anchor = disassembly_anchor(cpu, memory_bus, *anchor);
+    let lines = cpu.disassemble_range(*anchor, constants::DISASM_TOTAL_LINES, memory_bus);
 
+    // --- Draw Lines Forward ---
+    for (i, (line_addr, mnemonic, _)) in lines.into_iter().enumerate() {
         // Format the complete line: Address: Bytes Mnemonic
         let disasm_text = format!("0x{:04X}: {}", line_addr, mnemonic);
 
@@ -194,51 +107,179 @@ pub fn draw_disassembly_debug(
             constants::DEBUG_TEXT_COLOR
         };
 
-        // Render the text line
-        let surface = font
-            .render(&disasm_text)
-            .blended(text_color)
-            .map_err(|e| e.to_string())?;
+        // Calculate Y position for this line
+        let current_line_y = pane_y + (i as i32 * constants::DISASM_LINE_HEIGHT as i32);
 
-        let texture = texture_creator
-            .create_texture_from_surface(&surface)
-            .map_err(|e| e.to_string())?;
+        // Draw the line, with some left padding
+        text.draw(canvas, &disasm_text, text_color, pane_x + 5, current_line_y)?;
+    }
 
-        let TextureQuery {
-            width: text_width,
-            height: text_height,
-            ..
-        } = texture.query();
+    Ok(())
+}
 
-        // Calculate Y position for this line
-        let current_line_y = pane_y + (i as i32 * constants::DISASM_LINE_HEIGHT as i32);
+/// Where the disassembly view should start: `previous` if PC is still among the lines
+/// it shows with `DISASM_LINES_AFTER` below it, otherwise the furthest address before PC
+/// that decodes into PC within `DISASM_LINES_BEFORE` instructions (PC itself if none do).
+fn disassembly_anchor(cpu: &Cpu, memory_bus: &MemoryBus, previous: u16) -> u16 {
+    let pc = cpu.pc();
+    let keep_lines = constants::DISASM_TOTAL_LINES - constants::DISASM_LINES_AFTER;
+    let shown = cpu.disassemble_range(previous, keep_lines, memory_bus);
+    if shown.iter().any(|&(addr, _, _)| addr == pc) {
+        return previous;
+    }
 
-        // Define destination rectangle for the text
-        let dest_rect = Rect::new(
-            pane_x + 5, // Add some left padding
-            current_line_y,
-            text_width,
-            text_height,
-        );
+    let max_back = (constants::DISASM_LINES_BEFORE * constants::MAX_INSTR_BYTES) as u16;
+    (1..=max_back)
+        .rev()
+        .map(|back| pc.wrapping_sub(back))
+        .find(|&start| {
+            cpu.disassemble_range(start, constants::DISASM_LINES_BEFORE, memory_bus)
+                .iter()
+                .any(|&(addr, _, length)| addr.wrapping_add(length as u16) == pc)
+        })
+        .unwrap_or(pc)
+}
 
-        // Copy the texture to the canvas
-        canvas.copy(&texture, None, Some(dest_rect))?;
+/// Maps a PPU mode value (STAT bits 0-1) to a short label and indicator color.
+pub fn ppu_mode_label(mode: u8) -> (&'static str, Color) {
+    match mode {
+        HBLANK_MODE => ("HBlank", constants::PPU_MODE_HBLANK_COLOR),
+        VBLANK_MODE => ("VBlank", constants::PPU_MODE_VBLANK_COLOR),
+        OAM_SCAN_MODE => ("OAM", constants::PPU_MODE_OAM_COLOR),
+        VRAM_READ_MODE => ("VRAM", constants::PPU_MODE_VRAM_COLOR),
+        _ => ("???", constants::DEBUG_TEXT_COLOR),
+    }
+}
 
-        // Advance address for the next line using the *actual* instruction length
-        current_addr = current_addr.wrapping_add(instr_len);
+/// Draws a colored bar and label showing the live PPU mode and scanline.
+pub fn draw_ppu_mode_indicator(
+    canvas: &mut Canvas<Window>,
+    text: &mut TextCache,
+    mode: u8,
+    scanline: u8,
+    x: i32,
+    y: i32,
+) -> Result<(), String> {
+    canvas.set_draw_color(constants::DEBUG_BACKGROUND_COLOR);
+    canvas.fill_rect(Rect::new(
+        x,
+        y,
+        constants::DISASM_AREA_WIDTH,
+        constants::PPU_MODE_AREA_HEIGHT,
+    ))?;
 
-        // Safeguard against zero-length instructions (shouldn't happen with valid disassembler)
-        if instr_len == 0 {
-            // If this happens, the disassembler has a bug or encountered truly invalid data.
-            // We advance by 1 to avoid an infinite loop.
-            // Log this error if possible.
-            // eprintln!("Warning: Disassembled instruction at ${:04X} reported zero length.", line_addr);
-            current_addr = current_addr.wrapping_add(1);
+    let (label, color) = ppu_mode_label(mode);
+    canvas.set_draw_color(color);
+    canvas.fill_rect(Rect::new(
+        x + 5,
+        y + 2,
+        constants::PPU_MODE_BAR_WIDTH,
+        constants::PPU_MODE_AREA_HEIGHT - 4,
+    ))?;
+
+    let label_text = format!("Mode {} ({})  LY: {:3}", mode, label, scanline);
+    text.draw(
+        canvas,
+        &label_text,
+        constants::DEBUG_TEXT_COLOR,
+        x + 10 + constants::PPU_MODE_BAR_WIDTH as i32,
+        y,
+    )
+}
+
+/// Draws a hex dump of `MEMORY_VIEW_ROWS` rows of 16 bytes starting at `start`, with an
+/// ASCII column, and highlights the byte the CPU wrote last. Reads go through `peek_byte`,
+/// which sees the same banks as the CPU without firing hooks or watchpoints.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_memory_debug(
+    canvas: &mut Canvas<Window>,
+    text: &mut TextCache,
+    memory_bus: &MemoryBus,
+    region: &str,
+    start: u16,
+    x: i32,
+    y: i32,
+) -> Result<(), String> {
+    canvas.set_draw_color(constants::DEBUG_BACKGROUND_COLOR);
+    canvas.fill_rect(Rect::new(
+        x,
+        y,
+        constants::MEMORY_AREA_WIDTH,
+        constants::MEMORY_AREA_HEIGHT,
+    ))?;
+
+    let line_height = constants::DISASM_LINE_HEIGHT as i32;
+    let title = format!("Memory: {} (PgUp/PgDn scroll, Home next region)", region);
+    text.draw(canvas, &title, constants::DEBUG_TEXT_COLOR, x + 5, y)?;
+
+    // Line the ASCII column up past the widest possible hex row
+    let (hex_width, _) = text
+        .font()
+        .size_of(&format!("FFFF: {}", "FF ".repeat(16)))
+        .map_err(|e| e.to_string())?;
+    let ascii_x = x + 5 + hex_width as i32 + 10;
+
+    for row in 0..constants::MEMORY_VIEW_ROWS {
+        let row_addr = start.wrapping_add(row as u16 * 16);
+        let line_y = y + (row as i32 + 1) * line_height;
+        let bytes: Vec<u8> = (0..16)
+            .map(|i| memory_bus.peek_byte(row_addr.wrapping_add(i)))
+            .collect();
+        let hex_text = format!(
+            "{:04X}: {}",
+            row_addr,
+            bytes
+                .iter()
+                .map(|b| format!("{:02X} ", b))
+                .collect::<String>()
+        );
+
+        if let Some(offset) = memory_bus
+            .last_write_addr()
+            .map(|addr| addr.wrapping_sub(row_addr) as usize)
+            .filter(|&offset| offset < 16)
+        {
+            let byte_start = 6 + offset * 3; // Past "XXXX: " and the earlier bytes
+            let (before_width, _) = text
+                .font()
+                .size_of(&hex_text[..byte_start])
+                .map_err(|e| e.to_string())?;
+            let (byte_width, _) = text
+                .font()
+                .size_of(&hex_text[byte_start..byte_start + 2])
+                .map_err(|e| e.to_string())?;
+            canvas.set_draw_color(constants::MEMORY_WRITE_HIGHLIGHT_COLOR);
+            canvas.fill_rect(Rect::new(
+                x + 5 + before_width as i32,
+                line_y,
+                byte_width,
+                line_height as u32,
+            ))?;
         }
+
+        text.draw(
+            canvas,
+            &hex_text,
+            constants::DEBUG_TEXT_COLOR,
+            x + 5,
+            line_y,
+        )?;
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| {
+                if b == b' ' || b.is_ascii_graphic() {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        text.draw(canvas, &ascii, constants::DEBUG_TEXT_COLOR, ascii_x, line_y)?;
     }
 
     Ok(())
 }
+
 /// Draws the input state debug view.
 pub fn draw_input_debug(
     canvas: &mut Canvas<Window>,
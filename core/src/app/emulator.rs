@@ -1,128 +1,393 @@
 use crate::constants;
-use boba::apu::Apu;
-use boba::cpu::Cpu; // Use Cpu from lib
-use boba::memory_bus::MemoryBus;
-use boba::ppu::Ppu; // Use Ppu from lib
+use boba::cpu::TraceMode;
+use boba::emulator::{BreakReason, Emulator as Core, EmulatorBuilder as CoreBuilder};
+use boba::memory_bus::{BootRamInit, MemoryBus, WatchKind};
+use boba::palette::Palette;
+use boba::png;
+use boba::ppu::{VRAM_DEBUG_HEIGHT, VRAM_DEBUG_WIDTH};
+use boba::rom_file;
 use std::fs;
-use std::path::Path; // Use constants from sibling module
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Represents the core Game Boy emulator components.
+/// The core emulator plus what the SDL frontend adds on top: the ROM file and its
+/// battery/state files, screenshots, traces and stepping mode. Derefs to the core
+/// [`boba::emulator::Emulator`] for everything else.
 pub struct Emulator {
-    pub cpu: Cpu,
-    pub ppu: Ppu,
-    pub apu: Apu,
-    pub memory_bus: MemoryBus,
+    core: Core,
     /// Flag to indicate if the emulator is currently paused for instruction stepping.
     pub stepping: bool, // Added for stepping mode
+    /// Path of the currently inserted ROM, or `None` if the slot is empty.
+    rom_path: Option<PathBuf>,
+    /// Save state slot used by `save_state_file`/`load_state_file`.
+    state_slot: u8,
+    /// Where `toggle_trace` writes; `None` means `<rom>.trace.log`.
+    trace_path: Option<PathBuf>,
+    /// Instruction cap for traces started by `toggle_trace`.
+    trace_limit: Option<u64>,
 }
 
-impl Emulator {
-    /// Creates a new Emulator instance, loads the ROM, and initializes components.
-    pub fn new(rom_path: &Path, skip_boot_rom: bool) -> Result<Self, String> {
-        println!("Initializing APU...");
-        let apu = Apu::new(); // Assuming Apu::new() exists
+/// Configures and constructs an [`Emulator`].
+#[derive(Debug, Clone)]
+pub struct EmulatorBuilder {
+    core: CoreBuilder,
+}
 
-        println!("Initializing memory bus...");
-        let mut memory_bus = MemoryBus::new(); // Assuming MemoryBus::new() exists
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        EmulatorBuilder {
+            core: CoreBuilder::new(),
+        }
+    }
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See [`CoreBuilder::skip_boot_rom`].
+    pub fn skip_boot_rom(mut self, skip: bool) -> Self {
+        self.core = self.core.skip_boot_rom(skip);
+        self
+    }
+
+    /// See [`CoreBuilder::boot_rom`].
+    pub fn boot_rom(mut self, data: Vec<u8>) -> Self {
+        self.core = self.core.boot_rom(data);
+        self
+    }
+
+    /// See [`CoreBuilder::clock_multiplier`].
+    pub fn clock_multiplier(mut self, multiplier: f64) -> Self {
+        self.core = self.core.clock_multiplier(multiplier);
+        self
+    }
 
+    /// See [`CoreBuilder::palette`].
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.core = self.core.palette(palette);
+        self
+    }
+
+    /// See [`CoreBuilder::trust_rom_file_size`].
+    pub fn trust_rom_file_size(mut self, trust: bool) -> Self {
+        self.core = self.core.trust_rom_file_size(trust);
+        self
+    }
+
+    /// See [`CoreBuilder::boot_ram_init`].
+    pub fn boot_ram_init(mut self, init: BootRamInit) -> Self {
+        self.core = self.core.boot_ram_init(init);
+        self
+    }
+
+    /// See [`CoreBuilder::dmg_quirks`].
+    pub fn dmg_quirks(mut self, enabled: bool) -> Self {
+        self.core = self.core.dmg_quirks(enabled);
+        self
+    }
+
+    /// Builds the emulator and inserts the ROM at `rom_path`.
+    pub fn build(self, rom_path: &Path) -> Result<Emulator, String> {
+        let mut emulator = Emulator {
+            core: self.core.build_empty()?,
+            stepping: true, // Initialize stepping mode to off (continuous run)
+            rom_path: None,
+            state_slot: 0,
+            trace_path: None,
+            trace_limit: None,
+        };
+        emulator.insert_cartridge(rom_path)?;
+        Ok(emulator)
+    }
+}
+
+impl Deref for Emulator {
+    type Target = Core;
+
+    fn deref(&self) -> &Core {
+        &self.core
+    }
+}
+
+impl DerefMut for Emulator {
+    fn deref_mut(&mut self) -> &mut Core {
+        &mut self.core
+    }
+}
+
+impl Emulator {
+    /// Loads a new ROM (and its battery save, if any), resetting the CPU, PPU and APU.
+    /// Any cartridge already inserted is ejected first so its save RAM is written out,
+    /// but only once the new ROM has been read and its header checked: a bad file
+    /// leaves the current game running.
+    pub fn insert_cartridge(&mut self, rom_path: &Path) -> Result<(), String> {
         println!("Loading ROM: {}", rom_path.display());
-        let rom_data = fs::read(rom_path)
-            .map_err(|e| format!("Failed to read ROM '{}': {}", rom_path.display(), e))?;
-        let rom_size = rom_data.len();
-        memory_bus.load_rom(&rom_data);
-        println!("ROM loaded successfully ({} bytes)", rom_size);
-
-        println!("Initializing CPU (skip_boot_rom={})...", skip_boot_rom);
-        // NOTE: Kept `let mut cpu` as in your original code snippet.
-        // If `cpu` isn't modified later in `new`, `let cpu` might be more conventional.
-        let mut cpu = Cpu::new(skip_boot_rom); // Assuming Cpu::new(bool) exists
-
-        if skip_boot_rom {
-            println!("Skipping boot ROM - initializing I/O registers post-boot...");
-            // Assuming Cpu::initialize_post_boot_io() exists and takes &mut MemoryBus
-            Cpu::initialize_post_boot_io(&mut memory_bus);
+        let rom_data = rom_file::read_rom(rom_path)?;
+        MemoryBus::check_rom(&rom_data)
+            .map_err(|e| format!("Can't load ROM '{}': {}", rom_path.display(), e))?;
+
+        self.eject_cartridge()?;
+
+        self.core.load_rom(&rom_data)?;
+        println!("ROM loaded successfully ({} bytes)", rom_data.len());
+
+        let save_path = Self::save_path(rom_path);
+        if self.memory_bus.has_battery() && save_path.exists() {
+            self.memory_bus
+                .load_ram(&save_path)
+                .map_err(|e| format!("Failed to read save '{}': {}", save_path.display(), e))?;
+            println!("Loaded battery save: {}", save_path.display());
         }
-        // Note: The 'else' case (loading and running boot ROM) is not handled here,
-        // matching the provided code.
 
-        println!("Initializing PPU...");
-        let ppu = Ppu::new(); // Assuming Ppu::new() exists
+        self.rom_path = Some(rom_path.to_path_buf());
+        Ok(())
+    }
 
-        Ok(Emulator {
-            cpu,
-            ppu,
-            apu,
-            memory_bus,
-            stepping: true, // Initialize stepping mode to off (continuous run)
-        })
+    /// Removes the current cartridge, writing its battery-backed RAM next to the ROM
+    /// as a `.sav` file. Does nothing if no cartridge is inserted.
+    pub fn eject_cartridge(&mut self) -> Result<(), String> {
+        self.save_battery()?;
+        let Some(rom_path) = self.rom_path.take() else {
+            return Ok(());
+        };
+
+        self.core.eject_cartridge();
+        println!("Ejected cartridge: {}", rom_path.display());
+        Ok(())
     }
 
-    /// Toggles the emulator between continuous run mode and single-step mode.
-    pub fn toggle_stepping(&mut self) {
-        self.stepping = !self.stepping;
-        if self.stepping {
-            println!("[Stepping Mode Enabled]");
-        } else {
-            println!("[Continuous Mode Enabled]");
+    /// Writes the current cartridge's battery-backed RAM (and RTC) to its `.sav` file.
+    /// Does nothing if no cartridge is inserted or it has no battery.
+    pub fn save_battery(&self) -> Result<(), String> {
+        let Some(rom_path) = self.rom_path.as_deref() else {
+            return Ok(());
+        };
+        if !self.memory_bus.has_battery() {
+            return Ok(());
         }
+        let save_path = Self::save_path(rom_path);
+        self.memory_bus
+            .save_ram(&save_path)
+            .map_err(|e| format!("Failed to write save '{}': {}", save_path.display(), e))?;
+        println!("Saved battery RAM: {}", save_path.display());
+        Ok(())
     }
 
-    /// Executes exactly one CPU instruction and updates PPU/APU accordingly.
-    /// Use this when `stepping` mode is enabled.
-    /// Returns `Ok(())` or an error string if the CPU encounters an error.
-    pub fn step_instruction(&mut self) -> Result<(), String> {
-        // 1. Step CPU - returns cycles executed or error
-        let executed_cycles = match self.cpu.step(&mut self.memory_bus) {
-            Ok(cycles) => cycles as u32,
-            Err(error_message) => {
-                // Propagate the CPU error immediately, even in stepping mode
-                return Err(error_message);
+    /// Save state slot used for quick save/load.
+    pub fn state_slot(&self) -> u8 {
+        self.state_slot
+    }
+
+    /// Selects the save state slot (0 to `STATE_SLOTS - 1`) for quick save/load.
+    pub fn set_state_slot(&mut self, slot: u8) -> Result<(), String> {
+        if slot >= constants::STATE_SLOTS {
+            return Err(format!(
+                "Invalid state slot {} (must be 0-{})",
+                slot,
+                constants::STATE_SLOTS - 1
+            ));
+        }
+        self.state_slot = slot;
+        Ok(())
+    }
+
+    /// Writes a save state next to the ROM into the current slot's `.stateN` file.
+    pub fn save_state_file(&self) -> Result<PathBuf, String> {
+        let rom_path = self.rom_path.as_deref().ok_or("No cartridge inserted")?;
+        let state_path = Self::state_path(rom_path, self.state_slot);
+        fs::write(&state_path, self.save_state())
+            .map_err(|e| format!("Failed to write state '{}': {}", state_path.display(), e))?;
+        Ok(state_path)
+    }
+
+    /// Restores the current slot's save state (see `save_state_file`). An empty slot
+    /// is reported as an error and leaves the emulator untouched.
+    pub fn load_state_file(&mut self) -> Result<PathBuf, String> {
+        let rom_path = self.rom_path.as_deref().ok_or("No cartridge inserted")?;
+        let state_path = Self::state_path(rom_path, self.state_slot);
+        let data = match fs::read(&state_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(format!(
+                    "State slot {} is empty ('{}' not found)",
+                    self.state_slot,
+                    state_path.display()
+                ));
+            }
+            Err(e) => {
+                return Err(format!(
+                    "Failed to read state '{}': {}",
+                    state_path.display(),
+                    e
+                ));
             }
         };
+        self.load_state(&data)
+            .map_err(|e| format!("Failed to load state '{}': {}", state_path.display(), e))?;
+        Ok(state_path)
+    }
 
-        // Optional: Print CPU state after step for debugging
-        // println!("Stepped: PC=${:04X} Cycles: {} Flags: {:08b}", self.cpu.registers.pc, executed_cycles, self.cpu.registers.f);
+    /// Saves the current frame as `<rom>_<YYYYMMDD_HHMMSS>.png` (UTC) next to the ROM,
+    /// in the colors shown on screen.
+    pub fn save_screenshot(&self) -> Result<PathBuf, String> {
+        let mut rgba = vec![0u8; constants::GB_WIDTH * constants::GB_HEIGHT * 4];
+        self.render_into(&mut rgba);
+        self.write_screenshot("", constants::GB_WIDTH, constants::GB_HEIGHT, &rgba)
+    }
+
+    /// Saves the VRAM tile view as `<rom>_<YYYYMMDD_HHMMSS>_vram.png` next to the ROM,
+    /// in the debug palette the tile pane uses.
+    pub fn save_vram_screenshot(&self) -> Result<PathBuf, String> {
+        let rgba: Vec<u8> = self
+            .ppu
+            .get_vram_debug_buffer()
+            .iter()
+            .flat_map(|&shade| {
+                let color = constants::DEBUG_PALETTE[shade as usize % 4];
+                [color.r, color.g, color.b, 0xFF]
+            })
+            .collect();
+        self.write_screenshot("_vram", VRAM_DEBUG_WIDTH, VRAM_DEBUG_HEIGHT, &rgba)
+    }
 
-        // 2. Step PPU with the cycles the CPU used
-        self.ppu.step(executed_cycles, &mut self.memory_bus);
+    fn write_screenshot(
+        &self,
+        suffix: &str,
+        width: usize,
+        height: usize,
+        rgba: &[u8],
+    ) -> Result<PathBuf, String> {
+        let rom_path = self.rom_path.as_deref().ok_or("No cartridge inserted")?;
+        let stem = rom_path.file_stem().unwrap_or_default().to_string_lossy();
+        let path = rom_path.with_file_name(format!("{}_{}{}.png", stem, timestamp(), suffix));
+        png::write_rgba(&path, width as u32, height as u32, rgba)
+            .map_err(|e| format!("Failed to write screenshot '{}': {}", path.display(), e))?;
+        Ok(path)
+    }
 
-        // 3. Step APU with the cycles the CPU used
-        self.apu.step(executed_cycles, &mut self.memory_bus); // Assuming apu.step exists
+    /// Save state file for a ROM and slot (same name, `.stateN` extension).
+    fn state_path(rom_path: &Path, slot: u8) -> PathBuf {
+        rom_path.with_extension(format!("state{}", slot))
+    }
 
-        Ok(())
+    /// Sets where `toggle_trace` writes instruction traces (a `.gz` name compresses
+    /// them) and how many instructions a trace may hold before it stops by itself.
+    /// Without a path, traces go to `<rom>.trace.log` next to the ROM.
+    pub fn set_trace_options(&mut self, path: Option<PathBuf>, max_entries: Option<u64>) {
+        self.trace_path = path;
+        self.trace_limit = max_entries;
     }
 
-    /// Runs the emulator components for approximately one frame's worth of CPU cycles.
-    /// If `stepping` mode is enabled, this function does nothing immediately.
-    /// Returns `Ok(())` or an error string if the CPU encounters an error during the frame.
+    /// Starts an instruction trace, or stops and flushes the active one. Returns the
+    /// trace file if one was started.
+    pub fn toggle_trace(&mut self) -> Result<Option<PathBuf>, String> {
+        if self.cpu.trace_entries().is_some() {
+            self.cpu.stop_trace()?;
+            return Ok(None);
+        }
+        let path = match &self.trace_path {
+            Some(path) => path.clone(),
+            None => self
+                .rom_path
+                .as_deref()
+                .ok_or("No cartridge inserted")?
+                .with_extension("trace.log"),
+        };
+        let mode = if path.extension().is_some_and(|ext| ext == "gz") {
+            TraceMode::Gzip
+        } else {
+            TraceMode::Plain
+        };
+        let limit = self.trace_limit;
+        self.cpu.start_trace(&path, mode, limit)?;
+        Ok(Some(path))
+    }
+
+    /// Battery save file used for a given ROM (same name, `.sav` extension).
+    fn save_path(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("sav")
+    }
+
+    /// Toggles the emulator between continuous run mode and single-step mode.
+    pub fn toggle_stepping(&mut self) {
+        self.stepping = !self.stepping;
+        if self.stepping {
+            println!("[Stepping Mode Enabled]");
+        } else {
+            println!("[Continuous Mode Enabled]");
+        }
+    }
+
+    /// Runs one frame (see [`Core::run_frame`]) and flushes the battery save once the
+    /// game has finished writing to cartridge RAM. Hitting a breakpoint or watchpoint
+    /// ends the frame early and switches to stepping mode.
+    /// If `stepping` mode is enabled, this function does nothing immediately;
+    /// execution must be triggered via `step_instruction()`.
     pub fn run_frame(&mut self) -> Result<(), String> {
-        // If stepping is enabled, don't run the frame automatically.
-        // Execution must be triggered via step_instruction().
         if self.stepping {
             return Ok(());
         }
-
-        // --- Continuous Execution Logic (only runs if self.stepping is false) ---
-        let mut cycles_this_frame: u32 = 0;
-        while cycles_this_frame < constants::CYCLES_PER_FRAME {
-            // 1. Step CPU - returns cycles executed or error
-            let executed_cycles = match self.cpu.step(&mut self.memory_bus) {
-                Ok(cycles) => cycles as u32,
-                Err(error_message) => {
-                    // Propagate the CPU error immediately
-                    return Err(error_message);
-                }
-            };
-
-            // 2. Step PPU with the cycles the CPU used
-            self.ppu.step(executed_cycles, &mut self.memory_bus);
-
-            // 3. Step APU with the cycles the CPU used
-            self.apu.step(executed_cycles, &mut self.memory_bus); // Assuming apu.step exists
-
-            // 4. Accumulate cycles
-            cycles_this_frame += executed_cycles;
+        match self.core.run_frame_until_breakpoint()? {
+            Some(BreakReason::Breakpoint(addr)) => {
+                println!("Breakpoint hit at {:#06X}", addr);
+                println!("  {}", self.cpu.snapshot());
+                self.toggle_stepping();
+            }
+            Some(BreakReason::Watchpoint(hit)) => {
+                let access = match hit.kind {
+                    WatchKind::Read => "Read",
+                    WatchKind::Write | WatchKind::WriteValue(_) => "Write",
+                };
+                println!(
+                    "Watchpoint hit: {} of {:#04X} at {:#06X} (PC now {:#06X})",
+                    access,
+                    hit.value,
+                    hit.addr,
+                    self.cpu.pc()
+                );
+                println!("  {}", self.cpu.snapshot());
+                self.toggle_stepping();
+            }
+            None => {}
         }
-        Ok(()) // Frame completed successfully
+        if self.memory_bus.take_save_request() {
+            self.save_battery()?;
+        }
+        Ok(())
     }
 }
+
+/// The current UTC time as `YYYYMMDD_HHMMSS`, for screenshot file names.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's days_from_civil, inverted)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}_{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
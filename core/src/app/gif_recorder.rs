@@ -0,0 +1,89 @@
+use boba::palette::{Palette, Rgb};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::constants::{GB_HEIGHT, GB_WIDTH, TARGET_FPS};
+
+/// Records emulator frames to an animated GIF using the 4-color display palette.
+pub struct GifRecorder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    palette: Palette,
+    /// Record one frame, then drop this many.
+    frame_skip: u32,
+    /// Emulated frames seen since recording started.
+    frames_seen: u32,
+    /// GIF delay (centiseconds) already written, to spread rounding over frames.
+    delay_written: u32,
+}
+
+impl GifRecorder {
+    /// Creates `path` and writes the GIF header with `palette` as its global color table.
+    pub fn create(path: &Path, palette: Palette, frame_skip: u32) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|e| format!("Failed to create GIF '{}': {}", path.display(), e))?;
+        let color_table: Vec<u8> = palette.iter().flat_map(|c| [c.r, c.g, c.b]).collect();
+        let mut encoder = gif::Encoder::new(
+            BufWriter::new(file),
+            GB_WIDTH as u16,
+            GB_HEIGHT as u16,
+            &color_table,
+        )
+        .map_err(|e| format!("Failed to start GIF '{}': {}", path.display(), e))?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+        Ok(GifRecorder {
+            encoder,
+            palette,
+            frame_skip,
+            frames_seen: 0,
+            delay_written: 0,
+        })
+    }
+
+    /// Feeds one emulated frame (RGBA from `Emulator::render_into`). Only every
+    /// `frame_skip + 1`th frame is encoded; its delay covers the skipped ones.
+    pub fn push_frame(&mut self, rgba: &[u8]) -> Result<(), String> {
+        let record = self.frames_seen.is_multiple_of(self.frame_skip + 1);
+        self.frames_seen += 1;
+        if !record {
+            return Ok(());
+        }
+
+        // Delay from this frame's start to the next recorded one, in centiseconds
+        let next_recorded = self.frames_seen + self.frame_skip;
+        let target = next_recorded * 100 / TARGET_FPS;
+        let delay = target.saturating_sub(self.delay_written).max(1);
+        self.delay_written += delay;
+
+        let frame = gif::Frame {
+            width: GB_WIDTH as u16,
+            height: GB_HEIGHT as u16,
+            delay: delay as u16,
+            buffer: rgba_to_palette_indices(rgba, &self.palette).into(),
+            ..gif::Frame::default()
+        };
+        self.encoder
+            .write_frame(&frame)
+            .map_err(|e| format!("Failed to write GIF frame: {}", e))
+    }
+}
+
+/// Maps RGBA pixels to indices into `palette`, picking the nearest color so any
+/// tint applied on the way still lands on one of the four shades.
+pub fn rgba_to_palette_indices(rgba: &[u8], palette: &Palette) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .map(|pixel| {
+            let distance = |c: &Rgb| {
+                let dr = pixel[0] as i32 - c.r as i32;
+                let dg = pixel[1] as i32 - c.g as i32;
+                let db = pixel[2] as i32 - c.b as i32;
+                dr * dr + dg * dg + db * db
+            };
+            (0..palette.len())
+                .min_by_key(|&i| distance(&palette[i]))
+                .unwrap_or(0) as u8
+        })
+        .collect()
+}
@@ -1,37 +1,293 @@
+use crate::constants;
+use crate::keymap::KeyMap;
+use boba::GbButton;
 use boba::memory_bus::MemoryBus;
-use sdl2::EventPump;
-use sdl2::event::Event;
+use boba::rom_file::is_rom_path;
+use sdl2::controller::{Axis, GameController};
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
+use sdl2::{EventPump, GameControllerSubsystem};
+use std::path::PathBuf;
 
-/// Polls SDL events and updates the MemoryBus joypad state.
-/// Returns `true` if the quit event was received, `false` otherwise.
-pub fn handle_input(event_pump: &mut EventPump, memory_bus: &mut MemoryBus) -> bool {
-    for event in event_pump.poll_iter() {
-        match event {
-            Event::Quit { .. }
-            | Event::KeyDown {
-                keycode: Some(Keycode::Escape),
-                ..
-            } => {
-                println!("Exit requested.");
-                return true; // Signal quit
-            }
-            Event::KeyDown {
-                keycode: Some(key),
-                repeat: false,
-                ..
-            } => {
-                memory_bus.key_down(key); // Delegate to MemoryBus
+/// Left stick deflection (out of 32767) needed before it counts as a D-pad direction.
+const STICK_DEADZONE: i16 = 16_000;
+
+/// Frontend-level actions produced while polling SDL events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputAction {
+    /// The window was closed or Escape was pressed.
+    Quit,
+    /// A ROM file was dropped onto the window and should be hot-swapped in.
+    LoadRom(PathBuf),
+    /// F5: snapshot the emulator to the current slot's `.stateN` file.
+    SaveState,
+    /// F8: restore the emulator from the current slot's `.stateN` file.
+    LoadState,
+    /// Number keys 0-9: select the save state slot.
+    SelectStateSlot(u8),
+    /// F9: start or stop GIF recording.
+    ToggleRecording,
+    /// F12: save the screen as a PNG.
+    Screenshot,
+    /// F10: save the VRAM tile view as a PNG.
+    VramScreenshot,
+    /// F3: show or hide the debug panes.
+    ToggleDebug,
+    /// F11: switch between windowed and desktop fullscreen.
+    ToggleFullscreen,
+    /// The window changed size, so the panes need laying out again.
+    Resized,
+    /// PageUp/PageDown: scroll the memory viewer by this many rows.
+    ScrollMemory(i32),
+    /// Home: jump the memory viewer to the next preset region.
+    NextMemoryRegion,
+}
+
+/// Validates a dropped file name, returning the ROM path to load or `None` if it should be ignored.
+pub fn dropped_rom_path(filename: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(filename);
+    if is_rom_path(&path) {
+        Some(path)
+    } else {
+        eprintln!(
+            "Ignoring dropped file '{}': expected a .gb, .gbc or .zip ROM",
+            filename
+        );
+        None
+    }
+}
+
+/// Save state slot selected by a number key on the main keyboard row.
+fn state_slot_for_key(keycode: Keycode) -> Option<u8> {
+    let slot = match keycode {
+        Keycode::Num0 => 0,
+        Keycode::Num1 => 1,
+        Keycode::Num2 => 2,
+        Keycode::Num3 => 3,
+        Keycode::Num4 => 4,
+        Keycode::Num5 => 5,
+        Keycode::Num6 => 6,
+        Keycode::Num7 => 7,
+        Keycode::Num8 => 8,
+        Keycode::Num9 => 9,
+        _ => return None,
+    };
+    Some(slot)
+}
+
+/// Key map plus the game controllers currently plugged in.
+pub struct InputState {
+    keymap: KeyMap,
+    controller_subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>, // Kept open so their events keep coming
+    stick_x: Option<GbButton>,        // Direction the left stick holds horizontally
+    stick_y: Option<GbButton>,        // Direction the left stick holds vertically
+}
+
+impl InputState {
+    /// Controllers are opened as SDL reports them (including those connected at
+    /// startup); without a controller subsystem only the keyboard is used.
+    pub fn new(keymap: KeyMap, controller_subsystem: Option<GameControllerSubsystem>) -> Self {
+        InputState {
+            keymap,
+            controller_subsystem,
+            controllers: Vec::new(),
+            stick_x: None,
+            stick_y: None,
+        }
+    }
+
+    fn add_controller(&mut self, joystick_index: u32) {
+        let Some(subsystem) = &self.controller_subsystem else {
+            return;
+        };
+        match subsystem.open(joystick_index) {
+            Ok(controller) => {
+                println!("Controller connected: {}", controller.name());
+                self.controllers.push(controller);
             }
-            Event::KeyUp {
-                keycode: Some(key),
-                repeat: false,
-                ..
-            } => {
-                memory_bus.key_up(key); // Delegate to MemoryBus
+            Err(e) => eprintln!("Failed to open controller {}: {}", joystick_index, e),
+        }
+    }
+
+    fn remove_controller(&mut self, instance_id: u32) {
+        self.controllers.retain(|controller| {
+            let keep = controller.instance_id() != instance_id;
+            if !keep {
+                println!("Controller disconnected: {}", controller.name());
             }
-            _ => {} // Ignore other events
+            keep
+        });
+    }
+
+    /// Treats the left stick as a D-pad: crossing the deadzone presses a direction and
+    /// returning to center (or flipping over) releases it.
+    fn stick_moved(&mut self, axis: Axis, value: i16, memory_bus: &mut MemoryBus) {
+        let (negative, positive, held) = match axis {
+            Axis::LeftX => (GbButton::Left, GbButton::Right, &mut self.stick_x),
+            Axis::LeftY => (GbButton::Up, GbButton::Down, &mut self.stick_y),
+            _ => return,
+        };
+        let direction = if value < -STICK_DEADZONE {
+            Some(negative)
+        } else if value > STICK_DEADZONE {
+            Some(positive)
+        } else {
+            None
+        };
+        if direction == *held {
+            return;
+        }
+        if let Some(previous) = held.take() {
+            memory_bus.release_button(previous);
         }
+        if let Some(button) = direction {
+            memory_bus.press_button(button);
+        }
+        *held = direction;
     }
-    false // Continue running
+}
+
+/// Polls SDL events and updates the MemoryBus joypad state from the keyboard and any
+/// game controllers. Returns every action the main loop should take (quit, load a
+/// dropped ROM, save a state...), in the order the events came in.
+pub fn handle_input(
+    event_pump: &mut EventPump,
+    input: &mut InputState,
+    memory_bus: &mut MemoryBus,
+) -> Vec<InputAction> {
+    actions_for_events(event_pump.poll_iter(), input, memory_bus)
+}
+
+/// Applies the joypad changes in `events` and collects the actions they ask for, so
+/// several in one poll (say a dropped ROM and a resize) are all kept.
+pub fn actions_for_events(
+    events: impl IntoIterator<Item = Event>,
+    input: &mut InputState,
+    memory_bus: &mut MemoryBus,
+) -> Vec<InputAction> {
+    events
+        .into_iter()
+        .filter_map(|event| action_for_event(event, input, memory_bus))
+        .collect()
+}
+
+fn action_for_event(
+    event: Event,
+    input: &mut InputState,
+    memory_bus: &mut MemoryBus,
+) -> Option<InputAction> {
+    let action = match event {
+        Event::Quit { .. }
+        | Event::KeyDown {
+            keycode: Some(Keycode::Escape),
+            ..
+        } => {
+            println!("Exit requested.");
+            InputAction::Quit
+        }
+        Event::DropFile { filename, .. } => InputAction::LoadRom(dropped_rom_path(&filename)?),
+        Event::KeyDown {
+            keycode: Some(Keycode::F5),
+            repeat: false,
+            ..
+        } => InputAction::SaveState,
+        Event::KeyDown {
+            keycode: Some(Keycode::F8),
+            repeat: false,
+            ..
+        } => InputAction::LoadState,
+        Event::KeyDown {
+            keycode: Some(Keycode::F9),
+            repeat: false,
+            ..
+        } => InputAction::ToggleRecording,
+        Event::KeyDown {
+            keycode: Some(Keycode::F12),
+            repeat: false,
+            ..
+        } => InputAction::Screenshot,
+        Event::KeyDown {
+            keycode: Some(Keycode::F10),
+            repeat: false,
+            ..
+        } => InputAction::VramScreenshot,
+        Event::KeyDown {
+            keycode: Some(Keycode::F3),
+            repeat: false,
+            ..
+        } => InputAction::ToggleDebug,
+        Event::KeyDown {
+            keycode: Some(Keycode::F11),
+            repeat: false,
+            ..
+        } => InputAction::ToggleFullscreen,
+        Event::Window {
+            win_event: WindowEvent::Resized(..) | WindowEvent::SizeChanged(..),
+            ..
+        } => InputAction::Resized,
+        // Scrolling repeats while the key is held
+        Event::KeyDown {
+            keycode: Some(Keycode::PageUp),
+            ..
+        } => InputAction::ScrollMemory(-(constants::MEMORY_VIEW_ROWS as i32)),
+        Event::KeyDown {
+            keycode: Some(Keycode::PageDown),
+            ..
+        } => InputAction::ScrollMemory(constants::MEMORY_VIEW_ROWS as i32),
+        Event::KeyDown {
+            keycode: Some(Keycode::Home),
+            repeat: false,
+            ..
+        } => InputAction::NextMemoryRegion,
+        Event::KeyDown {
+            keycode,
+            scancode: Some(scancode),
+            repeat: false,
+            ..
+        } => {
+            // Number keys pick a save state slot unless the key map binds them
+            if let Some(button) = input.keymap.button_for_key(scancode) {
+                memory_bus.press_button(button);
+                return None;
+            }
+            InputAction::SelectStateSlot(keycode.and_then(state_slot_for_key)?)
+        }
+        Event::KeyUp {
+            scancode: Some(scancode),
+            repeat: false,
+            ..
+        } => {
+            if let Some(button) = input.keymap.button_for_key(scancode) {
+                memory_bus.release_button(button);
+            }
+            return None;
+        }
+        Event::ControllerDeviceAdded { which, .. } => {
+            input.add_controller(which);
+            return None;
+        }
+        Event::ControllerDeviceRemoved { which, .. } => {
+            input.remove_controller(which);
+            return None;
+        }
+        Event::ControllerButtonDown { button, .. } => {
+            if let Some(button) = input.keymap.button_for_pad(button) {
+                memory_bus.press_button(button);
+            }
+            return None;
+        }
+        Event::ControllerButtonUp { button, .. } => {
+            if let Some(button) = input.keymap.button_for_pad(button) {
+                memory_bus.release_button(button);
+            }
+            return None;
+        }
+        Event::ControllerAxisMotion { axis, value, .. } => {
+            input.stick_moved(axis, value, memory_bus);
+            return None;
+        }
+        _ => return None, // Ignore other events
+    };
+    Some(action)
 }
@@ -0,0 +1,142 @@
+use boba::GbButton;
+use sdl2::controller::Button as PadButton;
+use sdl2::keyboard::Scancode;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the key map file looked up next to the executable.
+pub const KEYMAP_FILE_NAME: &str = "input.ini";
+
+/// Maps keyboard scancodes and game controller buttons to Game Boy buttons.
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    keys: HashMap<Scancode, GbButton>,
+    pad_buttons: HashMap<PadButton, GbButton>,
+}
+
+impl Default for KeyMap {
+    /// Arrows/WASD for the D-pad, Z/J for A, X/K for B, Backspace/Right Shift for Select
+    /// and Enter/Space for Start; on a controller the D-pad, A/B, Back and Start.
+    fn default() -> Self {
+        let keys = [
+            (Scancode::Up, GbButton::Up),
+            (Scancode::W, GbButton::Up),
+            (Scancode::Down, GbButton::Down),
+            (Scancode::S, GbButton::Down),
+            (Scancode::Left, GbButton::Left),
+            (Scancode::A, GbButton::Left),
+            (Scancode::Right, GbButton::Right),
+            (Scancode::D, GbButton::Right),
+            (Scancode::Z, GbButton::A),
+            (Scancode::J, GbButton::A),
+            (Scancode::X, GbButton::B),
+            (Scancode::K, GbButton::B),
+            (Scancode::Backspace, GbButton::Select),
+            (Scancode::RShift, GbButton::Select),
+            (Scancode::Return, GbButton::Start),
+            (Scancode::Space, GbButton::Start),
+        ];
+        let pad_buttons = [
+            (PadButton::DPadUp, GbButton::Up),
+            (PadButton::DPadDown, GbButton::Down),
+            (PadButton::DPadLeft, GbButton::Left),
+            (PadButton::DPadRight, GbButton::Right),
+            (PadButton::A, GbButton::A),
+            (PadButton::B, GbButton::B),
+            (PadButton::Back, GbButton::Select),
+            (PadButton::Start, GbButton::Start),
+        ];
+        KeyMap {
+            keys: keys.into_iter().collect(),
+            pad_buttons: pad_buttons.into_iter().collect(),
+        }
+    }
+}
+
+impl KeyMap {
+    /// Parses a key map: one `<button> = <binding>, <binding>, ...` line per Game Boy
+    /// button (up, down, left, right, a, b, start, select). Bindings are SDL scancode
+    /// names (`Z`, `Return`, `Right Shift`) or `pad:` plus an SDL controller button name
+    /// (`pad:a`, `pad:dpup`, `pad:start`). A button listed in the file replaces all of
+    /// its default bindings; unlisted buttons keep theirs. Blank lines and lines starting
+    /// with `;` or `#` are ignored.
+    pub fn parse(text: &str) -> Result<KeyMap, String> {
+        let mut map = KeyMap::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            let (name, bindings) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Line {}: expected '<button> = <bindings>'", index + 1))?;
+            let button = parse_button(name.trim())
+                .ok_or_else(|| format!("Line {}: unknown button '{}'", index + 1, name.trim()))?;
+
+            map.keys.retain(|_, mapped| *mapped != button);
+            map.pad_buttons.retain(|_, mapped| *mapped != button);
+            for binding in bindings.split(',').map(str::trim).filter(|b| !b.is_empty()) {
+                if let Some(pad_name) = binding.strip_prefix("pad:") {
+                    let pad_button = PadButton::from_string(pad_name).ok_or_else(|| {
+                        format!(
+                            "Line {}: unknown controller button '{}'",
+                            index + 1,
+                            pad_name
+                        )
+                    })?;
+                    map.pad_buttons.insert(pad_button, button);
+                } else {
+                    let scancode = Scancode::from_name(binding)
+                        .ok_or_else(|| format!("Line {}: unknown key '{}'", index + 1, binding))?;
+                    map.keys.insert(scancode, button);
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    /// Loads the key map at `path`, using the defaults if the file doesn't exist.
+    pub fn load(path: &Path) -> Result<KeyMap, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => KeyMap::parse(&text)
+                .map_err(|e| format!("Invalid key map '{}': {}", path.display(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(KeyMap::default()),
+            Err(e) => Err(format!(
+                "Failed to read key map '{}': {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Where the key map is looked up: `input.ini` next to the executable.
+    pub fn default_path() -> Option<PathBuf> {
+        let exe = std::env::current_exe().ok()?;
+        Some(exe.parent()?.join(KEYMAP_FILE_NAME))
+    }
+
+    pub fn button_for_key(&self, scancode: Scancode) -> Option<GbButton> {
+        self.keys.get(&scancode).copied()
+    }
+
+    pub fn button_for_pad(&self, button: PadButton) -> Option<GbButton> {
+        self.pad_buttons.get(&button).copied()
+    }
+}
+
+fn parse_button(name: &str) -> Option<GbButton> {
+    let button = match name.to_ascii_lowercase().as_str() {
+        "up" => GbButton::Up,
+        "down" => GbButton::Down,
+        "left" => GbButton::Left,
+        "right" => GbButton::Right,
+        "a" => GbButton::A,
+        "b" => GbButton::B,
+        "start" => GbButton::Start,
+        "select" => GbButton::Select,
+        _ => return None,
+    };
+    Some(button)
+}
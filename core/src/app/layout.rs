@@ -0,0 +1,75 @@
+use crate::constants::{
+    DISASM_AREA_HEIGHT, DISASM_AREA_WIDTH, GB_HEIGHT, GB_WIDTH, INPUT_DEBUG_AREA_WIDTH,
+    MEMORY_AREA_HEIGHT, PADDING, PPU_MODE_AREA_HEIGHT, VRAM_VIEW_HEIGHT, VRAM_VIEW_WIDTH,
+};
+use sdl2::rect::{Point, Rect};
+
+/// Where everything is drawn for a given window size. The debug panes keep their fixed
+/// sizes; the GB screen gets whatever space is left, scaled by the largest whole factor
+/// that fits and centered in it. Without the debug panes the screen gets the whole
+/// window and the pane positions go unused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub gb_screen: Rect,
+    pub disasm: Point,
+    pub ppu_mode: Point,
+    pub memory: Point,
+    pub vram: Point,
+    pub input: Point,
+}
+
+impl Layout {
+    /// Lays out a `width` x `height` window (in pixels, as `Canvas::output_size` gives).
+    /// A window too small for everything still gets a 1x screen; panes past the edge
+    /// are clipped.
+    pub fn new(width: u32, height: u32, show_debug: bool) -> Self {
+        if !show_debug {
+            return Layout {
+                gb_screen: scaled_screen_rect(width, height),
+                disasm: Point::new(0, 0),
+                ppu_mode: Point::new(0, 0),
+                memory: Point::new(0, 0),
+                vram: Point::new(0, 0),
+                input: Point::new(0, 0),
+            };
+        }
+
+        let far_right_width = VRAM_VIEW_WIDTH.max(INPUT_DEBUG_AREA_WIDTH);
+        let debug_columns_width = PADDING + DISASM_AREA_WIDTH + PADDING + far_right_width;
+        let screen_area_width = width
+            .saturating_sub(debug_columns_width)
+            .max(GB_WIDTH as u32);
+        let screen_area_height = height
+            .saturating_sub(PADDING + MEMORY_AREA_HEIGHT)
+            .max(GB_HEIGHT as u32);
+
+        let disasm_x = (screen_area_width + PADDING) as i32;
+        let ppu_mode_y = DISASM_AREA_HEIGHT + PADDING;
+        let memory_y = screen_area_height.max(ppu_mode_y + PPU_MODE_AREA_HEIGHT) + PADDING;
+        let far_right_x = disasm_x + (DISASM_AREA_WIDTH + PADDING) as i32;
+
+        Layout {
+            gb_screen: scaled_screen_rect(screen_area_width, screen_area_height),
+            disasm: Point::new(disasm_x, 0),
+            ppu_mode: Point::new(disasm_x, ppu_mode_y as i32),
+            memory: Point::new(0, memory_y as i32),
+            vram: Point::new(far_right_x, 0),
+            input: Point::new(far_right_x, (VRAM_VIEW_HEIGHT + PADDING) as i32),
+        }
+    }
+}
+
+/// The 160x144 screen scaled by the largest integer factor that fits a `width` x
+/// `height` area at the origin, centered with the rest left as letterboxing.
+fn scaled_screen_rect(width: u32, height: u32) -> Rect {
+    let scale = (width / GB_WIDTH as u32)
+        .min(height / GB_HEIGHT as u32)
+        .max(1);
+    let (screen_width, screen_height) = (GB_WIDTH as u32 * scale, GB_HEIGHT as u32 * scale);
+    Rect::new(
+        (width.saturating_sub(screen_width) / 2) as i32,
+        (height.saturating_sub(screen_height) / 2) as i32,
+        screen_width,
+        screen_height,
+    )
+}
@@ -1,7 +1,8 @@
-use sdl2::ttf::Font; // Import Keycode
 use std::{
-    env,
-    path::Path,
+    env, fs,
+    io::{self, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
     thread,
     time::{Duration, Instant},
 };
@@ -10,62 +11,381 @@ use std::{
 mod constants;
 mod drawing;
 mod emulator;
+mod gif_recorder;
 mod input;
+mod keymap;
+mod layout;
 mod sdl_setup;
+mod text_cache;
 
-use emulator::Emulator;
+use boba::memory_bus::{BootRamInit, WatchKind};
+use boba::palette::{self, Palette};
+use emulator::EmulatorBuilder;
+use gif_recorder::GifRecorder;
+use layout::Layout;
+use sdl2::render::Canvas;
+use sdl2::video::{FullscreenType, Window};
+use text_cache::TextCache;
 
 fn main() -> Result<(), String> {
     // --- Argument Parsing ---
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        eprintln!("Usage: {} <rom_path>", args[0]);
+        eprintln!(
+            "Usage: {} <rom_path> [--clock <multiplier>] [--palette <file.pal>] [--serial] [--record-gif <file.gif>] [--gif-frame-skip <n>] [--trust-rom-size] [--dmg-quirks] [--no-debug] [--boot-rom <dmg_boot.bin>] [--font <file.ttf>] [--state-slot <0-9>] [--boot-ram <zero|random[:seed]|pattern>] [--break <addr>]... [--watch <addr>[-end][=value]]... [--watch-read <addr>[-end]]... [--trace <file[.gz]>] [--trace-limit <instructions>]",
+            args[0]
+        );
         std::process::exit(1);
     }
     let rom_path = Path::new(&args[1]);
+    let mut clock_multiplier = 1.0;
+    let mut display_palette = palette::DEFAULT_PALETTE;
+    let mut serial_to_stdout = false;
+    let mut record_gif: Option<PathBuf> = None;
+    let mut gif_frame_skip = 1; // 30 fps GIFs by default
+    let mut trust_rom_size = false;
+    let mut dmg_quirks = false;
+    let mut boot_rom = None;
+    let mut font_path: Option<PathBuf> = None;
+    let mut state_slot = 0;
+    let mut boot_ram_init = BootRamInit::Zero;
+    let mut breakpoints = Vec::new();
+    let mut watchpoints = Vec::new();
+    let mut trace_path: Option<PathBuf> = None;
+    let mut trace_limit = None;
+    let mut show_debug = true;
+    let mut arg_iter = args.iter().skip(2);
+    while let Some(arg) = arg_iter.next() {
+        match arg.as_str() {
+            "--clock" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--clock requires a multiplier, e.g. --clock 2.0")?;
+                clock_multiplier = value
+                    .parse::<f64>()
+                    .map_err(|e| format!("Invalid --clock value '{}': {}", value, e))?;
+            }
+            "--palette" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--palette requires a .pal file path")?;
+                display_palette = palette::load_palette(Path::new(value))?;
+            }
+            "--serial" => serial_to_stdout = true,
+            "--trust-rom-size" => trust_rom_size = true,
+            "--dmg-quirks" => dmg_quirks = true,
+            "--no-debug" => show_debug = false,
+            "--boot-rom" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--boot-rom requires a boot ROM file path")?;
+                let data = fs::read(value)
+                    .map_err(|e| format!("Failed to read boot ROM '{}': {}", value, e))?;
+                boot_rom = Some(data);
+            }
+            "--font" => {
+                let value = arg_iter.next().ok_or("--font requires a .ttf file path")?;
+                font_path = Some(PathBuf::from(value));
+            }
+            "--state-slot" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--state-slot requires a slot number (0-9)")?;
+                state_slot = value
+                    .parse::<u8>()
+                    .map_err(|e| format!("Invalid --state-slot value '{}': {}", value, e))?;
+            }
+            "--boot-ram" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--boot-ram requires zero, random[:seed] or pattern")?;
+                boot_ram_init = parse_boot_ram_init(value)?;
+            }
+            "--break" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--break requires a hex address, e.g. --break 0x0150")?;
+                breakpoints.push(parse_hex_u16(value, "--break")?);
+            }
+            "--watch" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--watch requires a hex address or range, e.g. --watch 0xFF40 or 0x8000-0x9FFF=0x42")?;
+                let (range, kind) = match value.split_once('=') {
+                    Some((range, written)) => {
+                        let written = u8::try_from(parse_hex_u16(written, "--watch")?)
+                            .map_err(|_| format!("Invalid --watch value '{}'", value))?;
+                        (range, WatchKind::WriteValue(written))
+                    }
+                    None => (value.as_str(), WatchKind::Write),
+                };
+                watchpoints.push((parse_hex_range(range, "--watch")?, kind));
+            }
+            "--watch-read" => {
+                let value = arg_iter.next().ok_or(
+                    "--watch-read requires a hex address or range, e.g. --watch-read 0xFF00",
+                )?;
+                watchpoints.push((parse_hex_range(value, "--watch-read")?, WatchKind::Read));
+            }
+            "--trace" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--trace requires an output file path")?;
+                trace_path = Some(PathBuf::from(value));
+            }
+            "--trace-limit" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--trace-limit requires an instruction count")?;
+                trace_limit = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|e| format!("Invalid --trace-limit value '{}': {}", value, e))?,
+                );
+            }
+            "--record-gif" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--record-gif requires an output .gif path")?;
+                record_gif = Some(PathBuf::from(value));
+            }
+            "--gif-frame-skip" => {
+                let value = arg_iter
+                    .next()
+                    .ok_or("--gif-frame-skip requires a frame count")?;
+                gif_frame_skip = value
+                    .parse::<u32>()
+                    .map_err(|e| format!("Invalid --gif-frame-skip value '{}': {}", value, e))?;
+            }
+            other => return Err(format!("Unknown argument: {}", other)),
+        }
+    }
     let rom_filename = rom_path.file_name().unwrap_or_default().to_string_lossy();
-    let window_title = format!("Rust GB Emu - {}", rom_filename);
+    let mut window_title = format!("Rust GB Emu - {}", rom_filename);
 
     // --- Setup SDL Context ---
-    let mut sdl_context = sdl_setup::init_sdl(&window_title)?;
+    let mut sdl_context = sdl_setup::init_sdl(&window_title, show_debug)?;
 
-    // --- Load Font ---
-    println!("Loading font: {}...", constants::FONT_PATH);
-    let font_path = Path::new(constants::FONT_PATH);
-    if !font_path.exists() {
-        return Err(format!("Font file not found: {}", constants::FONT_PATH));
-    }
-    let font: Font = sdl_context
-        .ttf_context
-        .load_font(font_path, constants::DEBUG_FONT_SIZE)?;
-    println!("Font loaded successfully.");
+    // --- Setup Audio (optional: keep running silently without a device) ---
+    let audio_queue = match sdl_setup::open_audio_queue(&sdl_context.sdl) {
+        Ok(queue) => Some(queue),
+        Err(e) => {
+            eprintln!("Audio disabled: {}", e);
+            None
+        }
+    };
+
+    // --- Load Font into the debug text cache (optional: the text panes are skipped without it) ---
+    let mut text_cache = sdl_setup::load_debug_font(
+        &sdl_context.ttf_context,
+        font_path.as_deref(),
+        constants::DEBUG_FONT,
+    )
+    .map(|font| TextCache::new(&sdl_context.texture_creator, font));
+
+    // --- Setup Input (key map from input.ini if present; controllers optional) ---
+    let keymap = match keymap::KeyMap::default_path() {
+        Some(path) => keymap::KeyMap::load(&path)?,
+        None => keymap::KeyMap::default(),
+    };
+    let controller_subsystem = match sdl_context.sdl.game_controller() {
+        Ok(subsystem) => Some(subsystem),
+        Err(e) => {
+            eprintln!("Controller support disabled: {}", e);
+            None
+        }
+    };
+    let mut input_state = input::InputState::new(keymap, controller_subsystem);
 
     // --- Setup Emulator ---
-    let mut emulator = Emulator::new(rom_path, true)?;
-
-    // --- Pre-calculate drawing coordinates ---
-    let gb_screen_x = 0;
-    let gb_screen_y = 0;
-    let disasm_pane_x = (constants::GB_SCREEN_WIDTH + constants::PADDING) as i32;
-    let disasm_pane_y = 0;
-    let far_right_pane_x =
-        disasm_pane_x + constants::DISASM_AREA_WIDTH as i32 + constants::PADDING as i32;
-    let vram_view_y = 0;
-    let input_view_y = constants::VRAM_VIEW_HEIGHT as i32 + constants::PADDING as i32; // Adjusted based on constants layout
+    let mut builder = EmulatorBuilder::new()
+        .skip_boot_rom(true)
+        .clock_multiplier(clock_multiplier)
+        .palette(display_palette)
+        .trust_rom_file_size(trust_rom_size)
+        .boot_ram_init(boot_ram_init)
+        .dmg_quirks(dmg_quirks);
+    if let Some(data) = boot_rom {
+        builder = builder.boot_rom(data);
+    }
+    let mut emulator = builder.build(rom_path)?;
+    emulator.set_state_slot(state_slot)?;
+    for addr in breakpoints {
+        emulator.add_breakpoint(addr);
+    }
+    for (range, kind) in watchpoints {
+        emulator.add_watch_range(range, kind);
+    }
+    let trace_from_start = trace_path.is_some();
+    emulator.set_trace_options(trace_path, trace_limit);
+    if trace_from_start && let Some(path) = emulator.toggle_trace()? {
+        println!("Tracing instructions to {}", path.display());
+    }
+    if serial_to_stdout {
+        // Test ROMs (e.g. Blargg's) report their results over the link port
+        emulator.memory_bus.set_serial_callback(|byte| {
+            print!("{}", byte as char);
+            let _ = io::stdout().flush();
+        });
+    }
+    println!(
+        "Clock: {}x ({} cycles per frame)",
+        clock_multiplier,
+        emulator.cycles_per_frame()
+    );
+
+    // --- Screen and VRAM view textures, refilled each drawn frame ---
+    let mut screen_texture = drawing::create_streaming_texture(
+        &sdl_context.texture_creator,
+        constants::GB_WIDTH as u32,
+        constants::GB_HEIGHT as u32,
+    )?;
+    let mut vram_texture = drawing::create_streaming_texture(
+        &sdl_context.texture_creator,
+        constants::VRAM_VIEW_WIDTH / constants::VRAM_DEBUG_SCALE_FACTOR,
+        constants::VRAM_VIEW_HEIGHT / constants::VRAM_DEBUG_SCALE_FACTOR,
+    )?;
+    let mut screen_frame = vec![0u8; constants::GB_WIDTH * constants::GB_HEIGHT * 4];
+
+    // --- GIF Recording (F9 toggles; --record-gif starts immediately) ---
+    let gif_path = record_gif
+        .clone()
+        .unwrap_or_else(|| rom_path.with_extension("gif"));
+    let mut gif_takes = 0;
+    let mut gif_frame = vec![0u8; constants::GB_WIDTH * constants::GB_HEIGHT * 4];
+    let mut recorder: Option<GifRecorder> = None;
+    if record_gif.is_some() {
+        recorder = Some(start_gif_take(
+            &gif_path,
+            &mut gif_takes,
+            *emulator.palette(),
+            gif_frame_skip,
+        )?);
+    }
+
+    // --- Pane positions, redone whenever the window changes size or F3 toggles the panes ---
+    let mut layout = window_layout(&sdl_context.canvas, show_debug)?;
 
     // --- Added: State for step/toggle key presses to prevent rapid multi-triggering ---
     let mut p_key_pressed_last_frame = false;
     let mut n_key_pressed_last_frame = false;
+    let mut o_key_pressed_last_frame = false;
+    let mut v_key_pressed_last_frame = false;
+    let mut t_key_pressed_last_frame = false;
+
+    // --- Disassembly view start, kept while PC stays on screen ---
+    let mut disasm_anchor: u16 = 0;
+
+    // --- Memory viewer: the preset region shown, and where the view is scrolled to ---
+    let mut memory_preset = 0;
+    let mut memory_view_addr = constants::MEMORY_VIEW_PRESETS[0].1;
+
+    // --- Speed control: hold Tab to run uncapped, F cycles through SPEED_STEPS ---
+    let mut f_key_pressed_last_frame = false;
+    let mut speed_step = 0;
+    let mut frame_index: u32 = 0;
+    let mut draw_interval: u32 = 1; // Draw every Nth frame when running uncapped
+    let mut speed_window_start = Instant::now();
+    let mut frames_in_window: u32 = 0;
 
     // --- Main Loop ---
     println!("Starting main loop...");
     'main_loop: loop {
         let frame_start_time = Instant::now();
 
-        // --- 1. Handle Input (Original signature: only returns quit signal) ---
+        // --- 1. Handle Input ---
         // This will handle standard emulator inputs (A, B, Start, Select, D-Pad, Quit)
-        if input::handle_input(&mut sdl_context.event_pump, &mut emulator.memory_bus) {
-            break 'main_loop;
+        // and ROMs dropped onto the window.
+        let actions = input::handle_input(
+            &mut sdl_context.event_pump,
+            &mut input_state,
+            &mut emulator.memory_bus,
+        );
+        for action in actions {
+            match action {
+                input::InputAction::Quit => break 'main_loop,
+                input::InputAction::LoadRom(path) => {
+                    // insert_cartridge ejects (and saves) the current game first
+                    match emulator.insert_cartridge(&path) {
+                        Ok(()) => {
+                            let rom_filename =
+                                path.file_name().unwrap_or_default().to_string_lossy();
+                            window_title = format!("Rust GB Emu - {}", rom_filename);
+                            if let Err(e) = sdl_context.canvas.window_mut().set_title(&window_title)
+                            {
+                                eprintln!("Failed to update window title: {}", e);
+                            }
+                        }
+                        Err(e) => eprintln!("Failed to load dropped ROM: {}", e),
+                    }
+                }
+                input::InputAction::SaveState => match emulator.save_state_file() {
+                    Ok(path) => println!("Saved state: {}", path.display()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                input::InputAction::LoadState => match emulator.load_state_file() {
+                    Ok(path) => println!("Loaded state: {}", path.display()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                input::InputAction::SelectStateSlot(slot) => match emulator.set_state_slot(slot) {
+                    Ok(()) => println!("State slot: {}", emulator.state_slot()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                input::InputAction::ToggleRecording => {
+                    if recorder.take().is_some() {
+                        println!("Stopped GIF recording."); // Dropping the encoder finishes the file
+                    } else {
+                        match start_gif_take(
+                            &gif_path,
+                            &mut gif_takes,
+                            *emulator.palette(),
+                            gif_frame_skip,
+                        ) {
+                            Ok(new_recorder) => recorder = Some(new_recorder),
+                            Err(e) => eprintln!("{}", e),
+                        }
+                    }
+                }
+                input::InputAction::Screenshot => match emulator.save_screenshot() {
+                    Ok(path) => println!("Saved screenshot: {}", path.display()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                input::InputAction::VramScreenshot => match emulator.save_vram_screenshot() {
+                    Ok(path) => println!("Saved VRAM screenshot: {}", path.display()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                input::InputAction::ToggleFullscreen => {
+                    let window = sdl_context.canvas.window_mut();
+                    let fullscreen = match window.fullscreen_state() {
+                        FullscreenType::Off => FullscreenType::Desktop,
+                        _ => FullscreenType::Off,
+                    };
+                    if let Err(e) = window.set_fullscreen(fullscreen) {
+                        eprintln!("Failed to toggle fullscreen: {}", e);
+                    }
+                }
+                input::InputAction::ToggleDebug => {
+                    show_debug = !show_debug;
+                    let window = sdl_context.canvas.window_mut();
+                    if window.fullscreen_state() == FullscreenType::Off {
+                        let (width, height) = constants::calculate_window_dims(show_debug);
+                        if let Err(e) = window.set_size(width, height) {
+                            eprintln!("Failed to resize window: {}", e);
+                        }
+                    }
+                    layout = window_layout(&sdl_context.canvas, show_debug)?;
+                }
+                input::InputAction::Resized => {
+                    layout = window_layout(&sdl_context.canvas, show_debug)?;
+                }
+                input::InputAction::ScrollMemory(rows) => {
+                    memory_view_addr = memory_view_addr.wrapping_add_signed(rows as i16 * 16);
+                }
+                input::InputAction::NextMemoryRegion => {
+                    memory_preset = (memory_preset + 1) % constants::MEMORY_VIEW_PRESETS.len();
+                    memory_view_addr = constants::MEMORY_VIEW_PRESETS[memory_preset].1;
+                }
+            }
         }
 
         // --- Added: Check for Stepping Control Keys ---
@@ -74,6 +394,15 @@ fn main() -> Result<(), String> {
             keyboard_state.is_scancode_pressed(sdl2::keyboard::Scancode::P);
         let n_key_currently_pressed =
             keyboard_state.is_scancode_pressed(sdl2::keyboard::Scancode::N);
+        let o_key_currently_pressed =
+            keyboard_state.is_scancode_pressed(sdl2::keyboard::Scancode::O);
+        let v_key_currently_pressed =
+            keyboard_state.is_scancode_pressed(sdl2::keyboard::Scancode::V);
+        let f_key_currently_pressed =
+            keyboard_state.is_scancode_pressed(sdl2::keyboard::Scancode::F);
+        let t_key_currently_pressed =
+            keyboard_state.is_scancode_pressed(sdl2::keyboard::Scancode::T);
+        let uncapped = keyboard_state.is_scancode_pressed(sdl2::keyboard::Scancode::Tab);
 
         let mut step_executed_this_iteration = false;
 
@@ -93,9 +422,48 @@ fn main() -> Result<(), String> {
             step_executed_this_iteration = true; // Mark that a step happened
         }
 
+        // Step over CALL/RST on V key *press* *if* in stepping mode
+        if emulator.stepping && v_key_currently_pressed && !v_key_pressed_last_frame {
+            if let Err(e) = emulator.step_over() {
+                eprintln!("Emulator Step Error: {}", e);
+            }
+            step_executed_this_iteration = true;
+        }
+
+        // Step out of the current subroutine on O key *press* *if* in stepping mode
+        if emulator.stepping && o_key_currently_pressed && !o_key_pressed_last_frame {
+            if let Err(e) = emulator.step_out() {
+                eprintln!("Emulator Step Error: {}", e);
+            }
+            step_executed_this_iteration = true;
+        }
+
+        // Start/stop the instruction trace on T key *press* *if* in stepping mode
+        if emulator.stepping && t_key_currently_pressed && !t_key_pressed_last_frame {
+            match emulator.toggle_trace() {
+                Ok(Some(path)) => println!("Tracing instructions to {}", path.display()),
+                Ok(None) => println!("Instruction trace stopped"),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        // Cycle the fixed speed on F key *press*
+        if f_key_currently_pressed && !f_key_pressed_last_frame {
+            speed_step = (speed_step + 1) % constants::SPEED_STEPS.len();
+            let speed = constants::SPEED_STEPS[speed_step];
+            match emulator.set_clock_multiplier(clock_multiplier * speed) {
+                Ok(()) => println!("Speed: {}x", speed),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
         // Update last frame state for keys
         p_key_pressed_last_frame = p_key_currently_pressed;
         n_key_pressed_last_frame = n_key_currently_pressed;
+        o_key_pressed_last_frame = o_key_currently_pressed;
+        v_key_pressed_last_frame = v_key_currently_pressed;
+        f_key_pressed_last_frame = f_key_currently_pressed;
+        t_key_pressed_last_frame = t_key_currently_pressed;
         // --- End Added ---
 
         // --- 2. Emulate One Frame (Conditional) ---
@@ -105,14 +473,48 @@ fn main() -> Result<(), String> {
                 eprintln!("Emulator Error: {}", e);
                 break 'main_loop;
             }
+            let samples = emulator.apu.take_samples();
+            if let Some(queue) = &audio_queue
+                && queue.size() < constants::MAX_QUEUED_AUDIO_BYTES
+                && let Err(e) = queue.queue_audio(&samples)
+            {
+                eprintln!("Failed to queue audio: {}", e);
+            }
+            if let Some(gif) = recorder.as_mut() {
+                emulator.render_into(&mut gif_frame);
+                if let Err(e) = gif.push_frame(&gif_frame) {
+                    eprintln!("{}; recording stopped", e);
+                    recorder = None;
+                }
+            }
+            frame_index = frame_index.wrapping_add(1);
+            frames_in_window += 1;
         }
         // Note: Single step execution is handled above based on 'N' key press
 
-        // --- 3. Update Debug Views ---
-        // Original logic: always update. We'll keep this for simplicity,
-        // although it could be optimized to only update when state changes.
-        // If optimizing: update if !emulator.stepping || step_executed_this_iteration
-        emulator.ppu.update_vram_debug_buffer(&emulator.memory_bus);
+        // Show the measured FPS and speed in the title, and pick how many frames to
+        // skip drawing so uncapped runs aren't held back by rendering
+        let window_elapsed = speed_window_start.elapsed();
+        if window_elapsed >= constants::SPEED_DISPLAY_INTERVAL {
+            let fps = frames_in_window as f32 / window_elapsed.as_secs_f32();
+            let speed = fps / constants::TARGET_FPS as f32 * emulator.clock_multiplier() as f32;
+            draw_interval = constants::draw_interval(speed);
+            let title = format!("{} - {:.0} FPS ({:.1}x)", window_title, fps, speed);
+            if let Err(e) = sdl_context.canvas.window_mut().set_title(&title) {
+                eprintln!("Failed to update window title: {}", e);
+            }
+            speed_window_start = Instant::now();
+            frames_in_window = 0;
+        }
+        if uncapped && !emulator.stepping && !frame_index.is_multiple_of(draw_interval) {
+            continue; // Frame skip: the frame was emulated, just not drawn
+        }
+
+        // --- 3. Update Debug Views (skipped entirely in the game-only layout) ---
+        if show_debug {
+            let core = &mut *emulator; // Borrow PPU and bus separately through the deref
+            core.ppu.update_vram_debug_buffer(&core.memory_bus);
+        }
 
         // --- 4. Drawing ---
         // Original logic: Draw every frame, which is correct.
@@ -121,55 +523,92 @@ fn main() -> Result<(), String> {
             .set_draw_color(constants::DEBUG_BACKGROUND_COLOR); // Use consistent background
         sdl_context.canvas.clear();
 
-        // Draw GB Screen
+        // Draw GB Screen: CGB games draw their own colors, DMG games go through the palette
+        emulator.render_into(&mut screen_frame);
         if let Err(e) = drawing::draw_gb_screen(
             &mut sdl_context.canvas,
-            emulator.ppu.get_frame_buffer(),
-            gb_screen_x,
-            gb_screen_y,
+            &mut screen_texture,
+            &screen_frame,
+            layout.gb_screen,
         ) {
             eprintln!("Error drawing GB screen: {}", e);
         }
 
-        // Draw Disassembly - Pass the locally loaded font
-        if let Err(e) = drawing::draw_disassembly_debug(
-            &mut sdl_context.canvas,
-            &sdl_context.texture_creator,
-            &font, // Pass the font loaded in main
-            &emulator.cpu,
-            &emulator.memory_bus,
-            disasm_pane_x,
-            disasm_pane_y,
-        ) {
-            eprintln!("Error drawing disassembly: {}", e);
-        }
+        if show_debug {
+            if let Some(text_cache) = &mut text_cache {
+                // Draw Disassembly
+                if let Err(e) = drawing::draw_disassembly_debug(
+                    &mut sdl_context.canvas,
+                    text_cache,
+                    &emulator.cpu,
+                    &emulator.memory_bus,
+                    &mut disasm_anchor,
+                    layout.disasm.x(),
+                    layout.disasm.y(),
+                ) {
+                    eprintln!("Error drawing disassembly: {}", e);
+                }
 
-        // Draw VRAM View
-        if let Err(e) = drawing::draw_vram_debug(
-            &mut sdl_context.canvas,
-            emulator.ppu.get_vram_debug_buffer(),
-            far_right_pane_x,
-            vram_view_y,
-        ) {
-            eprintln!("Error drawing VRAM: {}", e);
-        }
+                // Draw PPU Mode Indicator (below the disassembly pane)
+                if let Err(e) = drawing::draw_ppu_mode_indicator(
+                    &mut sdl_context.canvas,
+                    text_cache,
+                    emulator.ppu.mode(),
+                    emulator.ppu.scanline(),
+                    layout.ppu_mode.x(),
+                    layout.ppu_mode.y(),
+                ) {
+                    eprintln!("Error drawing PPU mode: {}", e);
+                }
 
-        // Draw Input View
-        // Get the current state directly from the joypad struct within the memory bus
-        if let Err(e) = drawing::draw_input_debug(
-            &mut sdl_context.canvas,
-            &emulator.memory_bus.joypad.get_state(), // Get fresh state
-            far_right_pane_x,
-            input_view_y,
-        ) {
-            eprintln!("Error drawing Input: {}", e);
+                // Draw Memory Viewer (below the GB screen and disassembly)
+                if let Err(e) = drawing::draw_memory_debug(
+                    &mut sdl_context.canvas,
+                    text_cache,
+                    &emulator.memory_bus,
+                    constants::MEMORY_VIEW_PRESETS[memory_preset].0,
+                    memory_view_addr,
+                    layout.memory.x(),
+                    layout.memory.y(),
+                ) {
+                    eprintln!("Error drawing memory: {}", e);
+                }
+            }
+
+            // Draw VRAM View
+            if let Err(e) = drawing::draw_vram_debug(
+                &mut sdl_context.canvas,
+                &mut vram_texture,
+                emulator.ppu.get_vram_debug_buffer(),
+                layout.vram.x(),
+                layout.vram.y(),
+            ) {
+                eprintln!("Error drawing VRAM: {}", e);
+            }
+
+            // Draw Input View
+            // Get the current state directly from the joypad struct within the memory bus
+            if let Err(e) = drawing::draw_input_debug(
+                &mut sdl_context.canvas,
+                &emulator.memory_bus.joypad.get_state(), // Get fresh state
+                layout.input.x(),
+                layout.input.y(),
+            ) {
+                eprintln!("Error drawing Input: {}", e);
+            }
         }
 
         sdl_context.canvas.present();
 
         // --- 5. Frame Timing (Conditional) ---
-        // Original timing logic, but only apply if NOT stepping
-        if !emulator.stepping {
+        // Original timing logic, but only apply if NOT stepping; uncapped runs don't sleep
+        if emulator.stepping {
+            // Added: If stepping, maybe sleep briefly to avoid maxing CPU when idle
+            if !step_executed_this_iteration {
+                // Avoid sleeping right after stepping
+                thread::sleep(Duration::from_millis(5)); // Reduce CPU usage while paused
+            }
+        } else if !uncapped {
             let elapsed_time = frame_start_time.elapsed();
             if elapsed_time < constants::TARGET_FRAME_DURATION {
                 let sleep_duration = constants::TARGET_FRAME_DURATION.saturating_sub(elapsed_time);
@@ -181,15 +620,88 @@ fn main() -> Result<(), String> {
                     thread::yield_now();
                 }
             }
-        } else {
-            // Added: If stepping, maybe sleep briefly to avoid maxing CPU when idle
-            if !step_executed_this_iteration {
-                // Avoid sleeping right after stepping
-                thread::sleep(Duration::from_millis(5)); // Reduce CPU usage while paused
-            }
         }
     } // End 'main_loop
 
+    // Finish any instruction trace so buffered lines (and a gzip trailer) reach the disk
+    if let Err(e) = emulator.cpu.stop_trace() {
+        eprintln!("{}", e);
+    }
+
+    // Flush battery-backed RAM so progress survives quitting
+    if let Err(e) = emulator.save_battery() {
+        eprintln!("{}", e);
+    }
     println!("Emulator stopped.");
     Ok(())
 }
+
+/// Lays the panes out for the canvas's current size in pixels.
+fn window_layout(canvas: &Canvas<Window>, show_debug: bool) -> Result<Layout, String> {
+    let (width, height) = canvas.output_size()?;
+    Ok(Layout::new(width, height, show_debug))
+}
+
+/// Starts a new GIF take. The first take uses `path`; later ones get a `-2`, `-3`, ...
+/// suffix so stopping and restarting doesn't overwrite an earlier recording.
+fn start_gif_take(
+    path: &Path,
+    takes: &mut u32,
+    palette: Palette,
+    frame_skip: u32,
+) -> Result<GifRecorder, String> {
+    *takes += 1;
+    let take_path = if *takes == 1 {
+        path.to_path_buf()
+    } else {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("{}-{}.gif", stem, takes))
+    };
+    let recorder = GifRecorder::create(&take_path, palette, frame_skip)?;
+    println!("Recording GIF: {}", take_path.display());
+    Ok(recorder)
+}
+
+/// Parses a hex number with an optional `0x` or `$` prefix, as `flag`'s argument.
+fn parse_hex_u16(value: &str, flag: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches('$');
+    u16::from_str_radix(digits, 16)
+        .map_err(|e| format!("Invalid {} value '{}': {}", flag, value, e))
+}
+
+/// Parses `<addr>` or `<first>-<last>` (inclusive) hex addresses, as `flag`'s argument.
+fn parse_hex_range(value: &str, flag: &str) -> Result<RangeInclusive<u16>, String> {
+    let (first, last) = value.split_once('-').unwrap_or((value, value));
+    let range = parse_hex_u16(first, flag)?..=parse_hex_u16(last, flag)?;
+    if range.is_empty() {
+        return Err(format!(
+            "Invalid {} range '{}': end before start",
+            flag, value
+        ));
+    }
+    Ok(range)
+}
+
+/// Parses `--boot-ram`: `zero`, `pattern`, `random` (seeded from the clock) or
+/// `random:<seed>` for a reproducible run.
+fn parse_boot_ram_init(value: &str) -> Result<BootRamInit, String> {
+    match value.split_once(':') {
+        None if value == "zero" => Ok(BootRamInit::Zero),
+        None if value == "pattern" => Ok(BootRamInit::HardwarePattern),
+        None if value == "random" => {
+            let seed = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+            println!("Boot RAM seed: {}", seed);
+            Ok(BootRamInit::Random(seed))
+        }
+        Some(("random", seed)) => seed
+            .parse::<u64>()
+            .map(BootRamInit::Random)
+            .map_err(|e| format!("Invalid --boot-ram seed '{}': {}", seed, e)),
+        _ => Err(format!(
+            "Invalid --boot-ram value '{}' (expected zero, random[:seed] or pattern)",
+            value
+        )),
+    }
+}
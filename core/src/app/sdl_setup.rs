@@ -1,8 +1,12 @@
 use crate::constants;
+use boba::apu::SAMPLE_RATE;
 use sdl2::Sdl;
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
 use sdl2::render::{Canvas, TextureCreator};
-use sdl2::ttf::Sdl2TtfContext; // Keep Path for checking
+use sdl2::rwops::RWops;
+use sdl2::ttf::{Font, Sdl2TtfContext};
 use sdl2::video::{Window, WindowContext};
+use std::path::Path;
 
 // No lifetime parameter needed
 pub struct SdlContext {
@@ -14,7 +18,7 @@ pub struct SdlContext {
 }
 
 // No lifetime parameter needed in signature or return type
-pub fn init_sdl(window_title: &str) -> Result<SdlContext, String> {
+pub fn init_sdl(window_title: &str, show_debug: bool) -> Result<SdlContext, String> {
     println!("Initializing SDL2...");
     let sdl = sdl2::init()?;
     let video_subsystem = sdl.video()?;
@@ -22,24 +26,19 @@ pub fn init_sdl(window_title: &str) -> Result<SdlContext, String> {
     println!("Initializing SDL2_ttf...");
     let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
 
-    // --- FONT LOADING REMOVED FROM HERE ---
-    // println!("Loading font: {}...", font_path_str);
-    // let font_path = Path::new(font_path_str);
-    // if !font_path.exists() {
-    //     return Err(format!("Font file not found: {}", font_path_str));
-    // }
-    // let font = ttf_context.load_font(font_path, constants::DEBUG_FONT_SIZE)?;
-    // println!("Font loaded successfully.");
-
-    let (window_width, window_height) = constants::calculate_window_dims();
+    let (window_width, window_height) = constants::calculate_window_dims(show_debug);
     println!("Creating window ({}x{})...", window_width, window_height);
 
     let window = video_subsystem
         .window(window_title, window_width, window_height)
         .position_centered()
+        .resizable()
         .build()
         .map_err(|e| e.to_string())?;
 
+    // Scale the screen and VRAM textures up with nearest-neighbor filtering, keeping pixels sharp
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+
     println!("Creating accelerated canvas...");
     let canvas = window
         .into_canvas()
@@ -63,3 +62,46 @@ pub fn init_sdl(window_title: &str) -> Result<SdlContext, String> {
         event_pump,
     })
 }
+
+/// Loads the debug font from `path`, falling back to the `embedded` font bytes when no
+/// path is given or it can't be loaded. The font only feeds the disassembly and PPU mode
+/// panes, so if neither loads this reports it and returns `None`, and callers keep
+/// running without those panes.
+pub fn load_debug_font<'ttf>(
+    ttf_context: &'ttf Sdl2TtfContext,
+    path: Option<&Path>,
+    embedded: &'static [u8],
+) -> Option<Font<'ttf, 'static>> {
+    if let Some(path) = path {
+        match ttf_context.load_font(path, constants::DEBUG_FONT_SIZE) {
+            Ok(font) => return Some(font),
+            Err(e) => eprintln!(
+                "Failed to load font '{}', using the built-in one: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+    let font = RWops::from_bytes(embedded)
+        .and_then(|rwops| ttf_context.load_font_from_rwops(rwops, constants::DEBUG_FONT_SIZE));
+    match font {
+        Ok(font) => Some(font),
+        Err(e) => {
+            eprintln!("Debug text disabled, failed to load font: {}", e);
+            None
+        }
+    }
+}
+
+/// Opens a stereo f32 audio queue at the APU's sample rate and starts playback.
+pub fn open_audio_queue(sdl: &Sdl) -> Result<AudioQueue<f32>, String> {
+    let audio_subsystem = sdl.audio()?;
+    let desired_spec = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE as i32),
+        channels: Some(2),
+        samples: Some(1024),
+    };
+    let queue = audio_subsystem.open_queue::<f32, _>(None, &desired_spec)?;
+    queue.resume();
+    Ok(queue)
+}
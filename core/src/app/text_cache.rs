@@ -0,0 +1,98 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator, TextureQuery};
+use sdl2::ttf::Font;
+use sdl2::video::{Window, WindowContext};
+use std::collections::HashMap;
+
+/// Most textures kept before the least recently drawn ones are dropped. The debug panes
+/// show a few dozen lines, and while paused or looping the same ones come back.
+const MAX_ENTRIES: usize = 512;
+
+struct CachedText<'a> {
+    texture: Texture<'a>,
+    last_used: u64,
+}
+
+/// Rendered debug text, keyed by string and color, so lines that don't change between
+/// frames skip the TTF render and texture upload. The cache owns the font it renders
+/// with: a different font means a new cache, so stale textures can't outlive it.
+pub struct TextCache<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    font: Font<'a, 'static>,
+    entries: HashMap<(String, Color), CachedText<'a>>,
+    clock: u64, // Bumped per draw, to find the least recently used entry
+}
+
+impl<'a> TextCache<'a> {
+    pub fn new(
+        texture_creator: &'a TextureCreator<WindowContext>,
+        font: Font<'a, 'static>,
+    ) -> Self {
+        TextCache {
+            texture_creator,
+            font,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// The font text is rendered with, for measuring strings.
+    pub fn font(&self) -> &Font<'a, 'static> {
+        &self.font
+    }
+
+    /// Draws `text` in `color` with its top-left corner at (`x`, `y`), rendering it only
+    /// if it isn't cached yet.
+    pub fn draw(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        text: &str,
+        color: Color,
+        x: i32,
+        y: i32,
+    ) -> Result<(), String> {
+        self.clock += 1;
+        let key = (text.to_string(), color);
+        if !self.entries.contains_key(&key) {
+            let texture = self.render(text, color)?;
+            if self.entries.len() >= MAX_ENTRIES {
+                self.evict_oldest();
+            }
+            self.entries.insert(
+                key.clone(),
+                CachedText {
+                    texture,
+                    last_used: 0,
+                },
+            );
+        }
+
+        let entry = self.entries.get_mut(&key).expect("entry was just inserted");
+        entry.last_used = self.clock;
+        let TextureQuery { width, height, .. } = entry.texture.query();
+        canvas.copy(&entry.texture, None, Some(Rect::new(x, y, width, height)))
+    }
+
+    fn render(&self, text: &str, color: Color) -> Result<Texture<'a>, String> {
+        let surface = self
+            .font
+            .render(text)
+            .blended(color)
+            .map_err(|e| e.to_string())?;
+        self.texture_creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())
+    }
+
+    fn evict_oldest(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+}
@@ -1,10 +1,27 @@
 use crate::memory_bus::MemoryBus;
+use noise::NoiseChannel;
+use serde::{Deserialize, Serialize};
+use square::SquareChannel;
+use wave::WaveChannel;
+
+mod noise;
+mod square;
+mod units;
+mod wave;
 
 // --- Constants --- (Keep as before)
 const CPU_FREQ: u32 = 4_194_304;
 const FRAME_SEQUENCER_FREQ_HZ: u32 = 512;
 const FRAME_SEQUENCER_DIVIDER: u32 = CPU_FREQ / FRAME_SEQUENCER_FREQ_HZ;
 
+/// Output sample rate (stereo frames per second) produced by `take_samples`.
+pub const SAMPLE_RATE: u32 = 44_100;
+/// Samples (left and right counted separately) buffered before new ones are dropped,
+/// so a frontend that never drains them can't grow the buffer without bound.
+const MAX_BUFFERED_SAMPLES: usize = SAMPLE_RATE as usize * 2;
+/// Scales the summed channels so four full-volume channels stay within -1.0..=1.0.
+const MIX_GAIN: f32 = 0.25;
+
 const NR10_ADDR: u16 = 0xFF10; // Channel 1 Sweep
 const NR11_ADDR: u16 = 0xFF11; // Channel 1 Length/Duty
 const NR12_ADDR: u16 = 0xFF12; // Channel 1 Volume/Envelope
@@ -51,6 +68,9 @@ const NR50_DEFAULT: u8 = 0x77; // Check PanDocs (seems to vary slightly)
 const NR51_DEFAULT: u8 = 0xF3; // Check PanDocs
 // NR52 is status/control, read constructed, write only affects bit 7
 
+/// The APU. Save states hold everything but the samples not yet taken and the sample
+/// rate setting.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Apu {
     // --- Timing ---
     cycle_counter: u32,
@@ -85,13 +105,18 @@ pub struct Apu {
     nr43: u8,
     nr44: u8,
 
-    // --- Channel State (Placeholders - requires detailed implementation) ---
-    // TODO: Replace these with actual channel state structs
-    ch1_active: bool,
-    ch2_active: bool,
-    ch3_active: bool,
-    ch4_active: bool,
-    // Add full state for each channel here...
+    // --- Output ---
+    sample_timer: u64, // Accumulates cycles * SAMPLE_RATE; one frame per sample_period
+    #[serde(skip)]
+    sample_period: u64, // CPU_FREQ scaled by the clock multiplier
+    #[serde(skip)]
+    samples: Vec<f32>, // Interleaved left/right samples not yet taken
+
+    // --- Channel State ---
+    ch1: SquareChannel,
+    ch2: SquareChannel,
+    ch3: WaveChannel,
+    ch4: NoiseChannel,
 }
 
 impl Apu {
@@ -101,6 +126,9 @@ impl Apu {
             frame_sequencer_step: 0,
 
             apu_enabled: false,
+            sample_timer: 0,
+            sample_period: CPU_FREQ as u64,
+            samples: Vec::new(),
             nr50: NR50_DEFAULT,
             nr51: NR51_DEFAULT,
 
@@ -119,19 +147,36 @@ impl Apu {
             nr43: NR43_DEFAULT,
             nr44: NR44_DEFAULT,
 
-            // Initialize channel states (placeholders)
-            ch1_active: false,
-            ch2_active: false,
-            ch3_active: false,
-            ch4_active: false,
-            // Initialize full channel state structs...
+            // Initialize channel states
+            ch1: SquareChannel::new(),
+            ch2: SquareChannel::new(),
+            ch3: WaveChannel::new(),
+            ch4: NoiseChannel::new(),
         }
     }
 
     pub fn step(&mut self, cycles: u32, memory_bus: &mut MemoryBus) {
+        // Apply register writes the CPU made since the last step, then publish the
+        // read-back values (write-only bits set, NR52 status) for CPU reads
+        let writes = memory_bus.take_apu_writes();
+        for &(addr, value) in &writes {
+            self.write_byte(addr, value, memory_bus);
+        }
+        if !writes.is_empty() {
+            self.sync_registers(memory_bus);
+        }
+
+        // Wave RAM lives on the bus; channel 3 plays from a copy of it
+        for (i, byte) in self.ch3.wave_ram.iter_mut().enumerate() {
+            *byte = memory_bus.get_io_reg(WAVE_RAM_START + i as u16);
+        }
+
+        self.generate_samples(cycles);
         if !self.apu_enabled {
+            memory_bus.set_wave_ram_playing_byte(None);
             return;
         }
+        let nr52_before = self.read_byte(NR52_ADDR);
 
         // --- Frame Sequencer Clocking ---
         self.cycle_counter += cycles;
@@ -158,10 +203,119 @@ impl Apu {
         }
 
         // --- Channel Frequency Timers ---
-        // TODO: Clock individual channel frequency timers based on `cycles`.
+        self.ch1.tick(cycles);
+        self.ch2.tick(cycles);
+        self.ch3.tick(cycles);
+        self.ch4.tick(cycles);
+        memory_bus.set_wave_ram_playing_byte(self.ch3.playing_byte());
+
+        // Length counters may have switched a channel off
+        let nr52 = self.read_byte(NR52_ADDR);
+        if nr52 != nr52_before {
+            memory_bus.set_io_reg_direct(NR52_ADDR, nr52);
+        }
+    }
+
+    /// Matches the sample clock to an overclocked or underclocked CPU: output stays at
+    /// `SAMPLE_RATE` per real second, so a game running at twice the speed sounds an
+    /// octave higher instead of piling up twice the samples.
+    pub fn set_clock_multiplier(&mut self, multiplier: f64) {
+        self.sample_period = (CPU_FREQ as f64 * multiplier).round().max(1.0) as u64;
+        self.sample_timer %= self.sample_period;
+    }
+
+    /// Emits stereo frames at `SAMPLE_RATE` per real second for the given span of CPU
+    /// cycles.
+    fn generate_samples(&mut self, cycles: u32) {
+        self.sample_timer += cycles as u64 * SAMPLE_RATE as u64;
+        while self.sample_timer >= self.sample_period {
+            self.sample_timer -= self.sample_period;
+            let (left, right) = self.mix();
+            if self.samples.len() < MAX_BUFFERED_SAMPLES {
+                self.samples.push(left);
+                self.samples.push(right);
+            }
+        }
+    }
+
+    /// Current left/right output, panned by NR51 and scaled by the NR50 master volumes.
+    fn mix(&self) -> (f32, f32) {
+        if !self.apu_enabled {
+            return (0.0, 0.0);
+        }
+        let mut left = 0.0;
+        let mut right = 0.0;
+        let channels = [
+            (self.ch1.dac_enabled(), self.ch1.amplitude()),
+            (self.ch2.dac_enabled(), self.ch2.amplitude()),
+            (self.ch3.dac_enabled(), self.ch3.amplitude()),
+            (self.ch4.dac_enabled(), self.ch4.amplitude()),
+        ];
+        for (index, (dac_enabled, amplitude)) in channels.into_iter().enumerate() {
+            if !dac_enabled {
+                continue; // A powered-off DAC contributes nothing
+            }
+            // DAC: digital 0..=15 maps linearly onto analog 1.0..=-1.0
+            let analog = 1.0 - amplitude as f32 / 7.5;
+            if self.nr51 & (0x10 << index) != 0 {
+                left += analog;
+            }
+            if self.nr51 & (0x01 << index) != 0 {
+                right += analog;
+            }
+        }
+        // NR50 volumes are 0-7, meaning 1/8 to 8/8
+        let left_volume = ((self.nr50 >> 4) & 0x07) as f32 + 1.0;
+        let right_volume = (self.nr50 & 0x07) as f32 + 1.0;
+        (
+            left * left_volume / 8.0 * MIX_GAIN,
+            right * right_volume / 8.0 * MIX_GAIN,
+        )
+    }
 
-        // --- Sample Generation ---
-        // TODO: Generate audio samples at the target output rate.
+    /// Returns and clears the interleaved stereo samples (left, right, left, ...)
+    /// generated at `SAMPLE_RATE` since the last call.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Completes an APU just read from a save state: checks and masks the channel
+    /// state, and keeps `running`'s sample rate and samples not yet taken. Fails
+    /// without touching `running` if the state is invalid.
+    pub(crate) fn restore_unsaved(&mut self, running: &mut Apu) -> Result<(), String> {
+        self.ch1.validate_loaded()?;
+        self.ch2.validate_loaded()?;
+        self.ch3.validate_loaded()?;
+        self.ch4.validate_loaded()?;
+        self.cycle_counter %= FRAME_SEQUENCER_DIVIDER;
+        self.frame_sequencer_step %= 8;
+        self.sample_period = running.sample_period;
+        self.sample_timer %= self.sample_period;
+        self.samples = std::mem::take(&mut running.samples);
+        Ok(())
+    }
+
+    /// Copies every register's read-back value onto the bus, where CPU reads find it.
+    fn sync_registers(&self, memory_bus: &mut MemoryBus) {
+        for addr in NR10_ADDR..=NR52_ADDR {
+            memory_bus.set_io_reg_direct(addr, self.read_byte(addr));
+        }
+    }
+
+    /// Re-applies the register values held on the bus, e.g. after restoring a save
+    /// state. Write-only bits (frequency low bytes, lengths) read back as 1s, so notes
+    /// may be off-pitch until the game writes them again. Nothing is triggered.
+    pub fn restore_registers(&mut self, memory_bus: &mut MemoryBus) {
+        let nr52 = memory_bus.get_io_reg(NR52_ADDR);
+        self.write_byte(NR52_ADDR, nr52, memory_bus);
+        for addr in NR10_ADDR..NR52_ADDR {
+            let mut value = memory_bus.get_io_reg(addr);
+            if matches!(addr, NR14_ADDR | NR24_ADDR | NR34_ADDR | NR44_ADDR) {
+                value &= 0x7F; // Don't trigger
+            }
+            self.write_byte(addr, value, memory_bus);
+        }
+        self.sync_registers(memory_bus);
     }
 
     /// Reads a byte from an APU register address (0xFF10-0xFF26).
@@ -209,17 +363,16 @@ impl Apu {
                 if self.apu_enabled {
                     nr52 |= 0x80;
                 }
-                // TODO: Update placeholder active flags from real channel state
-                if self.ch1_active {
+                if self.ch1.enabled {
                     nr52 |= 0x01;
                 }
-                if self.ch2_active {
+                if self.ch2.enabled {
                     nr52 |= 0x02;
                 }
-                if self.ch3_active {
+                if self.ch3.enabled {
                     nr52 |= 0x04;
                 }
-                if self.ch4_active {
+                if self.ch4.enabled {
                     nr52 |= 0x08;
                 }
                 nr52 | 0x70 // Bits 4-6 read as 1
@@ -261,54 +414,67 @@ impl Apu {
         match addr {
             // --- Channel 1 ---
             NR10_ADDR => {
-                self.nr10 = value; /* TODO: Update sweep state */
+                self.nr10 = value;
+                self.ch1.write_sweep(value);
             }
             NR11_ADDR => {
-                self.nr11 = value; /* TODO: Update length timer (bits 0-5), duty cycle (bits 6-7) */
+                self.nr11 = value;
+                self.ch1.write_length_duty(value);
             }
             NR12_ADDR => {
-                self.nr12 = value; /* TODO: Update envelope state, check DAC power */
+                self.nr12 = value;
+                self.ch1.write_envelope(value);
             }
-            NR13_ADDR => { /* Write-only, update internal freq state */ }
+            NR13_ADDR => self.ch1.write_frequency_lo(value),
             NR14_ADDR => {
-                self.nr14 = value; /* TODO: Update freq hi, handle TRIGGER(7), update length enable(6) */
+                self.nr14 = value;
+                self.ch1.write_frequency_hi(value); // Handles TRIGGER(7) and length enable(6)
             }
 
             // --- Channel 2 ---
             NR21_ADDR => {
-                self.nr21 = value; /* TODO: Update length timer, duty cycle */
+                self.nr21 = value;
+                self.ch2.write_length_duty(value);
             }
             NR22_ADDR => {
-                self.nr22 = value; /* TODO: Update envelope state, check DAC power */
+                self.nr22 = value;
+                self.ch2.write_envelope(value);
             }
-            NR23_ADDR => { /* Write-only, update internal freq state */ }
+            NR23_ADDR => self.ch2.write_frequency_lo(value),
             NR24_ADDR => {
-                self.nr24 = value; /* TODO: Update freq hi, handle TRIGGER(7), update length enable(6) */
+                self.nr24 = value;
+                self.ch2.write_frequency_hi(value); // Handles TRIGGER(7) and length enable(6)
             }
 
             // --- Channel 3 ---
             NR30_ADDR => {
-                self.nr30 = value; /* TODO: Update DAC enable (bit 7) */
+                self.nr30 = value;
+                self.ch3.write_dac(value);
             }
-            NR31_ADDR => { /* Write-only, update internal length state */ }
+            NR31_ADDR => self.ch3.write_length(value),
             NR32_ADDR => {
-                self.nr32 = value; /* TODO: Update volume level (bits 5-6) */
+                self.nr32 = value;
+                self.ch3.write_volume(value);
             }
-            NR33_ADDR => { /* Write-only, update internal freq state */ }
+            NR33_ADDR => self.ch3.write_frequency_lo(value),
             NR34_ADDR => {
-                self.nr34 = value; /* TODO: Update freq hi, handle TRIGGER(7), update length enable(6) */
+                self.nr34 = value;
+                self.ch3.write_frequency_hi(value); // Handles TRIGGER(7) and length enable(6)
             }
 
             // --- Channel 4 ---
-            NR41_ADDR => { /* Write-only, update internal length state */ }
+            NR41_ADDR => self.ch4.write_length(value),
             NR42_ADDR => {
-                self.nr42 = value; /* TODO: Update envelope state, check DAC power */
+                self.nr42 = value;
+                self.ch4.write_envelope(value);
             }
             NR43_ADDR => {
-                self.nr43 = value; /* TODO: Update clock shift, width mode, dividing ratio */
+                self.nr43 = value;
+                self.ch4.write_polynomial(value);
             }
             NR44_ADDR => {
-                self.nr44 = value; /* TODO: Handle TRIGGER(7), update length enable(6) */
+                self.nr44 = value;
+                self.ch4.write_control(value); // Handles TRIGGER(7) and length enable(6)
             }
 
             // --- Master Control ---
@@ -319,12 +485,65 @@ impl Apu {
         }
     }
 
-    // --- Helper Functions for Frame Sequencer (Keep placeholders) ---
-    fn clock_length_counters(&mut self, _memory_bus: &MemoryBus) { /* TODO */
+    /// Restarts a channel (1-4) as if bit 7 of its NRx4 register had been written.
+    /// Re-triggering an active channel reloads an expired length counter, restarts the
+    /// envelope at its initial volume and reloads the frequency timer.
+    pub fn trigger(&mut self, channel: u8) {
+        match channel {
+            1 => self.ch1.trigger(),
+            2 => self.ch2.trigger(),
+            3 => self.ch3.trigger(),
+            4 => self.ch4.trigger(),
+            _ => {}
+        }
+    }
+
+    /// Whether a channel (1-4) is currently playing, as reported in NR52.
+    pub fn channel_active(&self, channel: u8) -> bool {
+        match channel {
+            1 => self.ch1.enabled,
+            2 => self.ch2.enabled,
+            3 => self.ch3.enabled,
+            4 => self.ch4.enabled,
+            _ => false,
+        }
     }
-    fn clock_sweep_unit(&mut self, _memory_bus: &MemoryBus) { /* TODO */
+
+    /// Current envelope volume (0-15) of channel 1, 2 or 4.
+    pub fn channel_volume(&self, channel: u8) -> u8 {
+        match channel {
+            1 => self.ch1.envelope.volume(),
+            2 => self.ch2.envelope.volume(),
+            4 => self.ch4.envelope.volume(),
+            _ => 0,
+        }
     }
-    fn clock_envelope_units(&mut self, _memory_bus: &MemoryBus) { /* TODO */
+
+    /// Remaining length-counter ticks of a channel (1-4).
+    pub fn channel_length(&self, channel: u8) -> u16 {
+        match channel {
+            1 => self.ch1.length.value(),
+            2 => self.ch2.length.value(),
+            3 => self.ch3.length.value(),
+            4 => self.ch4.length.value(),
+            _ => 0,
+        }
+    }
+
+    // --- Helper Functions for Frame Sequencer ---
+    fn clock_length_counters(&mut self, _memory_bus: &MemoryBus) {
+        self.ch1.clock_length();
+        self.ch2.clock_length();
+        self.ch3.clock_length();
+        self.ch4.clock_length();
+    }
+    fn clock_sweep_unit(&mut self, _memory_bus: &MemoryBus) {
+        self.ch1.clock_sweep();
+    }
+    fn clock_envelope_units(&mut self, _memory_bus: &MemoryBus) {
+        self.ch1.clock_envelope();
+        self.ch2.clock_envelope();
+        self.ch4.clock_envelope();
     }
 
     /// Resets APU registers (mirrored state) and internal state when NR52 bit 7 is written to 0.
@@ -353,11 +572,11 @@ impl Apu {
         // self.nr50 = NR50_DEFAULT;
         // self.nr51 = NR51_DEFAULT;
 
-        // TODO: Reset *all* internal channel states (timers, counters, volume, freq, etc.)
-        self.ch1_active = false;
-        self.ch2_active = false;
-        self.ch3_active = false;
-        self.ch4_active = false;
+        // Wave RAM itself is unaffected; it lives on the bus
+        self.ch1 = SquareChannel::new();
+        self.ch2 = SquareChannel::new();
+        self.ch3 = WaveChannel::new();
+        self.ch4 = NoiseChannel::new();
 
         // Clear actual registers on the bus *if required* by hardware spec
         // This ensures consistency if other components read the bus directly.
@@ -368,7 +587,4 @@ impl Apu {
         // memory_bus.write_byte_direct(NR50_ADDR, self.nr50);
         // memory_bus.write_byte_direct(NR51_ADDR, self.nr51);
     }
-
-    // --- TODO: Add Sample Generation Logic ---
-    // --- TODO: Add detailed channel state structs and methods ---
 }
@@ -0,0 +1,140 @@
+//! Noise channel state (channel 4): a linear-feedback shift register clocked by NR43.
+
+use super::units::{Envelope, LengthCounter, dac_enabled};
+use serde::{Deserialize, Serialize};
+
+/// Base divisors selected by NR43 bits 0-2, before the clock shift is applied.
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct NoiseChannel {
+    pub(super) enabled: bool,
+    dac_enabled: bool,
+    pub(super) length: LengthCounter,
+    pub(super) envelope: Envelope,
+    clock_shift: u8,      // NR43 bits 4-7
+    width_7bit: bool,     // NR43 bit 3
+    divisor_code: u8,     // NR43 bits 0-2
+    frequency_timer: u32, // T-cycles until the LFSR is clocked
+    lfsr: u16,            // 15-bit shift register; output is bit 0 inverted
+}
+
+impl NoiseChannel {
+    pub(super) fn new() -> Self {
+        NoiseChannel {
+            enabled: false,
+            dac_enabled: false,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            clock_shift: 0,
+            width_7bit: false,
+            divisor_code: 0,
+            frequency_timer: 0,
+            lfsr: 0x7FFF,
+        }
+    }
+
+    /// NR41: length load (bits 0-5).
+    pub(super) fn write_length(&mut self, value: u8) {
+        self.length.load(value & 0x3F);
+    }
+
+    /// NR42: volume envelope. Clearing the DAC bits turns the channel off immediately.
+    pub(super) fn write_envelope(&mut self, value: u8) {
+        self.envelope.load(value);
+        self.dac_enabled = dac_enabled(value);
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+    }
+
+    /// NR43: clock shift, LFSR width and divisor code.
+    pub(super) fn write_polynomial(&mut self, value: u8) {
+        self.clock_shift = value >> 4;
+        self.width_7bit = (value & 0x08) != 0;
+        self.divisor_code = value & 0x07;
+    }
+
+    /// NR44: length enable (bit 6) and trigger (bit 7).
+    pub(super) fn write_control(&mut self, value: u8) {
+        self.length.enabled = (value & 0x40) != 0;
+        if (value & 0x80) != 0 {
+            self.trigger();
+        }
+    }
+
+    /// Restarts the channel with the LFSR reloaded to all ones.
+    pub(super) fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger();
+        self.frequency_timer = self.timer_period();
+        self.envelope.trigger();
+        self.lfsr = 0x7FFF;
+    }
+
+    /// Advances the frequency timer by the given number of T-cycles.
+    pub(super) fn tick(&mut self, cycles: u32) {
+        // Shifts 14 and 15 stop the LFSR from being clocked at all
+        if !self.enabled || self.clock_shift >= 14 {
+            return;
+        }
+        let mut remaining = cycles;
+        while remaining > 0 {
+            if self.frequency_timer > remaining {
+                self.frequency_timer -= remaining;
+                break;
+            }
+            remaining -= self.frequency_timer;
+            self.frequency_timer = self.timer_period();
+            self.lfsr = step_lfsr(self.lfsr, self.width_7bit);
+        }
+    }
+
+    /// Current digital output (0-15): the envelope volume while LFSR bit 0 is clear.
+    pub(super) fn amplitude(&self) -> u8 {
+        if self.enabled && self.lfsr & 1 == 0 {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    fn timer_period(&self) -> u32 {
+        DIVISORS[self.divisor_code as usize] << self.clock_shift
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Checks and masks channel state read from a save state.
+    pub(super) fn validate_loaded(&mut self) -> Result<(), String> {
+        self.length.validate_loaded(64)?;
+        self.envelope.validate_loaded();
+        self.clock_shift &= 0x0F;
+        self.divisor_code &= 0x07;
+        self.lfsr &= 0x7FFF;
+        Ok(())
+    }
+}
+
+/// Clocks the LFSR once: bit 0 XOR bit 1 is shifted in at bit 14 as everything moves
+/// right, and in 7-bit mode the same bit is also copied into bit 6.
+fn step_lfsr(lfsr: u16, width_7bit: bool) -> u16 {
+    let feedback = (lfsr ^ (lfsr >> 1)) & 1;
+    let mut lfsr = (lfsr >> 1) | (feedback << 14);
+    if width_7bit {
+        lfsr = (lfsr & !(1 << 6)) | (feedback << 6);
+    }
+    lfsr
+}
@@ -0,0 +1,145 @@
+//! Pulse (square wave) channel state, used for channels 1 and 2.
+
+use super::units::{Envelope, LengthCounter, Sweep, dac_enabled};
+use serde::{Deserialize, Serialize};
+
+/// Waveforms for the four duty settings (12.5%, 25%, 50%, 75%), one bit per step.
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct SquareChannel {
+    pub(super) enabled: bool,
+    dac_enabled: bool,
+    pub(super) length: LengthCounter,
+    pub(super) envelope: Envelope,
+    sweep: Sweep,         // NR10, channel 1 only (channel 2's never runs)
+    frequency: u16,       // 11-bit value from NRx3/NRx4
+    frequency_timer: u32, // T-cycles until the waveform advances one step
+    duty: u8,             // NRx1 bits 6-7
+    duty_position: u8,    // Current step (0-7) within the duty pattern
+}
+
+impl SquareChannel {
+    pub(super) fn new() -> Self {
+        SquareChannel {
+            enabled: false,
+            dac_enabled: false,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            sweep: Sweep::default(),
+            frequency: 0,
+            frequency_timer: 0,
+            duty: 0,
+            duty_position: 0,
+        }
+    }
+
+    /// NR10 (channel 1 only): sweep period (bits 4-6), direction (bit 3) and shift (bits 0-2).
+    pub(super) fn write_sweep(&mut self, value: u8) {
+        if self.sweep.load(value) {
+            self.enabled = false;
+        }
+    }
+
+    /// NRx1: length load (bits 0-5) and duty (bits 6-7).
+    pub(super) fn write_length_duty(&mut self, value: u8) {
+        self.length.load(value & 0x3F);
+        self.duty = value >> 6;
+    }
+
+    /// NRx2: volume envelope. Clearing the DAC bits turns the channel off immediately.
+    pub(super) fn write_envelope(&mut self, value: u8) {
+        self.envelope.load(value);
+        self.dac_enabled = dac_enabled(value);
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+    }
+
+    /// NRx3: frequency low bits.
+    pub(super) fn write_frequency_lo(&mut self, value: u8) {
+        self.frequency = (self.frequency & 0x0700) | value as u16;
+    }
+
+    /// NRx4: frequency high bits, length enable (bit 6) and trigger (bit 7).
+    pub(super) fn write_frequency_hi(&mut self, value: u8) {
+        self.frequency = (self.frequency & 0x00FF) | (((value & 0x07) as u16) << 8);
+        self.length.enabled = (value & 0x40) != 0;
+        if (value & 0x80) != 0 {
+            self.trigger();
+        }
+    }
+
+    /// Restarts the channel. Valid whether or not it is already playing.
+    pub(super) fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger();
+        self.frequency_timer = self.timer_period();
+        self.envelope.trigger();
+        if self.sweep.trigger(self.frequency) {
+            self.enabled = false;
+        }
+    }
+
+    /// Advances the frequency timer by the given number of T-cycles.
+    pub(super) fn tick(&mut self, cycles: u32) {
+        let mut remaining = cycles;
+        while remaining > 0 {
+            if self.frequency_timer > remaining {
+                self.frequency_timer -= remaining;
+                break;
+            }
+            remaining -= self.frequency_timer;
+            self.frequency_timer = self.timer_period();
+            self.duty_position = (self.duty_position + 1) % 8;
+        }
+    }
+
+    /// Current digital output (0-15): the envelope volume while the duty step is high.
+    pub(super) fn amplitude(&self) -> u8 {
+        let high = (DUTY_PATTERNS[self.duty as usize] >> (7 - self.duty_position)) & 1 != 0;
+        if self.enabled && high {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    fn timer_period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 4
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub(super) fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Sweep step: retunes the channel, or silences it if the frequency overflowed.
+    pub(super) fn clock_sweep(&mut self) {
+        match self.sweep.clock() {
+            Ok(Some(frequency)) => self.frequency = frequency,
+            Ok(None) => {}
+            Err(()) => self.enabled = false,
+        }
+    }
+
+    /// Checks and masks channel state read from a save state.
+    pub(super) fn validate_loaded(&mut self) -> Result<(), String> {
+        self.length.validate_loaded(64)?;
+        self.envelope.validate_loaded();
+        self.sweep.validate_loaded();
+        self.frequency &= 0x07FF;
+        self.duty &= 0x03;
+        self.duty_position %= 8;
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+//! Building blocks shared by several APU channels.
+
+use serde::{Deserialize, Serialize};
+
+/// Length counter. Clocked at 256 Hz by the frame sequencer; silences the
+/// channel when it reaches zero while enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct LengthCounter {
+    counter: u16,
+    max: u16, // 64 for channels 1, 2 and 4; 256 for channel 3
+    pub(super) enabled: bool,
+}
+
+impl LengthCounter {
+    pub(super) fn new(max: u16) -> Self {
+        LengthCounter {
+            counter: 0,
+            max,
+            enabled: false,
+        }
+    }
+
+    /// Loads the length timer from the NRx1 length bits.
+    pub(super) fn load(&mut self, length_data: u8) {
+        self.counter = self.max - (length_data as u16 & (self.max - 1));
+    }
+
+    /// Trigger behavior: an expired counter is reloaded to its maximum.
+    pub(super) fn trigger(&mut self) {
+        if self.counter == 0 {
+            self.counter = self.max;
+        }
+    }
+
+    /// Clocks the counter. Returns `true` if it just expired and the channel should turn off.
+    pub(super) fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        false
+    }
+
+    pub(super) fn value(&self) -> u16 {
+        self.counter
+    }
+
+    /// Checks a counter read from a save state against the channel's maximum.
+    pub(super) fn validate_loaded(&self, max: u16) -> Result<(), String> {
+        if self.max != max || self.counter > max {
+            return Err(format!(
+                "Invalid length counter in save state: {}/{}",
+                self.counter, self.max
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Volume envelope (NRx2). Clocked at 64 Hz by frame sequencer step 7.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct Envelope {
+    initial_volume: u8,
+    increase: bool,
+    period: u8,
+    timer: u8,
+    volume: u8,
+}
+
+impl Envelope {
+    /// Latches the NRx2 settings. They take effect on the next trigger.
+    pub(super) fn load(&mut self, nrx2: u8) {
+        self.initial_volume = nrx2 >> 4;
+        self.increase = (nrx2 & 0x08) != 0;
+        self.period = nrx2 & 0x07;
+    }
+
+    /// Trigger behavior: restart at the initial volume with a fresh period.
+    pub(super) fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.reload_value();
+    }
+
+    pub(super) fn clock(&mut self) {
+        if self.period == 0 {
+            return; // Period 0 disables the envelope
+        }
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = self.reload_value();
+            if self.increase && self.volume < 15 {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+
+    pub(super) fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// Clears bits a save state may have set outside the NRx2 fields.
+    pub(super) fn validate_loaded(&mut self) {
+        self.initial_volume &= 0x0F;
+        self.period &= 0x07;
+        self.volume &= 0x0F;
+    }
+
+    // The envelope timer treats a period of 0 as 8.
+    fn reload_value(&self) -> u8 {
+        if self.period == 0 { 8 } else { self.period }
+    }
+}
+
+/// Frequency sweep (NR10), channel 1 only. Clocked at 128 Hz by frame sequencer steps
+/// 2 and 6; works on a shadow copy of the frequency taken at trigger.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+    timer: u8,
+    enabled: bool,
+    shadow_frequency: u16,
+    negate_used: bool, // A subtraction happened since the last trigger
+}
+
+impl Sweep {
+    /// Latches NR10. Returns `true` if the channel must turn off: leaving subtraction
+    /// mode after it has been used since the last trigger disables it.
+    pub(super) fn load(&mut self, nr10: u8) -> bool {
+        self.period = (nr10 >> 4) & 0x07;
+        self.negate = (nr10 & 0x08) != 0;
+        self.shift = nr10 & 0x07;
+        self.negate_used && !self.negate
+    }
+
+    /// Trigger behavior: copies the frequency and, if shift is non-zero, runs the
+    /// overflow check right away. Returns `true` if that check disables the channel.
+    pub(super) fn trigger(&mut self, frequency: u16) -> bool {
+        self.shadow_frequency = frequency;
+        self.timer = self.reload_value();
+        self.enabled = self.period != 0 || self.shift != 0;
+        self.negate_used = false;
+        self.shift != 0 && self.next_frequency() > 2047
+    }
+
+    /// Clocks the sweep. Returns the new frequency to play, or `Err(())` if the
+    /// sweep overflowed past 2047 and the channel must turn off.
+    pub(super) fn clock(&mut self) -> Result<Option<u16>, ()> {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer != 0 {
+            return Ok(None);
+        }
+        self.timer = self.reload_value();
+        if !self.enabled || self.period == 0 {
+            return Ok(None);
+        }
+
+        let frequency = self.next_frequency();
+        if frequency > 2047 {
+            return Err(());
+        }
+        if self.shift == 0 {
+            return Ok(None);
+        }
+        self.shadow_frequency = frequency;
+        // The new frequency is checked for overflow again but not written back
+        if self.next_frequency() > 2047 {
+            return Err(());
+        }
+        Ok(Some(frequency))
+    }
+
+    /// Clears bits a save state may have set outside the NR10 fields.
+    pub(super) fn validate_loaded(&mut self) {
+        self.period &= 0x07;
+        self.shift &= 0x07;
+        self.shadow_frequency &= 0x07FF;
+    }
+
+    /// The shadow frequency shifted right by `shift`, added or subtracted.
+    fn next_frequency(&mut self) -> u16 {
+        let offset = self.shadow_frequency >> self.shift;
+        if self.negate {
+            self.negate_used = true;
+            self.shadow_frequency - offset
+        } else {
+            self.shadow_frequency + offset
+        }
+    }
+
+    // Like the envelope, the sweep timer treats a period of 0 as 8.
+    fn reload_value(&self) -> u8 {
+        if self.period == 0 { 8 } else { self.period }
+    }
+}
+
+/// The DAC is powered whenever any of NRx2 bits 3-7 are set.
+pub(super) fn dac_enabled(nrx2: u8) -> bool {
+    (nrx2 & 0xF8) != 0
+}
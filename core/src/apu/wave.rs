@@ -0,0 +1,143 @@
+//! Wave channel state (channel 3): plays 32 4-bit samples from wave RAM.
+
+use super::units::LengthCounter;
+use serde::{Deserialize, Serialize};
+
+/// Wave RAM size in bytes; each byte holds two samples, high nibble first.
+const WAVE_RAM_SIZE: usize = 16;
+const WAVE_SAMPLES: u8 = (WAVE_RAM_SIZE * 2) as u8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct WaveChannel {
+    pub(super) enabled: bool,
+    dac_enabled: bool, // NR30 bit 7
+    pub(super) length: LengthCounter,
+    volume_code: u8,      // NR32 bits 5-6: 0 = mute, 1 = 100%, 2 = 50%, 3 = 25%
+    frequency: u16,       // 11-bit value from NR33/NR34
+    frequency_timer: u32, // T-cycles until the next sample is read
+    position: u8,         // Index (0-31) of the sample last read
+    sample_buffer: u8,    // Sample last read; what the channel outputs
+    pub(super) wave_ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl WaveChannel {
+    pub(super) fn new() -> Self {
+        WaveChannel {
+            enabled: false,
+            dac_enabled: false,
+            length: LengthCounter::new(256),
+            volume_code: 0,
+            frequency: 0,
+            frequency_timer: 0,
+            position: 0,
+            sample_buffer: 0,
+            wave_ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+
+    /// NR30: DAC power (bit 7). Powering it off turns the channel off immediately.
+    pub(super) fn write_dac(&mut self, value: u8) {
+        self.dac_enabled = (value & 0x80) != 0;
+        if !self.dac_enabled {
+            self.enabled = false;
+        }
+    }
+
+    /// NR31: length load (all 8 bits).
+    pub(super) fn write_length(&mut self, value: u8) {
+        self.length.load(value);
+    }
+
+    /// NR32: output level (bits 5-6).
+    pub(super) fn write_volume(&mut self, value: u8) {
+        self.volume_code = (value >> 5) & 0x03;
+    }
+
+    /// NR33: frequency low bits.
+    pub(super) fn write_frequency_lo(&mut self, value: u8) {
+        self.frequency = (self.frequency & 0x0700) | value as u16;
+    }
+
+    /// NR34: frequency high bits, length enable (bit 6) and trigger (bit 7).
+    pub(super) fn write_frequency_hi(&mut self, value: u8) {
+        self.frequency = (self.frequency & 0x00FF) | (((value & 0x07) as u16) << 8);
+        self.length.enabled = (value & 0x40) != 0;
+        if (value & 0x80) != 0 {
+            self.trigger();
+        }
+    }
+
+    /// Restarts the channel from the start of the wave table. As on hardware the
+    /// sample buffer isn't refilled, so the first sample actually read is index 1.
+    pub(super) fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger();
+        self.frequency_timer = self.timer_period();
+        self.position = 0;
+    }
+
+    /// Advances the frequency timer by the given number of T-cycles.
+    pub(super) fn tick(&mut self, cycles: u32) {
+        if !self.enabled {
+            return;
+        }
+        let mut remaining = cycles;
+        while remaining > 0 {
+            if self.frequency_timer > remaining {
+                self.frequency_timer -= remaining;
+                break;
+            }
+            remaining -= self.frequency_timer;
+            self.frequency_timer = self.timer_period();
+            self.position = (self.position + 1) % WAVE_SAMPLES;
+            self.sample_buffer = self.sample_at(self.position);
+        }
+    }
+
+    /// Wave RAM byte the channel is reading while it plays, `None` when stopped.
+    /// The CPU only reaches this byte, whatever wave RAM address it uses.
+    pub(super) fn playing_byte(&self) -> Option<u8> {
+        self.enabled.then_some(self.position / 2)
+    }
+
+    /// Current digital output (0-15): the buffered sample shifted by the NR32 level.
+    pub(super) fn amplitude(&self) -> u8 {
+        if !self.enabled || self.volume_code == 0 {
+            return 0;
+        }
+        self.sample_buffer >> (self.volume_code - 1)
+    }
+
+    pub(super) fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    fn sample_at(&self, position: u8) -> u8 {
+        let byte = self.wave_ram[(position / 2) as usize];
+        if position & 1 == 0 {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        }
+    }
+
+    fn timer_period(&self) -> u32 {
+        (2048 - self.frequency as u32) * 2
+    }
+
+    pub(super) fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// Checks and masks channel state read from a save state.
+    pub(super) fn validate_loaded(&mut self) -> Result<(), String> {
+        self.length.validate_loaded(256)?;
+        self.volume_code &= 0x03;
+        self.frequency &= 0x07FF;
+        self.position %= WAVE_SAMPLES;
+        self.sample_buffer &= 0x0F;
+        Ok(())
+    }
+}
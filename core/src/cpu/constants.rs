@@ -17,6 +17,9 @@ pub const TIMER_VECTOR: u16 = 0x0050;
 pub const SERIAL_VECTOR: u16 = 0x0058;
 pub const JOYPAD_VECTOR: u16 = 0x0060;
 
+// The CPU stalls for 2050 M-cycles while a CGB speed switch takes effect
+pub const SPEED_SWITCH_CYCLES: u16 = 2050 * 4;
+
 // Related Memory Addresses (defined in memory_map.rs, but useful here for context)
 // pub use crate::memory_map::{INTERRUPT_ENABLE_REGISTER as IE_REGISTER, IF_ADDR as IF_REGISTER};
 // Note: It's generally better to import these from memory_map where needed,
@@ -1,14 +1,33 @@
-use crate::cpu::{Cpu, CpuResult};
+use crate::cpu::{Cpu, CpuResult, Ticker};
 use crate::memory_bus::MemoryBus;
 use lazy_static::lazy_static;
 
+/// An opcode handler. `Timed` handlers also get the tick callback so they can let the
+/// rest of the system catch up between their bus accesses.
+#[derive(Clone, Copy)]
+pub enum Execute {
+    Plain(fn(&mut Cpu, &mut MemoryBus) -> CpuResult<u16>),
+    Timed(fn(&mut Cpu, &mut MemoryBus, &mut Ticker) -> CpuResult<u16>),
+}
+
+impl Execute {
+    /// Runs the handler; plain handlers ignore `tick`.
+    #[inline(always)]
+    pub fn run(self, cpu: &mut Cpu, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        match self {
+            Execute::Plain(f) => f(cpu, bus),
+            Execute::Timed(f) => f(cpu, bus, tick),
+        }
+    }
+}
+
 // Definition from your previous code block
 #[derive(Clone)]
 pub struct Instruction {
     pub mnemonic: &'static str,
     pub length: u8,
-    pub cycles: u8, // Base T-cycles (minimum for conditional)
-    pub execute: fn(&mut Cpu, &mut MemoryBus) -> CpuResult<u16>, // Returns *additional* T-cycles or Error
+    pub cycles: u8,       // Base T-cycles (minimum for conditional)
+    pub execute: Execute, // Returns *additional* T-cycles or Error
 }
 
 impl Instruction {
@@ -22,7 +41,21 @@ impl Instruction {
             mnemonic,
             length,
             cycles,
-            execute,
+            execute: Execute::Plain(execute),
+        }
+    }
+
+    pub const fn new_timed(
+        mnemonic: &'static str,
+        length: u8,
+        cycles: u8,
+        execute: fn(&mut Cpu, &mut MemoryBus, &mut Ticker) -> CpuResult<u16>,
+    ) -> Self {
+        Instruction {
+            mnemonic,
+            length,
+            cycles,
+            execute: Execute::Timed(execute),
         }
     }
 
@@ -32,7 +65,7 @@ impl Instruction {
             mnemonic: "INVALID",
             length: 1,
             cycles: 4, // Base cycles consumed even on error path? Or handle in step? Let's assume 4.
-            execute: Cpu::handle_invalid_opcode, // Points to the error handler
+            execute: Execute::Plain(Cpu::handle_invalid_opcode), // Points to the error handler
         }
     }
 }
@@ -43,6 +76,12 @@ macro_rules! instr {
         Instruction::new($mne, $len, $cyc, $exec)
     };
 }
+// Same, for handlers that tick the system between their bus accesses
+macro_rules! instr_timed {
+    ($mne:expr, $len:expr, $cyc:expr, $exec:expr) => {
+        Instruction::new_timed($mne, $len, $cyc, $exec)
+    };
+}
 macro_rules! invalid {
     () => {
         Instruction::invalid()
@@ -278,21 +317,21 @@ lazy_static! {
         instr!("JP NZ, a16", 3, 12, Cpu::op_jp_nz_a16), // C2 JP NZ, a16
         instr!("JP a16", 3, 16, Cpu::op_jp_a16),        // C3 JP a16
         // --- 0xC4 --- CALL NZ,a16 (12 cycles if no call, 24 if call)
-        instr!("CALL NZ, a16", 3, 12, Cpu::op_call_nz_a16), // C4 CALL NZ, a16
-        instr!("PUSH BC", 1, 16, Cpu::op_push_bc),      // C5 PUSH BC
+        instr_timed!("CALL NZ, a16", 3, 12, Cpu::op_call_nz_a16), // C4 CALL NZ, a16
+        instr_timed!("PUSH BC", 1, 16, Cpu::op_push_bc), // C5 PUSH BC
         instr!("ADD A, d8", 2, 8, Cpu::op_add_a_d8),    // C6 ADD A, d8
-        instr!("RST 00H", 1, 16, Cpu::op_rst_00h),      // C7 RST 00H
+        instr_timed!("RST 00H", 1, 16, Cpu::op_rst_00h), // C7 RST 00H
         // --- 0xC8 --- RET Z (8 cycles if no return, 20 if return)
         instr!("RET Z", 1, 8, Cpu::op_ret_z),           // C8 RET Z
         instr!("RET", 1, 16, Cpu::op_ret),              // C9 RET
         // --- 0xCA --- JP Z,a16 (12 cycles if no jump, 16 if jump)
         instr!("JP Z, a16", 3, 12, Cpu::op_jp_z_a16),   // CA JP Z, a16
-        instr!("PREFIX CB", 1, 4, Cpu::op_prefix_cb),   // CB PREFIX CB
+        instr!("PREFIX CB", 2, 4, Cpu::op_prefix_cb),   // CB PREFIX CB
         // --- 0xCC --- CALL Z,a16 (12 cycles if no call, 24 if call)
-        instr!("CALL Z, a16", 3, 12, Cpu::op_call_z_a16), // CC CALL Z, a16
-        instr!("CALL a16", 3, 24, Cpu::op_call_a16),    // CD CALL a16
+        instr_timed!("CALL Z, a16", 3, 12, Cpu::op_call_z_a16), // CC CALL Z, a16
+        instr_timed!("CALL a16", 3, 24, Cpu::op_call_a16), // CD CALL a16
         instr!("ADC A, d8", 2, 8, Cpu::op_adc_a_d8),    // CE ADC A, d8
-        instr!("RST 08H", 1, 16, Cpu::op_rst_08h),      // CF RST 08H
+        instr_timed!("RST 08H", 1, 16, Cpu::op_rst_08h), // CF RST 08H
 
         // --- 0xD0 --- RET NC (8 cycles if no return, 20 if return)
         instr!("RET NC", 1, 8, Cpu::op_ret_nc),         // D0 RET NC
@@ -301,10 +340,10 @@ lazy_static! {
         instr!("JP NC, a16", 3, 12, Cpu::op_jp_nc_a16), // D2 JP NC, a16
         invalid!(),                                     // D3 Invalid
         // --- 0xD4 --- CALL NC,a16 (12 cycles if no call, 24 if call)
-        instr!("CALL NC, a16", 3, 12, Cpu::op_call_nc_a16), // D4 CALL NC, a16
-        instr!("PUSH DE", 1, 16, Cpu::op_push_de),      // D5 PUSH DE
+        instr_timed!("CALL NC, a16", 3, 12, Cpu::op_call_nc_a16), // D4 CALL NC, a16
+        instr_timed!("PUSH DE", 1, 16, Cpu::op_push_de), // D5 PUSH DE
         instr!("SUB A, d8", 2, 8, Cpu::op_sub_a_d8),    // D6 SUB A, d8
-        instr!("RST 10H", 1, 16, Cpu::op_rst_10h),      // D7 RST 10H
+        instr_timed!("RST 10H", 1, 16, Cpu::op_rst_10h), // D7 RST 10H
         // --- 0xD8 --- RET C (8 cycles if no return, 20 if return)
         instr!("RET C", 1, 8, Cpu::op_ret_c),           // D8 RET C
         instr!("RETI", 1, 16, Cpu::op_reti),            // D9 RETI
@@ -312,10 +351,10 @@ lazy_static! {
         instr!("JP C, a16", 3, 12, Cpu::op_jp_c_a16),   // DA JP C, a16
         invalid!(),                                     // DB Invalid
         // --- 0xDC --- CALL C,a16 (12 cycles if no call, 24 if call)
-        instr!("CALL C, a16", 3, 12, Cpu::op_call_c_a16), // DC CALL C, a16
+        instr_timed!("CALL C, a16", 3, 12, Cpu::op_call_c_a16), // DC CALL C, a16
         invalid!(),                                     // DD Invalid
         instr!("SBC A, d8", 2, 8, Cpu::op_sbc_a_d8),    // DE SBC A, d8
-        instr!("RST 18H", 1, 16, Cpu::op_rst_18h),      // DF RST 18H
+        instr_timed!("RST 18H", 1, 16, Cpu::op_rst_18h), // DF RST 18H
 
         // --- 0xE0 ---
         instr!("LDH (a8), A", 2, 12, Cpu::op_ldh_a8_a), // E0 LDH (a8), A
@@ -323,9 +362,9 @@ lazy_static! {
         instr!("LD (C), A", 1, 8, Cpu::op_ld_cp_a),     // E2 LD (C), A ; Note: Mnemonic uses C not (C)
         invalid!(),                                     // E3 Invalid
         invalid!(),                                     // E4 Invalid
-        instr!("PUSH HL", 1, 16, Cpu::op_push_hl),      // E5 PUSH HL
+        instr_timed!("PUSH HL", 1, 16, Cpu::op_push_hl), // E5 PUSH HL
         instr!("AND A, d8", 2, 8, Cpu::op_and_a_d8),    // E6 AND A, d8
-        instr!("RST 20H", 1, 16, Cpu::op_rst_20h),      // E7 RST 20H
+        instr_timed!("RST 20H", 1, 16, Cpu::op_rst_20h), // E7 RST 20H
         instr!("ADD SP, r8", 2, 16, Cpu::op_add_sp_r8), // E8 ADD SP, r8
         instr!("JP HL", 1, 4, Cpu::op_jp_hl),           // E9 JP HL ; Mnemonic sometimes (HL)
         instr!("LD (a16), A", 3, 16, Cpu::op_ld_a16_a), // EA LD (a16), A
@@ -333,7 +372,7 @@ lazy_static! {
         invalid!(),                                     // EC Invalid
         invalid!(),                                     // ED Invalid
         instr!("XOR A, d8", 2, 8, Cpu::op_xor_a_d8),    // EE XOR A, d8
-        instr!("RST 28H", 1, 16, Cpu::op_rst_28h),      // EF RST 28H
+        instr_timed!("RST 28H", 1, 16, Cpu::op_rst_28h), // EF RST 28H
 
         // --- 0xF0 ---
         instr!("LDH A, (a8)", 2, 12, Cpu::op_ldh_a_a8), // F0 LDH A, (a8)
@@ -341,9 +380,9 @@ lazy_static! {
         instr!("LD A, (C)", 1, 8, Cpu::op_ld_a_cp),     // F2 LD A, (C) ; Note: Mnemonic uses C not (C)
         instr!("DI", 1, 4, Cpu::op_di),                 // F3 DI
         invalid!(),                                     // F4 Invalid
-        instr!("PUSH AF", 1, 16, Cpu::op_push_af),      // F5 PUSH AF
+        instr_timed!("PUSH AF", 1, 16, Cpu::op_push_af), // F5 PUSH AF
         instr!("OR A, d8", 2, 8, Cpu::op_or_a_d8),      // F6 OR A, d8
-        instr!("RST 30H", 1, 16, Cpu::op_rst_30h),      // F7 RST 30H
+        instr_timed!("RST 30H", 1, 16, Cpu::op_rst_30h), // F7 RST 30H
         instr!("LD HL, SP+r8", 2, 12, Cpu::op_ld_hl_sp_r8), // F8 LD HL, SP+r8
         instr!("LD SP, HL", 1, 8, Cpu::op_ld_sp_hl),    // F9 LD SP, HL
         instr!("LD A, (a16)", 3, 16, Cpu::op_ld_a_a16), // FA LD A, (a16)
@@ -351,7 +390,7 @@ lazy_static! {
         invalid!(),                                     // FC Invalid
         invalid!(),                                     // FD Invalid
         instr!("CP A, d8", 2, 8, Cpu::op_cp_a_d8),      // FE CP A, d8
-        instr!("RST 38H", 1, 16, Cpu::op_rst_38h),      // FF RST 38H
+        instr_timed!("RST 38H", 1, 16, Cpu::op_rst_38h), // FF RST 38H
     ];
 
     // CB-prefixed instruction table (0x00 - 0xFF)
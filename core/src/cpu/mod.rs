@@ -8,6 +8,10 @@ use crate::memory_map::{
 };
 use instruction::{CB_INSTRUCTIONS, INSTRUCTIONS};
 use log;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use trace::TraceSlot;
 
 // Declare submodules
 mod constants;
@@ -19,15 +23,66 @@ mod ops_cb;
 mod ops_control;
 mod ops_load;
 mod ops_rot_shift;
+mod trace;
 
 // Re-export public constants if needed by external modules
 pub use constants::*;
+pub use trace::{TraceEntry, TraceMode, TraceWriter};
 
 // Type alias for CPU operation results
 pub type CpuResult<T> = Result<T, String>;
 
+/// Advances the rest of the system (PPU, timer, ...) by the given number of T-cycles.
+/// Handlers that support it call this between their bus accesses, so each access sees
+/// the hardware state of the moment it happens rather than of the instruction's start.
+pub type Ticker<'a> = dyn FnMut(&mut MemoryBus, u32) + 'a;
+
+/// The architectural CPU state: what a reference emulator can be compared against.
+/// Bookkeeping such as `total_cycles` and the last fetched opcode is left out, so two
+/// snapshots are equal exactly when the CPUs would behave the same from here on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuSnapshot {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    pub ime_scheduled: bool, // EI ran and IME turns on after the next instruction
+    pub halted: bool,
+    pub halt_bug: bool, // The next fetch won't advance PC past the opcode
+}
+
+impl fmt::Display for CpuSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} IME:{} EI:{} HALT:{} HALTBUG:{}",
+            self.a,
+            self.f,
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+            self.sp,
+            self.pc,
+            self.ime as u8,
+            self.ime_scheduled as u8,
+            self.halted as u8,
+            self.halt_bug as u8
+        )
+    }
+}
+
 /// Represents the Game Boy's SM83 CPU state and provides execution logic.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cpu {
     // --- 8-bit Registers ---
     a: u8, // Accumulator
@@ -44,15 +99,21 @@ pub struct Cpu {
     pc: u16, // Program Counter
 
     // --- CPU State Flags ---
-    ime: bool,            // Interrupt Master Enable flag (enabled/disabled)
-    halted: bool,         // CPU is in HALT state (waiting for interrupt)
-    stop_requested: bool, // CPU received STOP instruction (low power state)
-    ime_scheduled: bool,  // IME will be enabled after the next instruction
+    ime: bool,              // Interrupt Master Enable flag (enabled/disabled)
+    halted: bool,           // CPU is in HALT state (waiting for interrupt)
+    halt_bug: bool,         // Next fetch doesn't advance PC past the opcode (HALT bug)
+    stop_requested: bool,   // CPU received STOP instruction (low power state)
+    ime_scheduled: bool,    // IME will be enabled after the next instruction
+    ime_just_enabled: bool, // IME was set by an EI right before the current instruction
 
     // --- Internal Timing/Execution State ---
     total_cycles: u64,   // Total T-cycles executed since start/reset
     fetched_opcode: u8,  // Last opcode fetched (for error reporting/debugging)
     instruction_pc: u16, // PC at the start of the current instruction (for reads/debugging)
+
+    // --- Debugging ---
+    #[serde(skip)]
+    trace: TraceSlot, // Executed-instruction trace, if one has been started
 }
 
 // Core CPU logic (new, step, interrupts, helpers, accessors) remains here
@@ -82,11 +143,14 @@ impl Cpu {
             pc: init_pc,
             ime: false, // IME is initially disabled
             halted: false,
+            halt_bug: false,
             stop_requested: false,
             ime_scheduled: false,
+            ime_just_enabled: false,
             total_cycles: 0,
             fetched_opcode: 0,
             instruction_pc: 0,
+            trace: TraceSlot::default(),
         }
     }
 
@@ -127,25 +191,36 @@ impl Cpu {
         memory_bus.write_byte(memory_map::WY_ADDR, 0x00);
         memory_bus.write_byte(memory_map::WX_ADDR, 0x00);
         memory_bus.write_byte(memory_map::INTERRUPT_ENABLE_REGISTER, 0x00);
-        // IF (0xFF0F) is often 0xE1 post-boot (VBL, LCD, Timer flags set) but clear here?
-        // Let's initialize IF based on the MemoryBus::new() which sets it to E1.
-        // memory_bus.write_byte(memory_map::IF_ADDR, 0x00); // Or E1?
+        // Same value MemoryBus::new() starts with; IE is 0, so nothing fires until enabled
+        memory_bus.write_byte(memory_map::IF_ADDR, memory_map::POST_BOOT_IF);
         memory_bus.write_byte(0xFF50, 0x01); // Boot ROM Lock (write 1 to disable)
     }
 
     /// Executes a single CPU instruction cycle (fetch, decode, execute).
     /// Returns the number of T-cycles consumed by the instruction.
     pub fn step(&mut self, memory_bus: &mut MemoryBus) -> CpuResult<u16> {
+        self.step_with_ticker(memory_bus, &mut |_, _| {})
+    }
+
+    /// Like `step`, but calls `tick` with the T-cycles elapsed before each bus access of
+    /// instructions with per-access timing (CALL, PUSH, RST and interrupt dispatch).
+    /// The return value is still the instruction's full cost; the caller ticks whatever
+    /// part of it `tick` hasn't seen yet.
+    pub fn step_with_ticker(
+        &mut self,
+        memory_bus: &mut MemoryBus,
+        tick: &mut Ticker,
+    ) -> CpuResult<u16> {
         // --- Interrupt Handling Phase ---
-        let mut ime_just_enabled = false;
+        // EI's delay: the instruction right after it always runs before any interrupt
+        self.ime_just_enabled = self.ime_scheduled;
         if self.ime_scheduled {
             self.ime = true;
             self.ime_scheduled = false;
-            ime_just_enabled = true;
         }
 
-        let interrupt_cycles = if self.ime && !ime_just_enabled {
-            self.handle_interrupts(memory_bus)
+        let interrupt_cycles = if self.ime && !self.ime_just_enabled {
+            self.handle_interrupts(memory_bus, tick)
         } else {
             0
         };
@@ -159,8 +234,8 @@ impl Cpu {
 
         // --- Halted/Stopped Phase ---
         if self.halted {
-            let ie = memory_bus.read_byte(memory_map::INTERRUPT_ENABLE_REGISTER);
-            let iflags = memory_bus.read_byte(memory_map::IF_ADDR);
+            let ie = memory_bus.get_io_reg(memory_map::INTERRUPT_ENABLE_REGISTER);
+            let iflags = memory_bus.get_io_reg(memory_map::IF_ADDR);
             if (ie & iflags & 0x1F) != 0 {
                 self.halted = false;
                 self.total_cycles = self.total_cycles.wrapping_add(4);
@@ -175,24 +250,29 @@ impl Cpu {
         // --- Fetch Phase ---
         self.instruction_pc = self.pc;
         self.fetched_opcode = self.read_byte_at_pc(memory_bus);
+        if self.trace.0.is_some() {
+            self.record_trace(memory_bus);
+        }
 
         // --- Decode Phase ---
         let instruction = &INSTRUCTIONS[self.fetched_opcode as usize];
 
         // --- Advance PC Phase ---
-        self.pc = self.pc.wrapping_add(instruction.length as u16);
+        // Under the HALT bug PC fails to move past the opcode, so that byte is read again:
+        // as the first operand, or as the next opcode of a 1-byte instruction, which then
+        // runs twice. Operands are read from just behind PC, so they see it too.
+        let halt_bug = std::mem::take(&mut self.halt_bug);
+        self.pc = self
+            .instruction_pc
+            .wrapping_add(instruction.length as u16 - halt_bug as u16);
 
         // --- Execute Phase ---
-        let execute_result = (instruction.execute)(self, memory_bus);
+        let execute_result = instruction.execute.run(self, memory_bus, tick);
 
         // --- Process Result ---
         match execute_result {
             Ok(additional_cycles) => {
-                let base_cycles = if self.fetched_opcode == 0xCB {
-                    4 // Base cost of CB prefix itself
-                } else {
-                    instruction.cycles as u16
-                };
+                let base_cycles = instruction.cycles as u16;
                 let total_instruction_cycles = base_cycles.wrapping_add(additional_cycles);
                 self.total_cycles = self
                     .total_cycles
@@ -206,12 +286,12 @@ impl Cpu {
                     self.fetched_opcode,
                     error_message
                 );
-                let base_cycles = if self.fetched_opcode == 0xCB {
-                    4
-                } else {
-                    instruction.cycles as u16
-                };
-                self.total_cycles = self.total_cycles.wrapping_add(base_cycles as u64);
+                self.total_cycles = self.total_cycles.wrapping_add(instruction.cycles as u64);
+                if let Some(trace) = self.trace.0.as_mut()
+                    && let Err(e) = trace.dump()
+                {
+                    log::error!("Failed to dump instruction trace: {}", e);
+                }
                 Err(format!(
                     "CPU Error at PC={:#06X} (Opcode {:#04X}): {}",
                     self.instruction_pc, self.fetched_opcode, error_message
@@ -222,9 +302,9 @@ impl Cpu {
 
     /// Checks for and handles pending interrupts if IME is enabled.
     /// Returns the number of cycles taken if an interrupt was handled (20), otherwise 0.
-    fn handle_interrupts(&mut self, memory_bus: &mut MemoryBus) -> u16 {
-        let if_flags = memory_bus.read_byte(memory_map::IF_ADDR);
-        let ie_flags = memory_bus.read_byte(memory_map::INTERRUPT_ENABLE_REGISTER);
+    fn handle_interrupts(&mut self, memory_bus: &mut MemoryBus, tick: &mut Ticker) -> u16 {
+        let if_flags = memory_bus.get_io_reg(memory_map::IF_ADDR);
+        let ie_flags = memory_bus.get_io_reg(memory_map::INTERRUPT_ENABLE_REGISTER);
         let pending = if_flags & ie_flags & 0x1F;
         if pending == 0 {
             return 0;
@@ -247,38 +327,128 @@ impl Cpu {
             unreachable!();
         };
 
-        let current_if = memory_bus.read_byte(memory_map::IF_ADDR);
-        memory_bus.write_byte(memory_map::IF_ADDR, current_if & !(1 << interrupt_bit));
-        self.push_word(self.pc, memory_bus);
+        let current_if = memory_bus.get_io_reg(memory_map::IF_ADDR);
+        memory_bus.set_io_reg_direct(memory_map::IF_ADDR, current_if & !(1 << interrupt_bit));
+        // Dispatch happens between instructions, so `pc` already points at the next
+        // instruction to run; that is the address RETI must return to.
+        tick(memory_bus, 8); // Two wait states before the push
+        self.push_word(self.pc, memory_bus, tick);
         self.pc = vector;
         20 // Interrupt handling cycles
     }
 
+    // --- Instruction Tracing ---
+    /// Starts recording every executed instruction to `path`, replacing any active trace.
+    /// With `max_entries` set, the trace stops by itself after that many instructions.
+    pub fn start_trace(
+        &mut self,
+        path: &Path,
+        mode: TraceMode,
+        max_entries: Option<u64>,
+    ) -> Result<(), String> {
+        self.stop_trace()?;
+        let mut writer = TraceWriter::create(path, mode)
+            .map_err(|e| format!("Failed to open trace file {}: {}", path.display(), e))?;
+        if let Some(max_entries) = max_entries {
+            writer = writer.with_max_entries(max_entries);
+        }
+        self.trace.0 = Some(writer);
+        Ok(())
+    }
+
+    /// Stops the active trace, flushing it (and dumping a ring buffer) to disk.
+    pub fn stop_trace(&mut self) -> Result<(), String> {
+        match self.trace.0.take() {
+            Some(writer) => writer
+                .finish()
+                .map_err(|e| format!("Failed to finish instruction trace: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of instructions recorded by the active trace, if any.
+    pub fn trace_entries(&self) -> Option<u64> {
+        self.trace
+            .0
+            .as_ref()
+            .map(|writer| writer.entries_recorded())
+    }
+
+    fn record_trace(&mut self, memory_bus: &MemoryBus) {
+        // Only the raw bytes are kept: entries are disassembled when they're written out
+        let length = instruction_length(self.fetched_opcode);
+        let mut bytes = [0; 3];
+        for (i, byte) in bytes.iter_mut().enumerate().take(length as usize) {
+            *byte = memory_bus.peek_byte(self.instruction_pc.wrapping_add(i as u16));
+        }
+        let entry = TraceEntry {
+            pc: self.instruction_pc,
+            bytes,
+            length,
+            a: self.a,
+            f: self.f,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            h: self.h,
+            l: self.l,
+            sp: self.sp,
+            cycles: self.total_cycles,
+        };
+        if let Some(writer) = self.trace.0.as_mut()
+            && let Err(e) = writer.record(entry)
+        {
+            log::error!("Instruction trace write failed, disabling trace: {}", e);
+            self.trace.0 = None;
+        }
+        if self.trace.0.as_ref().is_some_and(|writer| writer.is_full()) {
+            log::info!("Instruction trace reached its limit, stopping");
+            if let Err(e) = self.stop_trace() {
+                log::error!("{}", e);
+            }
+        }
+    }
+
     // --- Memory Access Helpers ---
     #[inline(always)]
     fn read_byte_at_pc(&self, memory_bus: &MemoryBus) -> u8 {
         memory_bus.read_byte(self.pc)
     }
+    // Operands are the bytes just behind PC, which `step` has already moved past them
     #[inline(always)]
     fn read_d8(&self, memory_bus: &MemoryBus) -> u8 {
-        memory_bus.read_byte(self.instruction_pc.wrapping_add(1))
+        memory_bus.read_byte(self.pc.wrapping_sub(1))
     }
     #[inline(always)]
     fn read_d16(&self, memory_bus: &MemoryBus) -> u16 {
-        let lo = memory_bus.read_byte(self.instruction_pc.wrapping_add(1));
-        let hi = memory_bus.read_byte(self.instruction_pc.wrapping_add(2));
+        let lo = memory_bus.read_byte(self.pc.wrapping_sub(2));
+        let hi = memory_bus.read_byte(self.pc.wrapping_sub(1));
+        u16::from_le_bytes([lo, hi])
+    }
+    /// `read_d16` for timed handlers: ticks the M-cycle before each operand read, the
+    /// first of which is the opcode fetch.
+    #[inline(always)]
+    fn read_d16_timed(&self, memory_bus: &mut MemoryBus, tick: &mut Ticker) -> u16 {
+        tick(memory_bus, 4);
+        let lo = memory_bus.read_byte(self.pc.wrapping_sub(2));
+        tick(memory_bus, 4);
+        let hi = memory_bus.read_byte(self.pc.wrapping_sub(1));
         u16::from_le_bytes([lo, hi])
     }
     #[inline(always)]
     fn read_r8(&self, memory_bus: &MemoryBus) -> i8 {
-        memory_bus.read_byte(self.instruction_pc.wrapping_add(1)) as i8
+        memory_bus.read_byte(self.pc.wrapping_sub(1)) as i8
     }
 
     // --- Stack Operations ---
+    /// Pushes high byte then low byte, one M-cycle apart. The caller ticks everything
+    /// up to the first write; the cycle after the second is left to `step`'s caller.
     #[inline(always)]
-    fn push_word(&mut self, value: u16, memory_bus: &mut MemoryBus) {
+    fn push_word(&mut self, value: u16, memory_bus: &mut MemoryBus, tick: &mut Ticker) {
         self.sp = self.sp.wrapping_sub(1);
         memory_bus.write_byte(self.sp, (value >> 8) as u8);
+        tick(memory_bus, 4);
         self.sp = self.sp.wrapping_sub(1);
         memory_bus.write_byte(self.sp, (value & 0xFF) as u8);
     }
@@ -347,6 +517,7 @@ impl Cpu {
 
     // --- ALU and Bit Operation Helpers ---
     // (Keep these internal helpers within the main impl block)
+    // 8-bit INC/DEC (including INC/DEC (HL)) only touch Z/N/H; C must be preserved.
     fn inc_u8(&mut self, value: u8) -> u8 {
         let result = value.wrapping_add(1);
         self.set_flag(FLAG_Z, result == 0);
@@ -556,50 +727,124 @@ impl Cpu {
         self.total_cycles
     }
 
+    /// Captures the comparable CPU state, e.g. for differential testing.
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            a: self.a,
+            f: self.f,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            h: self.h,
+            l: self.l,
+            sp: self.sp,
+            pc: self.pc,
+            ime: self.ime,
+            ime_scheduled: self.ime_scheduled,
+            halted: self.halted,
+            halt_bug: self.halt_bug,
+        }
+    }
+
+    // --- Save States ---
+    /// Completes a CPU just read from a save state: a running trace carries on from
+    /// `running`, and F's unused low bits are cleared.
+    pub(crate) fn restore_unsaved(&mut self, running: &mut Cpu) {
+        self.trace = std::mem::take(&mut running.trace);
+        self.f &= 0xF0;
+    }
+
     // --- Debugging Helpers ---
+    /// Disassembles `count` consecutive instructions forward from `start`, returning each
+    /// one's address, mnemonic and length. Addresses wrap around past 0xFFFF.
+    pub fn disassemble_range(
+        &self,
+        start: u16,
+        count: usize,
+        bus: &MemoryBus,
+    ) -> Vec<(u16, String, u8)> {
+        let mut address = start;
+        (0..count)
+            .map(|_| {
+                let (text, length) = self.disassemble_instruction(address, bus);
+                let line = (address, text, length);
+                address = address.wrapping_add(length as u16);
+                line
+            })
+            .collect()
+    }
+
+    /// Disassembles the instruction at `address`, returning its mnemonic and length in bytes.
+    /// The length is always in `1..=3` so callers can safely walk memory with it.
     pub fn disassemble_instruction(&self, address: u16, bus: &MemoryBus) -> (String, u8) {
-        let opcode = bus.read_byte(address);
-        if opcode == 0xCB {
-            let cb_opcode = bus.read_byte(address.wrapping_add(1));
-            if let Some(cb_instr) = CB_INSTRUCTIONS.get(cb_opcode as usize) {
-                (format!("{}", cb_instr.mnemonic), 2)
-            } else {
-                (format!("DB CB, {:02X}", cb_opcode), 2)
-            }
+        let bytes = [0, 1, 2].map(|i| bus.peek_byte(address.wrapping_add(i)));
+        disassemble(address, &bytes)
+    }
+}
+
+/// Length in bytes of the instruction starting with `opcode`, always in `1..=3`.
+fn instruction_length(opcode: u8) -> u8 {
+    if opcode == 0xCB {
+        return 2;
+    }
+    match INSTRUCTIONS.get(opcode as usize) {
+        Some(instr) => {
+            // Callers advance by this length, so it must never be zero.
+            debug_assert!(
+                (1..=3).contains(&instr.length),
+                "opcode {:#04X} has invalid length {}",
+                opcode,
+                instr.length
+            );
+            instr.length.clamp(1, 3)
+        }
+        None => 1,
+    }
+}
+
+/// Disassembles the instruction whose bytes (opcode first, then up to two operands) sit
+/// at `address`, returning its mnemonic and length in bytes.
+fn disassemble(address: u16, bytes: &[u8; 3]) -> (String, u8) {
+    let opcode = bytes[0];
+    let length = instruction_length(opcode);
+    if opcode == 0xCB {
+        let cb_opcode = bytes[1];
+        if let Some(cb_instr) = CB_INSTRUCTIONS.get(cb_opcode as usize) {
+            (format!("{}", cb_instr.mnemonic), length)
         } else {
-            if let Some(instr) = INSTRUCTIONS.get(opcode as usize) {
-                let operand_str = match instr.length {
-                    1 => "".to_string(),
-                    2 => {
-                        let d8 = bus.read_byte(address.wrapping_add(1));
-                        if instr.mnemonic.starts_with("JR")
-                            || instr.mnemonic == "ADD SP, r8"
-                            || instr.mnemonic == "LD HL, SP+r8"
-                        {
-                            format!("${:+}", d8 as i8)
-                        } else {
-                            format!("0b{:02X}", d8)
-                        }
-                    }
-                    3 => {
-                        let lo = bus.read_byte(address.wrapping_add(1));
-                        let hi = bus.read_byte(address.wrapping_add(2));
-                        format!("0x{:04X}", u16::from_le_bytes([lo, hi]))
+            (format!("DB CB, {:02X}", cb_opcode), length)
+        }
+    } else {
+        if let Some(instr) = INSTRUCTIONS.get(opcode as usize) {
+            let operand_str = match instr.length {
+                1 => "".to_string(),
+                2 => {
+                    let d8 = bytes[1];
+                    if instr.mnemonic.starts_with("JR") {
+                        // Show where it lands: relative to the instruction after the JR
+                        let target = address.wrapping_add(2).wrapping_add_signed(d8 as i8 as i16);
+                        format!("0x{:04X}", target)
+                    } else if instr.mnemonic == "ADD SP, r8" || instr.mnemonic == "LD HL, SP+r8" {
+                        format!("${:+}", d8 as i8)
+                    } else {
+                        format!("0b{:02X}", d8)
                     }
-                    _ => "".to_string(),
-                };
-                let formatted_mnemonic = instr
-                    .mnemonic
-                    .replace("d16", &operand_str)
-                    .replace("a16", &operand_str)
-                    .replace("d8", &operand_str)
-                    .replace("r8", &operand_str)
-                    .trim_end()
-                    .to_string();
-                (formatted_mnemonic, instr.length)
-            } else {
-                (format!("DB {:02X}", opcode), 1)
-            }
+                }
+                3 => format!("0x{:04X}", u16::from_le_bytes([bytes[1], bytes[2]])),
+                _ => "".to_string(),
+            };
+            let formatted_mnemonic = instr
+                .mnemonic
+                .replace("d16", &operand_str)
+                .replace("a16", &operand_str)
+                .replace("d8", &operand_str)
+                .replace("r8", &operand_str)
+                .trim_end()
+                .to_string();
+            (formatted_mnemonic, length)
+        } else {
+            (format!("DB {:02X}", opcode), length)
         }
     }
 }
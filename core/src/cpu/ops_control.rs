@@ -1,5 +1,5 @@
 use super::instruction::CB_INSTRUCTIONS;
-use super::{Cpu, CpuResult, constants::*};
+use super::{Cpu, CpuResult, Ticker, constants::*};
 use crate::memory_bus::MemoryBus;
 use crate::memory_map;
 use log;
@@ -71,33 +71,40 @@ impl Cpu {
     }
 
     // CALL a16 / CALL cc, a16
-    fn conditional_call_a16(&mut self, condition: bool, bus: &mut MemoryBus) -> CpuResult<u16> {
-        let addr = self.read_d16(bus);
+    fn conditional_call_a16(
+        &mut self,
+        condition: bool,
+        bus: &mut MemoryBus,
+        tick: &mut Ticker,
+    ) -> CpuResult<u16> {
+        let addr = self.read_d16_timed(bus, tick);
         if condition {
-            self.push_word(self.pc, bus); // Push address *after* CALL instruction
+            tick(bus, 8); // Rest of the operand read, then SP is decremented
+            self.push_word(self.pc, bus, tick); // Push address *after* CALL instruction
             self.pc = addr;
             Ok(12) // Branch taken costs 12 extra cycles (total 24)
         } else {
             Ok(0) // Branch not taken costs 0 extra cycles (total 12)
         }
     }
-    pub fn op_call_a16(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        let addr = self.read_d16(bus);
-        self.push_word(self.pc, bus);
+    pub fn op_call_a16(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        let addr = self.read_d16_timed(bus, tick);
+        tick(bus, 8); // Rest of the operand read, then SP is decremented
+        self.push_word(self.pc, bus, tick);
         self.pc = addr;
         Ok(0) // Unconditional CALL takes 24 base cycles
     }
-    pub fn op_call_nz_a16(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.conditional_call_a16(!self.get_flag(FLAG_Z), bus)
+    pub fn op_call_nz_a16(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.conditional_call_a16(!self.get_flag(FLAG_Z), bus, tick)
     }
-    pub fn op_call_z_a16(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.conditional_call_a16(self.get_flag(FLAG_Z), bus)
+    pub fn op_call_z_a16(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.conditional_call_a16(self.get_flag(FLAG_Z), bus, tick)
     }
-    pub fn op_call_nc_a16(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.conditional_call_a16(!self.get_flag(FLAG_C), bus)
+    pub fn op_call_nc_a16(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.conditional_call_a16(!self.get_flag(FLAG_C), bus, tick)
     }
-    pub fn op_call_c_a16(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.conditional_call_a16(self.get_flag(FLAG_C), bus)
+    pub fn op_call_c_a16(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.conditional_call_a16(self.get_flag(FLAG_C), bus, tick)
     }
 
     // RET / RET cc / RETI
@@ -133,34 +140,37 @@ impl Cpu {
     }
 
     // RST n
-    fn rst(&mut self, vector: u16, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.push_word(self.pc, bus);
+    /// Calls one of the eight fixed vectors (0x00, 0x08, ..., 0x38). PC has already been
+    /// advanced past the 1-byte opcode, so the pushed return address is the next instruction.
+    fn rst(&mut self, vector: u16, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        tick(bus, 8); // Opcode fetch, then an internal delay while SP is decremented
+        self.push_word(self.pc, bus, tick);
         self.pc = vector;
         Ok(0) // RST takes 16 base cycles
     }
-    pub fn op_rst_00h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0000, bus)
+    pub fn op_rst_00h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0000, bus, tick)
     }
-    pub fn op_rst_08h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0008, bus)
+    pub fn op_rst_08h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0008, bus, tick)
     }
-    pub fn op_rst_10h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0010, bus)
+    pub fn op_rst_10h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0010, bus, tick)
     }
-    pub fn op_rst_18h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0018, bus)
+    pub fn op_rst_18h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0018, bus, tick)
     }
-    pub fn op_rst_20h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0020, bus)
+    pub fn op_rst_20h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0020, bus, tick)
     }
-    pub fn op_rst_28h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0028, bus)
+    pub fn op_rst_28h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0028, bus, tick)
     }
-    pub fn op_rst_30h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0030, bus)
+    pub fn op_rst_30h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0030, bus, tick)
     }
-    pub fn op_rst_38h(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.rst(0x0038, bus)
+    pub fn op_rst_38h(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        self.rst(0x0038, bus, tick)
     }
 
     // Misc Control
@@ -174,22 +184,33 @@ impl Cpu {
         Ok(0)
     }
     pub fn op_halt(&mut self, memory_bus: &mut MemoryBus) -> CpuResult<u16> {
-        let ie = memory_bus.read_byte(memory_map::INTERRUPT_ENABLE_REGISTER);
-        let iflags = memory_bus.read_byte(memory_map::IF_ADDR);
+        let ie = memory_bus.get_io_reg(memory_map::INTERRUPT_ENABLE_REGISTER);
+        let iflags = memory_bus.get_io_reg(memory_map::IF_ADDR);
         if !self.ime && (ie & iflags & 0x1F) != 0 {
-            log::warn!(
-                "HALT bug triggered at PC={:#06X}! IME=0, IE&IF={:02X}. Next instruction will execute.",
+            // HALT bug: the CPU doesn't halt, and the byte after HALT is read twice
+            log::debug!(
+                "HALT bug triggered at PC={:#06X}: IME=0, IE&IF={:02X}",
                 self.instruction_pc,
                 ie & iflags & 0x1F
             );
-            // PC already incremented, effectively skipping HALT cycle.
+            self.halt_bug = true;
+        } else if self.ime_just_enabled && (ie & iflags & 0x1F) != 0 {
+            // EI right before HALT: the pending interrupt is serviced without halting,
+            // but PC isn't advanced, so the handler returns to this HALT and it runs again
+            self.pc = self.instruction_pc;
         } else {
             self.halted = true;
         }
         Ok(0)
     }
-    pub fn op_stop(&mut self, _bus: &mut MemoryBus) -> CpuResult<u16> {
-        // For now, just flag it. Proper STOP needs more handling (power modes, CGB speed switch).
+    pub fn op_stop(&mut self, memory_bus: &mut MemoryBus) -> CpuResult<u16> {
+        // STOP resets DIV like a write would, which can also tick TIMA (see Timer::step)
+        memory_bus.write_byte(memory_map::DIV_ADDR, 0);
+        // CGB: with a switch armed in KEY1, STOP changes speed and execution carries on
+        if memory_bus.perform_speed_switch() {
+            return Ok(SPEED_SWITCH_CYCLES);
+        }
+        // For now, just flag it. Proper STOP needs more handling (power modes).
         self.stop_requested = true;
         log::warn!(
             "STOP instruction encountered at PC={:#06X} (behavior may be incomplete)",
@@ -224,13 +245,15 @@ impl Cpu {
 
     // --- CB Prefix Dispatcher ---
     pub fn op_prefix_cb(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        let cb_opcode = bus.read_byte(self.instruction_pc.wrapping_add(1));
+        let cb_opcode = self.read_d8(bus);
         let cb_instr = &CB_INSTRUCTIONS[cb_opcode as usize];
         // Execute the specific CB function (defined in ops_cb.rs)
-        let cb_result = (cb_instr.execute)(self, bus);
+        // CB opcodes are all plain handlers, so the tick callback goes unused
+        let cb_result = cb_instr.execute.run(self, bus, &mut |_, _| {});
         match cb_result {
-            // Return cycles for the specific CB op *only*
-            Ok(_) => Ok(cb_instr.cycles as u16),
+            // CB table cycles include the prefix fetch, which `step` already charges
+            // as the PREFIX CB base cost, so only the remainder is additional.
+            Ok(_) => Ok((cb_instr.cycles as u16).saturating_sub(4)),
             Err(e) => Err(format!("CB Opcode {:#04X} Error: {}", cb_opcode, e)),
         }
     }
@@ -1,4 +1,4 @@
-use super::{Cpu, CpuResult};
+use super::{Cpu, CpuResult, Ticker};
 use crate::memory_bus::MemoryBus;
 
 // --- LD Implementations ---
@@ -30,7 +30,7 @@ impl Cpu {
         bus.write_byte(self.get_de(), self.a);
         Ok(0)
     }
-    // LD (HL+/-), A
+    // LD (HL+/-), A - the write uses HL as it was *before* the increment/decrement
     pub fn op_ld_hli_a(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
         let addr = self.get_hl();
         bus.write_byte(addr, self.a);
@@ -98,7 +98,7 @@ impl Cpu {
         self.a = bus.read_byte(self.get_de());
         Ok(0)
     }
-    // LD A, (HL+/-)
+    // LD A, (HL+/-) - the read uses HL as it was *before* the increment/decrement
     pub fn op_ld_a_hli(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
         let addr = self.get_hl();
         self.a = bus.read_byte(addr);
@@ -239,21 +239,25 @@ impl Cpu {
     }
 
     // PUSH qq
-    pub fn op_push_bc(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.push_word(self.get_bc(), bus);
+    pub fn op_push_bc(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        tick(bus, 8); // Opcode fetch, then an internal delay while SP is decremented
+        self.push_word(self.get_bc(), bus, tick);
         Ok(0)
     }
-    pub fn op_push_de(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.push_word(self.get_de(), bus);
+    pub fn op_push_de(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        tick(bus, 8);
+        self.push_word(self.get_de(), bus, tick);
         Ok(0)
     }
-    pub fn op_push_hl(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
-        self.push_word(self.get_hl(), bus);
+    pub fn op_push_hl(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
+        tick(bus, 8);
+        self.push_word(self.get_hl(), bus, tick);
         Ok(0)
     }
-    pub fn op_push_af(&mut self, bus: &mut MemoryBus) -> CpuResult<u16> {
+    pub fn op_push_af(&mut self, bus: &mut MemoryBus, tick: &mut Ticker) -> CpuResult<u16> {
         self.f &= 0xF0; // Mask lower bits before push
-        self.push_word(self.get_af(), bus);
+        tick(bus, 8);
+        self.push_word(self.get_af(), bus, tick);
         Ok(0)
     }
 
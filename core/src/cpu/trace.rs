@@ -0,0 +1,222 @@
+//! Executed-instruction tracing for long debugging sessions.
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Where and how executed instructions are recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    /// Plain text, one line per instruction.
+    Plain,
+    /// Gzip-compressed text, one line per instruction.
+    Gzip,
+    /// Keep only the last `n` instructions in memory and write them out on a CPU error,
+    /// a panic that unwinds past the CPU, or when the trace is stopped. A process that
+    /// aborts (e.g. `panic = "abort"`) or is killed loses them.
+    RingBuffer(usize),
+}
+
+/// CPU state captured at the start of an executed instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    /// The instruction's bytes; only the first `length` are meaningful.
+    pub bytes: [u8; 3],
+    pub length: u8,
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub cycles: u64,
+}
+
+impl TraceEntry {
+    /// The instruction's mnemonic with its operands filled in.
+    pub fn disassembly(&self) -> String {
+        super::disassemble(self.pc, &self.bytes).0
+    }
+}
+
+/// One line per instruction, in the register-dump format most emulators' trace loggers
+/// share, so traces can be diffed against a known-good one:
+/// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 (cy: 0) |[00] 0x0100: NOP`,
+/// where the bracketed part is the instruction's bytes.
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} (cy: {}) |[",
+            self.a,
+            self.f,
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+            self.sp,
+            self.pc,
+            self.cycles
+        )?;
+        for (i, byte) in self.bytes[..self.length as usize].iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        write!(f, "] 0x{:04X}: {}", self.pc, self.disassembly())
+    }
+}
+
+enum TraceSink {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Ring {
+        entries: VecDeque<TraceEntry>,
+        capacity: usize,
+        path: PathBuf,
+    },
+}
+
+/// An open instruction trace.
+pub struct TraceWriter {
+    sink: TraceSink,
+    entries_recorded: u64,
+    max_entries: Option<u64>,
+}
+
+impl TraceWriter {
+    /// Opens a trace at `path`. Ring-buffer traces only create the file when dumped.
+    pub fn create(path: &Path, mode: TraceMode) -> io::Result<Self> {
+        let sink = match mode {
+            TraceMode::Plain => TraceSink::Plain(BufWriter::new(File::create(path)?)),
+            TraceMode::Gzip => TraceSink::Gzip(GzEncoder::new(
+                BufWriter::new(File::create(path)?),
+                Compression::default(),
+            )),
+            TraceMode::RingBuffer(capacity) => {
+                if capacity == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "ring buffer trace capacity must be non-zero",
+                    ));
+                }
+                TraceSink::Ring {
+                    entries: VecDeque::with_capacity(capacity.min(1 << 20)),
+                    capacity,
+                    path: path.to_path_buf(),
+                }
+            }
+        };
+        Ok(TraceWriter {
+            sink,
+            entries_recorded: 0,
+            max_entries: None,
+        })
+    }
+
+    /// Caps the trace at `max_entries` instructions so a long run can't fill the disk.
+    pub fn with_max_entries(mut self, max_entries: u64) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Whether the trace has recorded as many instructions as its cap allows.
+    pub fn is_full(&self) -> bool {
+        self.max_entries
+            .is_some_and(|max| self.entries_recorded >= max)
+    }
+
+    /// Records one executed instruction.
+    pub fn record(&mut self, entry: TraceEntry) -> io::Result<()> {
+        self.entries_recorded += 1;
+        match &mut self.sink {
+            TraceSink::Plain(writer) => writeln!(writer, "{}", entry),
+            TraceSink::Gzip(writer) => writeln!(writer, "{}", entry),
+            TraceSink::Ring {
+                entries, capacity, ..
+            } => {
+                if entries.len() == *capacity {
+                    entries.pop_front();
+                }
+                entries.push_back(entry);
+                Ok(())
+            }
+        }
+    }
+
+    /// Total number of instructions recorded, including any dropped from a ring buffer.
+    pub fn entries_recorded(&self) -> u64 {
+        self.entries_recorded
+    }
+
+    /// Writes a ring buffer's contents to its file. No-op for streaming modes.
+    pub fn dump(&mut self) -> io::Result<()> {
+        if let TraceSink::Ring { entries, path, .. } = &self.sink {
+            let mut writer = BufWriter::new(File::create(path)?);
+            for entry in entries {
+                writeln!(writer, "{}", entry)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes and closes the trace, dumping a ring buffer if one is in use.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.dump()?;
+        match self.sink {
+            TraceSink::Plain(mut writer) => writer.flush(),
+            TraceSink::Gzip(writer) => writer.finish()?.flush(),
+            TraceSink::Ring { .. } => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for TraceWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match &self.sink {
+            TraceSink::Plain(_) => "Plain".to_string(),
+            TraceSink::Gzip(_) => "Gzip".to_string(),
+            TraceSink::Ring { capacity, .. } => format!("RingBuffer({})", capacity),
+        };
+        f.debug_struct("TraceWriter")
+            .field("mode", &mode)
+            .field("entries_recorded", &self.entries_recorded)
+            .field("max_entries", &self.max_entries)
+            .finish()
+    }
+}
+
+/// Holds the CPU's active trace, if any. Cloning a `Cpu` does not clone its trace file.
+#[derive(Debug, Default)]
+pub(super) struct TraceSlot(pub(super) Option<TraceWriter>);
+
+impl Clone for TraceSlot {
+    fn clone(&self) -> Self {
+        TraceSlot(None)
+    }
+}
+
+/// A panic skips the CPU error path that dumps a ring buffer, so dump it while unwinding:
+/// the last instructions before the panic are the ones worth having.
+impl Drop for TraceSlot {
+    fn drop(&mut self) {
+        if std::thread::panicking()
+            && let Some(writer) = self.0.as_mut()
+            && let Err(e) = writer.dump()
+        {
+            log::error!("Failed to dump instruction trace: {}", e);
+        }
+    }
+}
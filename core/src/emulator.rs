@@ -0,0 +1,647 @@
+use crate::apu::Apu;
+use crate::cpu::{Cpu, CpuResult};
+use crate::joypad::GbButton;
+use crate::memory_bus::{BootRamInit, MemoryBus, RomSizePolicy, WatchHit, WatchKind};
+use crate::memory_map::{IF_ADDR, POST_BOOT_IF};
+use crate::palette::{self, DEFAULT_PALETTE, Palette};
+use crate::ppu::{
+    DOTS_PER_SCANLINE, FRAME_BUFFER_SIZE, GB_HEIGHT, GB_WIDTH, Ppu, SCANLINES_PER_FRAME,
+};
+use crate::state::{STATE_MAGIC, STATE_VERSION};
+use crate::timer::Timer;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+/// Standard Game Boy CPU frequency.
+pub const CPU_FREQ_HZ: f64 = 4_194_304.0;
+/// Frames per second `run_frame` budgets its cycles for (approx. 69905 cycles each).
+pub const FRAMES_PER_SECOND: u32 = 60;
+/// Step over/out give up if the subroutine hasn't returned after this many frames,
+/// e.g. because it never does.
+pub const STEP_MAX_FRAMES: u64 = 60;
+
+/// Why `run_frame_until_breakpoint` stopped before the end of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// PC reached this breakpoint.
+    Breakpoint(u16),
+    /// The last instruction made an access a watchpoint was waiting for.
+    Watchpoint(WatchHit),
+}
+
+/// A complete Game Boy with no frontend attached: no windowing, audio output or file
+/// access. Feed it ROM bytes, drive it with `run_frame` and read back the frame buffer,
+/// audio samples (`apu.take_samples`) and serial output.
+pub struct Emulator {
+    pub cpu: Cpu,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    pub timer: Timer,
+    pub memory_bus: MemoryBus,
+    skip_boot_rom: bool,
+    /// DMG boot ROM to run at power-on, required unless `skip_boot_rom` is set.
+    boot_rom: Option<Vec<u8>>,
+    /// IF value when skipping the boot ROM.
+    post_boot_if: u8,
+    /// Emulated CPU frequency relative to the real hardware (1.0 = normal).
+    clock_multiplier: f64,
+    /// Base-rate T-cycles `run_frame` emulates at the clock multiplier.
+    cycles_per_frame: u32,
+    rom_size_policy: RomSizePolicy,
+    boot_ram_init: BootRamInit,
+    dmg_quirks: bool,
+    /// Display colors for DMG shades 0-3, used by `render_into`.
+    palette: Palette,
+    /// PCs that `run_frame_until_breakpoint` stops at.
+    breakpoints: HashSet<u16>,
+}
+
+/// Configures and constructs an [`Emulator`].
+#[derive(Debug, Clone)]
+pub struct EmulatorBuilder {
+    skip_boot_rom: bool,
+    boot_rom: Option<Vec<u8>>,
+    post_boot_if: u8,
+    clock_multiplier: f64,
+    rom_size_policy: RomSizePolicy,
+    boot_ram_init: BootRamInit,
+    dmg_quirks: bool,
+    palette: Palette,
+}
+
+impl Default for EmulatorBuilder {
+    fn default() -> Self {
+        EmulatorBuilder {
+            skip_boot_rom: true,
+            boot_rom: None,
+            post_boot_if: POST_BOOT_IF,
+            clock_multiplier: 1.0,
+            rom_size_policy: RomSizePolicy::Header,
+            boot_ram_init: BootRamInit::Zero,
+            dmg_quirks: false,
+            palette: DEFAULT_PALETTE,
+        }
+    }
+}
+
+impl EmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start directly at 0x0100 with post-boot register values instead of running a boot ROM.
+    pub fn skip_boot_rom(mut self, skip: bool) -> Self {
+        self.skip_boot_rom = skip;
+        self
+    }
+
+    /// Runs this 256-byte DMG boot ROM at power-on (scrolling logo included) instead of
+    /// starting at 0x0100 with post-boot register values. Implies `skip_boot_rom(false)`.
+    pub fn boot_rom(mut self, data: Vec<u8>) -> Self {
+        self.boot_rom = Some(data);
+        self.skip_boot_rom = false;
+        self
+    }
+
+    /// IF value to start with when skipping the boot ROM. Defaults to what the DMG boot
+    /// ROM leaves behind (0xE1, VBlank pending); 0x00 matches emulators that clear it.
+    pub fn post_boot_if(mut self, value: u8) -> Self {
+        self.post_boot_if = value;
+        self
+    }
+
+    /// Scales the emulated CPU frequency (e.g. 2.0 runs twice as fast, 0.5 at half speed).
+    /// Timer and APU are clocked from CPU cycles, so they scale along with it; audio
+    /// still comes out at `SAMPLE_RATE`, pitched by the same factor. See
+    /// [`Emulator::set_clock_multiplier`] to change it while running.
+    pub fn clock_multiplier(mut self, multiplier: f64) -> Self {
+        self.clock_multiplier = multiplier;
+        self
+    }
+
+    /// Take the ROM bank count from the file size instead of the header, for truncated or
+    /// overdumped ROMs whose header claims the wrong size.
+    pub fn trust_rom_file_size(mut self, trust: bool) -> Self {
+        self.rom_size_policy = if trust {
+            RomSizePolicy::FileSize
+        } else {
+            RomSizePolicy::Header
+        };
+        self
+    }
+
+    /// What RAM holds at power-on (see [`BootRamInit`]). Defaults to all zeros.
+    pub fn boot_ram_init(mut self, init: BootRamInit) -> Self {
+        self.boot_ram_init = init;
+        self
+    }
+
+    /// Emulates DMG hardware bugs some games rely on, such as the STAT write bug.
+    pub fn dmg_quirks(mut self, enabled: bool) -> Self {
+        self.dmg_quirks = enabled;
+        self
+    }
+
+    /// Colors `render_into` shows DMG shades 0-3 in (lightest to darkest). Defaults to
+    /// [`DEFAULT_PALETTE`].
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Builds the emulator and inserts the cartridge with ROM contents `rom_data`.
+    pub fn build(self, rom_data: &[u8]) -> Result<Emulator, String> {
+        let mut emulator = self.build_empty()?;
+        emulator.load_rom(rom_data)?;
+        Ok(emulator)
+    }
+
+    /// Builds the emulator with the cartridge slot left empty.
+    pub fn build_empty(self) -> Result<Emulator, String> {
+        let cycles_per_frame = cycles_per_frame_at(self.clock_multiplier)?;
+        if !self.skip_boot_rom && self.boot_rom.is_none() {
+            return Err("Running the boot ROM requires boot ROM data".to_string());
+        }
+
+        let mut emulator = Emulator {
+            cpu: Cpu::new(self.skip_boot_rom),
+            ppu: Ppu::new(),
+            apu: Apu::new(),
+            timer: Timer::new(),
+            memory_bus: MemoryBus::new(),
+            skip_boot_rom: self.skip_boot_rom,
+            boot_rom: self.boot_rom,
+            post_boot_if: self.post_boot_if,
+            clock_multiplier: self.clock_multiplier,
+            cycles_per_frame,
+            rom_size_policy: self.rom_size_policy,
+            boot_ram_init: self.boot_ram_init,
+            dmg_quirks: self.dmg_quirks,
+            palette: self.palette,
+            breakpoints: HashSet::new(),
+        };
+        emulator.replace_memory_bus(emulator.new_memory_bus());
+        emulator.apu.set_clock_multiplier(emulator.clock_multiplier);
+        Ok(emulator)
+    }
+}
+
+/// Base-rate T-cycles per frame at `clock_multiplier`, or an error if the multiplier
+/// isn't a positive number.
+fn cycles_per_frame_at(clock_multiplier: f64) -> Result<u32, String> {
+    if !clock_multiplier.is_finite() || clock_multiplier <= 0.0 {
+        return Err(format!(
+            "Invalid clock multiplier: {} (must be a positive number)",
+            clock_multiplier
+        ));
+    }
+    Ok((CPU_FREQ_HZ * clock_multiplier / FRAMES_PER_SECOND as f64)
+        .round()
+        .max(1.0) as u32)
+}
+
+/// Save state payload after the magic and version. Borrowed when saving, owned when
+/// loaded.
+#[derive(Serialize, Deserialize)]
+struct SavedState<'a> {
+    /// `MemoryBus::cartridge_id` of the game the state belongs to.
+    cartridge_id: [u8; 19],
+    memory_bus: Cow<'a, MemoryBus>,
+    ppu: Cow<'a, Ppu>,
+    timer: Cow<'a, Timer>,
+    apu: Cow<'a, Apu>,
+    cpu: Cow<'a, Cpu>,
+}
+
+impl Emulator {
+    /// Creates an emulator with default settings running the given ROM. Running the
+    /// boot ROM needs its data, so use [`EmulatorBuilder::boot_rom`] for `skip_boot: false`.
+    pub fn from_rom_bytes(rom_data: &[u8], skip_boot: bool) -> Result<Self, String> {
+        EmulatorBuilder::new()
+            .skip_boot_rom(skip_boot)
+            .build(rom_data)
+    }
+
+    /// Base-rate T-cycles emulated per frame; the CPU runs twice as many in CGB double
+    /// speed.
+    pub fn cycles_per_frame(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
+    /// Emulated CPU frequency relative to the real hardware.
+    pub fn clock_multiplier(&self) -> f64 {
+        self.clock_multiplier
+    }
+
+    /// Changes the emulated CPU frequency while running, e.g. to fast-forward: each
+    /// `run_frame` then emulates `multiplier` frames' worth of cycles. The APU keeps
+    /// producing `SAMPLE_RATE` samples per real second, so sound plays at the same
+    /// multiple of its pitch. Non-positive or non-finite values are an error.
+    pub fn set_clock_multiplier(&mut self, multiplier: f64) -> Result<(), String> {
+        self.cycles_per_frame = cycles_per_frame_at(multiplier)?;
+        self.clock_multiplier = multiplier;
+        self.apu.set_clock_multiplier(multiplier);
+        Ok(())
+    }
+
+    /// Inserts a cartridge with ROM contents `rom_data` and powers on, resetting every
+    /// component. Battery-backed RAM starts blank; restore it through `memory_bus`.
+    /// A ROM [`MemoryBus::check_rom`] rejects is reported as an error and leaves the
+    /// running game as it was.
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), String> {
+        MemoryBus::check_rom(rom_data)?;
+        let mut memory_bus = self.new_memory_bus();
+        memory_bus.load_rom(rom_data)?;
+        if self.skip_boot_rom {
+            Cpu::initialize_post_boot_io(&mut memory_bus);
+            memory_bus.write_byte(IF_ADDR, self.post_boot_if);
+        } else if let Some(boot_rom) = &self.boot_rom {
+            // The boot ROM sets up the I/O registers itself, then unmaps via 0xFF50
+            memory_bus.load_boot_rom(boot_rom)?;
+        }
+
+        // Installed last, so the frontend's hooks don't see the post-boot setup writes
+        self.replace_memory_bus(memory_bus);
+        self.cpu = Cpu::new(self.skip_boot_rom);
+        self.ppu = Ppu::new();
+        self.apu = Apu::new();
+        self.apu.set_clock_multiplier(self.clock_multiplier);
+        self.timer = Timer::new();
+        Ok(())
+    }
+
+    /// Removes the cartridge, leaving an empty slot behind so a stale ROM can't keep
+    /// running. Save its battery-backed RAM first if it should be kept.
+    pub fn eject_cartridge(&mut self) {
+        self.replace_memory_bus(self.new_memory_bus());
+        self.cpu = Cpu::new(self.skip_boot_rom);
+        self.ppu = Ppu::new();
+        self.apu = Apu::new();
+        self.apu.set_clock_multiplier(self.clock_multiplier);
+        self.timer = Timer::new();
+    }
+
+    /// An empty memory bus with the configured settings.
+    fn new_memory_bus(&self) -> MemoryBus {
+        let mut memory_bus = MemoryBus::new();
+        memory_bus.set_rom_size_policy(self.rom_size_policy);
+        memory_bus.set_boot_ram_init(self.boot_ram_init);
+        memory_bus.set_dmg_quirks(self.dmg_quirks);
+        memory_bus
+    }
+
+    /// Swaps in `memory_bus`, carrying over the frontend's serial callback, bus access
+    /// hooks and watchpoints.
+    fn replace_memory_bus(&mut self, mut memory_bus: MemoryBus) {
+        memory_bus.set_hooks(self.memory_bus.take_hooks());
+        if let Some(callback) = self.memory_bus.take_serial_callback() {
+            memory_bus.set_serial_callback(callback);
+        }
+        for (range, kind) in self.memory_bus.watchpoints().to_vec() {
+            memory_bus.add_watch_range(range, kind);
+        }
+        self.memory_bus = memory_bus;
+    }
+
+    /// The last frame drawn, one DMG shade (0-3, lightest to darkest) per pixel in
+    /// row-major 160x144 order. Meaningless in CGB mode; see `cgb_frame_buffer`.
+    pub fn frame_buffer(&self) -> &[u8] {
+        self.ppu.get_frame_buffer()
+    }
+
+    /// The last frame drawn in CGB mode, as RGB555 colors in row-major 160x144 order.
+    pub fn cgb_frame_buffer(&self) -> &[u16] {
+        self.ppu.get_cgb_frame_buffer()
+    }
+
+    /// Writes the current frame into `rgba_out` as native 160x144 RGBA8 using the display palette
+    /// (or the game's own colors in CGB mode), for hosts that upload the frame to their own texture.
+    ///
+    /// Panics if `rgba_out` is not exactly `GB_WIDTH * GB_HEIGHT * 4` bytes.
+    pub fn render_into(&self, rgba_out: &mut [u8]) {
+        let frame_buffer = self.frame_buffer();
+        assert_eq!(
+            rgba_out.len(),
+            frame_buffer.len() * 4,
+            "render_into expects a {}x{} RGBA buffer",
+            GB_WIDTH,
+            GB_HEIGHT
+        );
+
+        if self.memory_bus.is_cgb() {
+            for (pixel, &rgb555) in rgba_out.chunks_exact_mut(4).zip(self.cgb_frame_buffer()) {
+                let color = palette::rgb555_to_rgb(rgb555);
+                pixel.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+            }
+            return;
+        }
+
+        for (pixel, &shade) in rgba_out.chunks_exact_mut(4).zip(frame_buffer.iter()) {
+            let color = self.palette[shade as usize % 4]; // Modulo 4 for safety
+            pixel.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+        }
+    }
+
+    /// Display colors for DMG shades 0-3.
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Replaces the display colors for DMG shades 0-3. Takes effect from the next
+    /// `render_into`.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Presses a joypad button (raising the joypad interrupt if the game is polling it).
+    pub fn press_button(&mut self, button: GbButton) {
+        self.memory_bus.press_button(button);
+    }
+
+    /// Releases a joypad button.
+    pub fn release_button(&mut self, button: GbButton) {
+        self.memory_bus.release_button(button);
+    }
+
+    /// Serializes the CPU, PPU, timer, APU and memory (including cartridge RAM, mapper
+    /// and RTC state) into a save state, so loading it continues exactly where it left off.
+    pub fn save_state(&self) -> Vec<u8> {
+        let state = SavedState {
+            cartridge_id: self.memory_bus.cartridge_id(),
+            memory_bus: Cow::Borrowed(&self.memory_bus),
+            ppu: Cow::Borrowed(&self.ppu),
+            timer: Cow::Borrowed(&self.timer),
+            apu: Cow::Borrowed(&self.apu),
+            cpu: Cow::Borrowed(&self.cpu),
+        };
+        let mut data = STATE_MAGIC.to_vec();
+        data.extend_from_slice(&STATE_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut data, &state).expect("save state serializes");
+        data
+    }
+
+    /// Restores a state produced by `save_state` for the currently inserted ROM.
+    /// On error the running game is left untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let payload = data
+            .strip_prefix(STATE_MAGIC)
+            .ok_or_else(|| "Not a save state file".to_string())?;
+        let (version, payload) = payload
+            .split_first_chunk::<4>()
+            .ok_or_else(|| "Save state is truncated".to_string())?;
+        let version = u32::from_le_bytes(*version);
+        if version != STATE_VERSION {
+            return Err(format!(
+                "Unsupported save state version {} (expected {})",
+                version, STATE_VERSION
+            ));
+        }
+        let state: SavedState =
+            bincode::deserialize(payload).map_err(|e| format!("Corrupt save state: {}", e))?;
+        if state.cartridge_id != self.memory_bus.cartridge_id() {
+            return Err("Save state was made with a different cartridge".to_string());
+        }
+
+        let mut memory_bus = state.memory_bus.into_owned();
+        let mut ppu = state.ppu.into_owned();
+        let mut apu = state.apu.into_owned();
+        let mut cpu = state.cpu.into_owned();
+        // Every check runs before anything is taken from the running game
+        memory_bus.check_fits(&self.memory_bus)?;
+        ppu.validate_loaded()?;
+        apu.restore_unsaved(&mut self.apu)?;
+        memory_bus.restore_unsaved(&mut self.memory_bus);
+        cpu.restore_unsaved(&mut self.cpu);
+
+        self.memory_bus = memory_bus;
+        self.ppu = ppu;
+        self.timer = state.timer.into_owned();
+        self.apu = apu;
+        self.cpu = cpu;
+        Ok(())
+    }
+
+    /// Returns and clears the text the game has sent over the serial port, e.g. the
+    /// "Passed"/"Failed" report of Blargg's test ROMs. Non-UTF-8 bytes become U+FFFD.
+    pub fn take_serial_output(&mut self) -> String {
+        String::from_utf8_lossy(&self.memory_bus.take_serial_output()).into_owned()
+    }
+
+    /// Sets a breakpoint at `addr`. Returns `false` if there already was one.
+    pub fn add_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.insert(addr)
+    }
+
+    /// Clears the breakpoint at `addr`. Returns `false` if there was none.
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    /// Addresses with a breakpoint set.
+    pub fn breakpoints(&self) -> &HashSet<u16> {
+        &self.breakpoints
+    }
+
+    /// Makes `run_frame_until_breakpoint` stop after an instruction accessing `addr`
+    /// as `kind` describes. Returns `false` if this watchpoint was already set.
+    pub fn add_watchpoint(&mut self, addr: u16, kind: WatchKind) -> bool {
+        self.memory_bus.add_watchpoint(addr, kind)
+    }
+
+    /// Like `add_watchpoint`, for every address in `range`.
+    pub fn add_watch_range(&mut self, range: RangeInclusive<u16>, kind: WatchKind) -> bool {
+        self.memory_bus.add_watch_range(range, kind)
+    }
+
+    /// Clears a watchpoint. Returns `false` if it wasn't set.
+    pub fn remove_watchpoint(&mut self, addr: u16, kind: WatchKind) -> bool {
+        self.memory_bus.remove_watchpoint(addr, kind)
+    }
+
+    /// Clears a watchpoint set with `add_watch_range`. Returns `false` if it wasn't set.
+    pub fn remove_watch_range(&mut self, range: RangeInclusive<u16>, kind: WatchKind) -> bool {
+        self.memory_bus.remove_watch_range(range, kind)
+    }
+
+    /// Executes exactly one CPU instruction and updates PPU/APU accordingly.
+    /// Returns `Ok(())` or an error string if the CPU encounters an error.
+    pub fn step_instruction(&mut self) -> Result<(), String> {
+        self.step().map(|_| ())
+    }
+
+    /// Runs until the current subroutine returns: stops right after the RET/RETI (or
+    /// taken conditional RET) that pops SP above its value on entry. Returns from nested
+    /// calls and interrupt handlers leave SP at or below that value, so they don't stop it.
+    /// Gives up with an error after `STEP_MAX_FRAMES` frames' worth of cycles.
+    pub fn step_out(&mut self) -> Result<(), String> {
+        let entry_sp = self.cpu.sp();
+        let max_cycles = self.cycles_per_frame as u64 * STEP_MAX_FRAMES;
+        let mut cycles: u64 = 0;
+        while cycles < max_cycles {
+            let opcode = self.memory_bus.peek_byte(self.cpu.pc());
+            let (executed_cycles, _) = self.step()?;
+            cycles += executed_cycles as u64;
+            let is_return = matches!(opcode, 0xC9 | 0xD9 | 0xC0 | 0xC8 | 0xD0 | 0xD8);
+            if is_return && self.cpu.sp() > entry_sp {
+                return Ok(());
+            }
+        }
+        Err(format!(
+            "Step out: no return from SP={:#06X} within {} frames",
+            entry_sp, STEP_MAX_FRAMES
+        ))
+    }
+
+    /// Executes one instruction, but runs a CALL or RST to completion: stops once PC
+    /// reaches the instruction after it with SP back at its current value, so recursive
+    /// calls passing through the same address don't stop early. Gives up with an error
+    /// after `STEP_MAX_FRAMES` frames' worth of cycles.
+    pub fn step_over(&mut self) -> Result<(), String> {
+        let pc = self.cpu.pc();
+        let return_addr = match self.memory_bus.peek_byte(pc) {
+            0xCD | 0xC4 | 0xCC | 0xD4 | 0xDC => pc.wrapping_add(3), // CALL (conditional)
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => pc.wrapping_add(1), // RST
+            _ => return self.step_instruction(),
+        };
+        let entry_sp = self.cpu.sp();
+        let max_cycles = self.cycles_per_frame as u64 * STEP_MAX_FRAMES;
+        let mut cycles: u64 = 0;
+        while cycles < max_cycles {
+            let (executed_cycles, _) = self.step()?;
+            cycles += executed_cycles as u64;
+            if self.cpu.pc() == return_addr && self.cpu.sp() >= entry_sp {
+                return Ok(());
+            }
+        }
+        Err(format!(
+            "Step over: the call at {:#06X} didn't return within {} frames",
+            pc, STEP_MAX_FRAMES
+        ))
+    }
+
+    /// Runs until the PPU enters VBlank and returns the frame it just finished.
+    /// If the LCD is off (so VBlank never comes) it gives up after one frame's worth of
+    /// cycles and returns the buffer as it is.
+    pub fn run_until_vblank(&mut self) -> CpuResult<&[u8; FRAME_BUFFER_SIZE]> {
+        let frame_cycles = DOTS_PER_SCANLINE * SCANLINES_PER_FRAME as u32;
+        let mut cycles: u32 = 0;
+        while cycles < frame_cycles {
+            let (executed_cycles, entered_vblank) = self.step()?;
+            if entered_vblank {
+                break;
+            }
+            cycles += executed_cycles;
+        }
+        Ok(self.ppu.get_frame_buffer())
+    }
+
+    /// Executes one CPU instruction and clocks every other component by the cycles it took.
+    /// Instructions with per-access timing clock them between their bus accesses as they
+    /// go; the rest of their cycles are clocked once they finish.
+    /// Returns the time that took in base-rate T-cycles (half the CPU cycles in CGB double
+    /// speed) and whether the PPU entered VBlank meanwhile.
+    fn step(&mut self) -> CpuResult<(u32, bool)> {
+        let Self {
+            cpu,
+            ppu,
+            apu,
+            timer,
+            memory_bus,
+            ..
+        } = self;
+
+        // 1. Step CPU - returns cycles executed or error
+        let mut ticked_cycles = 0;
+        let mut entered_vblank = false;
+        let executed_cycles = cpu.step_with_ticker(memory_bus, &mut |bus, cycles| {
+            ticked_cycles += cycles;
+            entered_vblank |= Self::tick_components(ppu, apu, timer, bus, cycles);
+        })? as u32;
+
+        // 2. Clock the components through whatever the CPU didn't tick while executing
+        let remaining_cycles = executed_cycles.saturating_sub(ticked_cycles);
+        entered_vblank |= Self::tick_components(ppu, apu, timer, memory_bus, remaining_cycles);
+
+        let base_cycles = if memory_bus.double_speed() {
+            executed_cycles / 2
+        } else {
+            executed_cycles
+        };
+        Ok((base_cycles, entered_vblank))
+    }
+
+    /// Clocks everything but the CPU by `cycles` CPU T-cycles. Returns whether the PPU
+    /// entered VBlank.
+    fn tick_components(
+        ppu: &mut Ppu,
+        apu: &mut Apu,
+        timer: &mut Timer,
+        memory_bus: &mut MemoryBus,
+        cycles: u32,
+    ) -> bool {
+        // In double speed the CPU, timer, serial and DMA run twice as fast as PPU and APU
+        let base_cycles = if memory_bus.double_speed() {
+            cycles / 2
+        } else {
+            cycles
+        };
+
+        // Advance any OAM DMA or serial transfer in progress, then step PPU and timer
+        memory_bus.tick_dma(cycles);
+        memory_bus.tick_serial(cycles);
+        // The PPU makes at most one mode change per step, so long stalls like the STOP
+        // speed switch are fed to it a machine cycle at a time
+        let mut entered_vblank = false;
+        let mut dots_left = base_cycles;
+        loop {
+            let dots = dots_left.min(4);
+            entered_vblank |= ppu.step(dots, memory_bus);
+            dots_left -= dots;
+            if dots_left == 0 {
+                break;
+            }
+        }
+        timer.step(cycles, memory_bus);
+
+        // Step APU at the base rate
+        apu.step(base_cycles, memory_bus);
+
+        entered_vblank
+    }
+
+    /// Runs the emulator components for approximately one frame's worth of CPU cycles,
+    /// scaled by the clock multiplier.
+    /// Returns `Ok(())` or an error string if the CPU encounters an error during the frame.
+    pub fn run_frame(&mut self) -> Result<(), String> {
+        self.run_frame_inner(false).map(|_| ())
+    }
+
+    /// Like `run_frame`, but stops early once an instruction leaves PC on a breakpoint
+    /// (before the instruction there runs) or makes an access a watchpoint is waiting
+    /// for. Returns why it stopped, or `None` if the whole frame ran. Resuming from a
+    /// breakpoint runs its instruction without stopping.
+    pub fn run_frame_until_breakpoint(&mut self) -> Result<Option<BreakReason>, String> {
+        self.memory_bus.take_watch_hit(); // Accesses made while stepping don't count
+        self.run_frame_inner(true)
+    }
+
+    fn run_frame_inner(&mut self, check_breakpoints: bool) -> Result<Option<BreakReason>, String> {
+        let mut cycles_this_frame: u32 = 0;
+        while cycles_this_frame < self.cycles_per_frame {
+            let (executed_cycles, _) = self.step()?;
+            cycles_this_frame += executed_cycles;
+            if !check_breakpoints {
+                continue;
+            }
+            if let Some(hit) = self.memory_bus.take_watch_hit() {
+                return Ok(Some(BreakReason::Watchpoint(hit)));
+            }
+            if self.breakpoints.contains(&self.cpu.pc()) {
+                return Ok(Some(BreakReason::Breakpoint(self.cpu.pc())));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -1,4 +1,18 @@
-use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Serialize};
+
+/// A Game Boy button, independent of any frontend's input handling. Frontends map
+/// their own keys or pads onto these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GbButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    Start,
+    Select,
+}
 
 /// Represents the state of the Game Boy's buttons.
 /// True = pressed, False = released (internal representation)
@@ -14,9 +28,11 @@ pub struct JoypadState {
     pub start: bool,
 }
 
-/// Manages Joypad state and interaction with the P1 register.
-#[derive(Clone, Debug, Default)]
+/// Manages Joypad state and interaction with the P1 register. Save states keep only
+/// the P1 selection; which buttons are held always comes from the player.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Joypad {
+    #[serde(skip)]
     state: JoypadState,
     // Store the P1 register's selectable bits (written by the game)
     p1_register_selection: u8, // Bits 4 and 5
@@ -78,112 +94,28 @@ impl Joypad {
         self.p1_register_selection = value & 0x30;
     }
 
-    /// Handles a key press event. Returns true if a Joypad interrupt should be requested.
-    pub fn key_down(&mut self, key: Keycode) -> bool {
-        let mut button_newly_pressed = false;
-        let mut selection_active = false;
-
-        match key {
-            // Directions (Check bit 4 of P1 register selection)
-            Keycode::Right | Keycode::D => {
-                if !self.state.right {
-                    button_newly_pressed = true;
-                    self.state.right = true;
-                }
-                if self.p1_register_selection & 0x10 == 0 {
-                    selection_active = true;
-                }
-            }
-            Keycode::Left | Keycode::A => {
-                // Remap 'A' key to Left
-                if !self.state.left {
-                    button_newly_pressed = true;
-                    self.state.left = true;
-                }
-                if self.p1_register_selection & 0x10 == 0 {
-                    selection_active = true;
-                }
-            }
-            Keycode::Up | Keycode::W => {
-                if !self.state.up {
-                    button_newly_pressed = true;
-                    self.state.up = true;
-                }
-                if self.p1_register_selection & 0x10 == 0 {
-                    selection_active = true;
-                }
-            }
-            Keycode::Down | Keycode::S => {
-                if !self.state.down {
-                    button_newly_pressed = true;
-                    self.state.down = true;
-                }
-                if self.p1_register_selection & 0x10 == 0 {
-                    selection_active = true;
-                }
-            }
-            // Actions (Check bit 5 of P1 register selection)
-            Keycode::Z | Keycode::J => {
-                // GB 'A' button
-                if !self.state.a {
-                    button_newly_pressed = true;
-                    self.state.a = true;
-                }
-                if self.p1_register_selection & 0x20 == 0 {
-                    selection_active = true;
-                }
-            }
-            Keycode::X | Keycode::K => {
-                // GB 'B' button
-                if !self.state.b {
-                    button_newly_pressed = true;
-                    self.state.b = true;
-                }
-                if self.p1_register_selection & 0x20 == 0 {
-                    selection_active = true;
-                }
-            }
-            Keycode::Backspace | Keycode::RShift => {
-                // GB 'Select' button
-                if !self.state.select {
-                    button_newly_pressed = true;
-                    self.state.select = true;
-                }
-                if self.p1_register_selection & 0x20 == 0 {
-                    selection_active = true;
-                }
-            }
-            Keycode::Return | Keycode::Space => {
-                // GB 'Start' button
-                if !self.state.start {
-                    button_newly_pressed = true;
-                    self.state.start = true;
-                }
-                if self.p1_register_selection & 0x20 == 0 {
-                    selection_active = true;
-                }
-            }
-            _ => {} // Ignore other keys
-        }
-
-        // Request Joypad interrupt only if a button state changed from released->pressed
-        // AND that button's group (Directions/Actions) is currently selected by the game.
-        button_newly_pressed && selection_active
+    /// Presses or releases a button. Returns true if a Joypad interrupt should be
+    /// requested (the button went from released to pressed while its group is selected).
+    pub fn set_button(&mut self, button: GbButton, pressed: bool) -> bool {
+        let (state, group_select_bit) = match button {
+            // Directions are selected by P1 bit 4, actions by bit 5
+            GbButton::Right => (&mut self.state.right, 0x10),
+            GbButton::Left => (&mut self.state.left, 0x10),
+            GbButton::Up => (&mut self.state.up, 0x10),
+            GbButton::Down => (&mut self.state.down, 0x10),
+            GbButton::A => (&mut self.state.a, 0x20),
+            GbButton::B => (&mut self.state.b, 0x20),
+            GbButton::Select => (&mut self.state.select, 0x20),
+            GbButton::Start => (&mut self.state.start, 0x20),
+        };
+        let newly_pressed = pressed && !*state;
+        *state = pressed;
+        newly_pressed && self.p1_register_selection & group_select_bit == 0
     }
 
-    /// Handles a key release event.
-    pub fn key_up(&mut self, key: Keycode) {
-        match key {
-            Keycode::Right | Keycode::D => self.state.right = false,
-            Keycode::Left | Keycode::A => self.state.left = false,
-            Keycode::Up | Keycode::W => self.state.up = false,
-            Keycode::Down | Keycode::S => self.state.down = false,
-            Keycode::Z | Keycode::J => self.state.a = false,
-            Keycode::X | Keycode::K => self.state.b = false,
-            Keycode::Backspace | Keycode::RShift => self.state.select = false,
-            Keycode::Return | Keycode::Space => self.state.start = false,
-            _ => {} // Ignore other keys
-        }
+    /// Holds the same buttons as `other`, keeping this P1 selection.
+    pub(crate) fn hold_buttons_of(&mut self, other: &Joypad) {
+        self.state = other.state.clone();
     }
 
     // Optional: Allow external access to raw state if needed elsewhere
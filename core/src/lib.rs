@@ -1,8 +1,16 @@
 pub mod apu;
 pub mod cpu;
+pub mod emulator;
 pub mod joypad;
 pub mod mbc;
 pub mod memory_bus;
 pub mod memory_map;
+pub mod palette;
+pub mod png;
 pub mod ppu;
+pub mod rom_file;
 pub mod rtc;
+pub mod state;
+pub mod timer;
+
+pub use joypad::GbButton;
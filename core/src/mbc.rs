@@ -1,23 +1,28 @@
 // src/mbc.rs
 
 /// Defines the Memory Bank Controller type used by the cartridge.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum MbcType {
+    #[default]
     NoMbc,
     Mbc1,
+    Mbc2,
     Mbc3,
-    // Future: Mbc2, Mbc5, etc.
+    Mbc5,
 }
 
 impl MbcType {
     /// Determines MBC Type, RAM presence, and Battery presence from the cartridge type code.
-    pub fn from_header(cartridge_type_code: u8) -> (Self, bool, bool) {
-        match cartridge_type_code {
+    pub fn from_header(cartridge_type_code: u8) -> Result<(Self, bool, bool), String> {
+        let mbc = match cartridge_type_code {
             0x00 => (MbcType::NoMbc, false, false),
             0x01 => (MbcType::Mbc1, false, false),
             0x02 => (MbcType::Mbc1, true, false),
             0x03 => (MbcType::Mbc1, true, true),
-            // 0x05 | 0x06 => panic!("MBC2 not implemented"), // Placeholder
+            // MBC2 RAM (512x4 bits) is built into the controller, so it's always present
+            0x05 => (MbcType::Mbc2, true, false), // MBC2
+            0x06 => (MbcType::Mbc2, true, true),  // MBC2+BATT
+            // ROM-only carts with external RAM
             0x08 => (MbcType::NoMbc, true, false), // ROM+RAM
             0x09 => (MbcType::NoMbc, true, true),  // ROM+RAM+BATT
             0x0F => (MbcType::Mbc3, false, true),  // MBC3+TIMER+BATT
@@ -25,8 +30,19 @@ impl MbcType {
             0x11 => (MbcType::Mbc3, false, false), // MBC3
             0x12 => (MbcType::Mbc3, true, false),  // MBC3+RAM
             0x13 => (MbcType::Mbc3, true, true),   // MBC3+RAM+BATT
-            // 0x19..=0x1E => panic!("MBC5 not implemented"), // Placeholder
-            _ => panic!("Unsupported cartridge type: {:02X}", cartridge_type_code),
-        }
+            0x19 => (MbcType::Mbc5, false, false), // MBC5
+            0x1A => (MbcType::Mbc5, true, false),  // MBC5+RAM
+            0x1B => (MbcType::Mbc5, true, true),   // MBC5+RAM+BATT
+            0x1C => (MbcType::Mbc5, false, false), // MBC5+RUMBLE
+            0x1D => (MbcType::Mbc5, true, false),  // MBC5+RUMBLE+RAM
+            0x1E => (MbcType::Mbc5, true, true),   // MBC5+RUMBLE+RAM+BATT
+            _ => {
+                return Err(format!(
+                    "Unsupported cartridge type: {:02X}",
+                    cartridge_type_code
+                ));
+            }
+        };
+        Ok(mbc)
     }
 }
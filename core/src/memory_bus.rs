@@ -1,29 +1,240 @@
-use crate::joypad::Joypad;
+use crate::joypad::{GbButton, Joypad};
 use crate::mbc::MbcType;
 use crate::memory_map::*;
-use crate::rtc::RtcRegisters;
-use sdl2::keyboard::Keycode; // Keep for key_down/key_up method signature
+use crate::rtc::{RTC_SAVE_SIZE, RtcRegisters};
+use crate::state::{big_array, big_array_2d};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// MBC2 has 512 half-bytes of RAM built into the controller, stored here one per byte.
+const MBC2_RAM_SIZE: usize = 512;
+
+/// T-cycles an OAM DMA spends per byte; 160 bytes take 160 machine cycles.
+const OAM_DMA_CYCLES_PER_BYTE: u32 = 4;
+
+/// T-cycles for an 8-bit serial transfer on the internal 8192 Hz clock.
+const SERIAL_TRANSFER_CYCLES: u32 = 8 * 512;
+
+/// Receives each byte the game sends over the link port.
+pub type SerialCallback = Box<dyn FnMut(u8)>;
+
+/// Holds the serial callback, if any. Cloning a `MemoryBus` does not clone the callback.
+#[derive(Default)]
+struct SerialSlot(Option<SerialCallback>);
+
+impl Clone for SerialSlot {
+    fn clone(&self) -> Self {
+        SerialSlot(None)
+    }
+}
+
+/// Called with the address and value of a bus access. Takes `&self` since reads do, so
+/// use a `Cell`/`RefCell` (or a channel) to collect what it sees.
+pub type AccessCallback = Box<dyn Fn(u16, u8)>;
+
+/// Optional callbacks fired for every CPU-visible access (`read_byte`/`write_byte`),
+/// e.g. to build a bus trace for comparing against hardware captures. Internal reads by
+/// the PPU, DMA and debug views (`peek_byte`) don't fire them, and neither does the
+/// register setup `Emulator::load_rom` does before the game starts. Cloning a
+/// `MemoryBus` does not clone the hooks.
+#[derive(Default)]
+pub struct BusHooks {
+    /// Fires after each read with the value the CPU got (0xFF if blocked).
+    pub on_read: Option<AccessCallback>,
+    /// Fires for each write attempt, including ones dropped as blocked.
+    pub on_write: Option<AccessCallback>,
+}
+
+impl Clone for BusHooks {
+    fn clone(&self) -> Self {
+        BusHooks::default()
+    }
+}
+
+/// How reads from the prohibited region 0xFEA0-0xFEFF behave. Writes are always ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NotUsableReads {
+    /// Always read 0xFF. Simple and what most games expect.
+    #[default]
+    OpenBus,
+    /// DMG/MGB/SGB: 0xFF while the PPU holds OAM (modes 2 and 3), 0x00 otherwise.
+    /// The OAM corruption these reads trigger on hardware is not modelled.
+    Dmg,
+    /// CGB (revision E): 0xFF while OAM is blocked, otherwise the high nibble of the
+    /// address' low byte repeated (0xFEA0 -> 0xAA, 0xFEB5 -> 0xBB).
+    Cgb,
+}
+
+/// Where `load_rom` takes the number of ROM banks from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RomSizePolicy {
+    /// Use the header's ROM size byte (0x0148), only warning if the file disagrees.
+    #[default]
+    Header,
+    /// Use the banks actually present in the file, so truncated or overdumped ROMs
+    /// can't select banks past the data.
+    FileSize,
+}
+
+/// What WRAM, VRAM, OAM and HRAM hold at power-on. Real hardware starts with
+/// semi-random contents, which a few games and demos read before initializing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BootRamInit {
+    /// All zeros. Deterministic, and what most games expect.
+    #[default]
+    Zero,
+    /// Pseudo-random bytes from the given seed, so a run can still be reproduced.
+    Random(u64),
+    /// Runs of 8 bytes alternating between 0x00 and 0xFF (starting with 0x00), the
+    /// pattern many DMG units show in uninitialized RAM.
+    HardwarePattern,
+}
+
+/// Which CPU accesses to an address trigger a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Any read.
+    Read,
+    /// Any write.
+    Write,
+    /// A write of this value.
+    WriteValue(u8),
+}
+
+/// A watchpoint that fired: the address accessed, how it was watched and the value
+/// read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: u16,
+    pub kind: WatchKind,
+    pub value: u8,
+}
+
+/// What `load_rom` takes from a cartridge header, once it has checked it's supported.
+struct CartridgeHeader {
+    mbc_type: MbcType,
+    has_ram: bool,
+    has_battery: bool,
+    cgb_mode: bool,
+    num_rom_banks: usize,
+    ram_size: usize,
+}
+
+impl CartridgeHeader {
+    fn parse(rom_data: &[u8]) -> Result<Self, String> {
+        if rom_data.len() < ROM_BANK_0_SIZE {
+            return Err(format!(
+                "ROM is {} bytes, too small for even one 16 KB bank",
+                rom_data.len()
+            ));
+        }
+        let cartridge_type_code = rom_data[0x0147];
+        let rom_size_code = rom_data[0x0148];
+        let ram_size_code = rom_data[0x0149];
+        let cgb_flag = rom_data[0x0143];
+
+        let (mbc_type, has_ram, has_battery) = MbcType::from_header(cartridge_type_code)?;
+        let num_rom_banks = match rom_size_code {
+            0x00..=0x08 => 2 << rom_size_code, // 2, 4, 8, ..., 512
+            0x52 => 72,
+            0x53 => 80,
+            0x54 => 96,
+            _ => return Err(format!("Unsupported ROM size code: {:02X}", rom_size_code)),
+        };
+        // MBC2 headers report no RAM; its RAM lives inside the controller.
+        let ram_size = match ram_size_code {
+            _ if mbc_type == MbcType::Mbc2 => MBC2_RAM_SIZE,
+            0x00 => 0,
+            0x01 => 2 * 1024,   // 2 KiB (rarely used?)
+            0x02 => 8 * 1024,   // 8 KiB (1 bank)
+            0x03 => 32 * 1024,  // 32 KiB (4 banks)
+            0x04 => 128 * 1024, // 128 KiB (16 banks)
+            0x05 => 64 * 1024,  // 64 KiB (8 banks)
+            _ => return Err(format!("Unsupported RAM size code: {:02X}", ram_size_code)),
+        };
+        Ok(CartridgeHeader {
+            mbc_type,
+            has_ram,
+            has_battery,
+            cgb_mode: cgb_flag == 0x80 || cgb_flag == 0xC0,
+            num_rom_banks,
+            ram_size,
+        })
+    }
+}
 
 /// Represents the Game Boy's memory map with MBC1/MBC3 support and input handling.
-#[derive(Clone)]
+///
+/// Save states hold the memory, mapper and I/O state. The `#[serde(skip)]` fields (the
+/// cartridge ROM and type, settings, frontend hooks and debugger state) come from the
+/// running game instead; see `restore_unsaved`.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MemoryBus {
     // Core Memory Areas
+    #[serde(skip)]
+    boot_rom: Option<Box<[u8; BOOT_ROM_SIZE]>>,
+    boot_rom_mapped: bool, // Cleared for good by a write to 0xFF50
+    #[serde(skip, default = "empty_rom_bank_0")]
     rom_bank_0: Box<[u8; ROM_BANK_0_SIZE]>,
-    vram: Box<[u8; VRAM_SIZE]>,
+    #[serde(with = "big_array_2d")]
+    vram: Box<[[u8; VRAM_SIZE]; 2]>, // Bank 1 (CGB only) holds more tiles and the BG map attributes
+    #[serde(with = "big_array")]
     wram_bank_0: Box<[u8; WRAM_BANK_0_SIZE]>,
-    wram_bank_n: Box<[u8; WRAM_BANK_N_SIZE]>, // Always Bank 1 for DMG/CGB in non-CGB mode
+    #[serde(with = "big_array_2d")]
+    wram_bank_n: Box<[[u8; WRAM_BANK_N_SIZE]; WRAM_SWITCHABLE_BANKS]>, // Banks 1-7; only bank 1 outside CGB mode
+    #[serde(with = "big_array")]
     oam: Box<[u8; OAM_SIZE]>,
+    #[serde(with = "big_array")]
     io_registers: Box<[u8; IO_REGISTERS_SIZE]>,
+    #[serde(with = "big_array")]
     hram: Box<[u8; HRAM_SIZE]>,
     interrupt_enable: u8, // FFFF (IE Register)
 
     // Cartridge Data & State
+    #[serde(skip)]
     full_rom_data: Vec<u8>,
     external_ram: Vec<u8>,
+    #[serde(skip)]
     mbc_type: MbcType,
+    #[serde(skip)]
     has_ram: bool,
+    #[serde(skip)]
     has_battery: bool, // For saving RAM/RTC state
+    #[serde(skip)]
+    has_rtc: bool, // MBC3+TIMER cartridges (types 0x0F/0x10)
+    #[serde(skip)]
+    ram_dirty: bool, // External RAM/RTC written since the last save
+    #[serde(skip)]
+    save_requested: bool, // Game disabled RAM after writing to it; a good time to flush
+    div_reset: bool,    // DIV was written; the Timer clears its internal counter
+    tima_written: bool, // TIMA was written; cancels a pending TMA reload
+    stat_written: bool, // STAT was written; the PPU applies the DMG STAT write bug
+    #[serde(skip)]
+    cgb_mode: bool, // Cartridge header requests CGB features (0x0143 = 0x80/0xC0)
+    #[serde(skip)]
+    not_usable_reads: NotUsableReads,
+    #[serde(skip)]
+    rom_size_policy: RomSizePolicy,
+    #[serde(skip)]
+    dmg_quirks: bool, // Emulate DMG-only hardware bugs games rely on (STAT write bug)
+
+    // CGB State
+    svbk: u8,                 // WRAM bank select (bits 0-2, 0 selects bank 1)
+    double_speed: bool,       // KEY1 bit 7: CPU runs at twice the base clock
+    speed_switch_armed: bool, // KEY1 bit 0: the next STOP switches speed
+    vbk: u8,                  // VRAM bank the CPU sees (bit 0)
+    bcps: u8,                 // BG palette RAM index (bits 0-5), auto-increment (bit 7)
+    ocps: u8,                 // OBJ palette RAM index (bits 0-5), auto-increment (bit 7)
+    // Palette RAM written through BCPD/OCPD: 8 palettes x 4 RGB555 colors each
+    #[serde(with = "big_array")]
+    bg_palette_ram: [u8; CGB_PALETTE_RAM_SIZE],
+    #[serde(with = "big_array")]
+    obj_palette_ram: [u8; CGB_PALETTE_RAM_SIZE],
 
     // MBC State
     current_rom_bank: usize,
@@ -35,6 +246,31 @@ pub struct MemoryBus {
     mbc1_rom_bank_lower: u8,
     mbc1_bank_upper: u8, // RAM bank or ROM bank upper bits
 
+    // OAM DMA State
+    dma_active: bool,
+    dma_source: u16, // Start address of the block being copied into OAM
+    dma_bytes_copied: usize,
+    dma_cycles: u32, // T-cycles elapsed since the transfer started
+
+    // Serial Port State
+    serial_cycles_remaining: u32, // T-cycles until the active transfer completes, 0 if idle
+    #[serde(skip)]
+    serial_callback: SerialSlot,
+    #[serde(skip)]
+    hooks: BusHooks,
+    #[serde(skip)]
+    watchpoints: Vec<(RangeInclusive<u16>, WatchKind)>, // Debugger watchpoints; few enough to scan
+    #[serde(skip)]
+    watch_hit: Cell<Option<WatchHit>>, // First watchpoint hit, until taken with take_watch_hit
+    #[serde(skip)]
+    last_write: Option<u16>, // Latest CPU write that reached memory, for the memory viewer
+    #[serde(skip)]
+    serial_output: Vec<u8>, // Bytes fully shifted out, until taken with take_serial_output
+    #[serde(skip)]
+    apu_writes: Vec<(u16, u8)>, // Sound register writes the APU hasn't applied yet
+    #[serde(skip)]
+    wave_ram_playing_byte: Option<u8>, // Wave RAM byte channel 3 is reading, while it plays
+
     // MBC3 specific RTC state
     rtc: RtcRegisters,
     rtc_latched: RtcRegisters,
@@ -42,13 +278,19 @@ pub struct MemoryBus {
     rtc_mapped_register: u8, // Which RTC reg (0x08-0x0C) is mapped via RAM bank select
 
     // Input State (delegated to Joypad struct)
-    pub joypad: Joypad, // Made public to allow external calls to key_down/up
+    pub joypad: Joypad, // Public for debug views; input goes through press/release_button
 
     // Calculated sizes (from ROM header)
+    #[serde(skip)]
     num_rom_banks: usize,
+    #[serde(skip)]
     num_ram_banks: usize,
 }
 
+fn empty_rom_bank_0() -> Box<[u8; ROM_BANK_0_SIZE]> {
+    Box::new([0; ROM_BANK_0_SIZE])
+}
+
 impl MemoryBus {
     pub fn new() -> Self {
         // Initialize IO registers with known default values after boot ROM (if skipping)
@@ -61,7 +303,7 @@ impl MemoryBus {
         io_regs[(TIMA_ADDR - IO_REGISTERS_START) as usize] = 0x00; // TIMA
         io_regs[(TMA_ADDR - IO_REGISTERS_START) as usize] = 0x00; // TMA
         io_regs[(TAC_ADDR - IO_REGISTERS_START) as usize] = 0x00; // TAC // PanDocs says F0? Check boot ROM behavior. Let's stick to 00 for now.
-        io_regs[(IF_ADDR - IO_REGISTERS_START) as usize] = 0xE1; // IF - Pandocs says 0xE1 after boot (VBL,LCD,TIM)
+        io_regs[(IF_ADDR - IO_REGISTERS_START) as usize] = POST_BOOT_IF; // IF - VBlank pending after boot
         io_regs[(NR10_ADDR - IO_REGISTERS_START) as usize] = 0x80; // NR10
         // TODO: Initialize NR11-NR52 with their defaults ...
         io_regs[(0xFF11 - IO_REGISTERS_START) as usize] = 0xBF; // NR11
@@ -96,10 +338,12 @@ impl MemoryBus {
         io_regs[(WX_ADDR - IO_REGISTERS_START) as usize] = 0x00; // WX
 
         let mut bus = MemoryBus {
+            boot_rom: None,
+            boot_rom_mapped: false,
             rom_bank_0: Box::new([0; ROM_BANK_0_SIZE]),
-            vram: Box::new([0; VRAM_SIZE]),
+            vram: Box::new([[0; VRAM_SIZE]; 2]),
             wram_bank_0: Box::new([0; WRAM_BANK_0_SIZE]),
-            wram_bank_n: Box::new([0; WRAM_BANK_N_SIZE]),
+            wram_bank_n: Box::new([[0; WRAM_BANK_N_SIZE]; WRAM_SWITCHABLE_BANKS]),
             oam: Box::new([0; OAM_SIZE]),
             io_registers: Box::new(io_regs), // Use initialized IO regs
             hram: Box::new([0; HRAM_SIZE]),
@@ -110,6 +354,25 @@ impl MemoryBus {
             mbc_type: MbcType::NoMbc, // Default, overwritten by load_rom
             has_ram: false,
             has_battery: false,
+            has_rtc: false,
+            ram_dirty: false,
+            save_requested: false,
+            div_reset: false,
+            tima_written: false,
+            stat_written: false,
+            cgb_mode: false,
+            not_usable_reads: NotUsableReads::default(),
+            rom_size_policy: RomSizePolicy::default(),
+            dmg_quirks: false,
+
+            svbk: 0,
+            vbk: 0,
+            bg_palette_ram: [0xFF; CGB_PALETTE_RAM_SIZE],
+            obj_palette_ram: [0xFF; CGB_PALETTE_RAM_SIZE],
+            bcps: 0,
+            ocps: 0,
+            double_speed: false,
+            speed_switch_armed: false,
 
             current_rom_bank: 1, // Default for banks 1-N
             current_ram_bank: 0,
@@ -119,6 +382,21 @@ impl MemoryBus {
             mbc1_rom_bank_lower: 1,
             mbc1_bank_upper: 0,
 
+            dma_active: false,
+            dma_source: 0,
+            dma_bytes_copied: 0,
+            dma_cycles: 0,
+
+            serial_cycles_remaining: 0,
+            serial_callback: SerialSlot::default(),
+            hooks: BusHooks::default(),
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+            last_write: None,
+            serial_output: Vec::new(),
+            apu_writes: Vec::new(),
+            wave_ram_playing_byte: None,
+
             rtc: RtcRegisters::new(),             // Use constructor
             rtc_latched: RtcRegisters::default(), // Will be cloned on latch
             rtc_latch_state: 0,
@@ -137,28 +415,26 @@ impl MemoryBus {
         bus
     }
 
-    /// Loads ROM data and configures MBC based on the header.
-    pub fn load_rom(&mut self, rom_data: &[u8]) {
-        if rom_data.len() < 0x150 {
-            panic!("ROM data is too small to contain a valid header");
-        }
+    /// Checks that `rom_data` is a ROM `load_rom` accepts: at least one 16 KB bank, with
+    /// a supported cartridge type, ROM size and RAM size in its header. Lets a frontend
+    /// reject a bad file before it ejects the running game.
+    pub fn check_rom(rom_data: &[u8]) -> Result<(), String> {
+        CartridgeHeader::parse(rom_data).map(|_| ())
+    }
 
-        // Basic header info
+    /// Loads ROM data and configures MBC based on the header. A ROM `check_rom` rejects
+    /// is reported as an error and leaves the bus untouched.
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), String> {
+        let header = CartridgeHeader::parse(rom_data)?;
         let cartridge_type_code = rom_data[0x0147];
-        let rom_size_code = rom_data[0x0148];
         let ram_size_code = rom_data[0x0149];
-
-        // Determine MBC Type, RAM, Battery using MbcType helper
-        (self.mbc_type, self.has_ram, self.has_battery) = MbcType::from_header(cartridge_type_code);
+        self.cgb_mode = header.cgb_mode;
+        (self.mbc_type, self.has_ram, self.has_battery) =
+            (header.mbc_type, header.has_ram, header.has_battery);
+        self.has_rtc = cartridge_type_code == 0x0F || cartridge_type_code == 0x10;
 
         // Determine ROM size and number of banks
-        self.num_rom_banks = match rom_size_code {
-            0x00..=0x08 => 2 << rom_size_code, // 2, 4, 8, ..., 512
-            0x52 => 72,
-            0x53 => 80,
-            0x54 => 96,
-            _ => panic!("Unsupported ROM size code: {:02X}", rom_size_code),
-        };
+        self.num_rom_banks = header.num_rom_banks;
         let expected_rom_size = self.num_rom_banks * ROM_BANK_N_SIZE;
         if rom_data.len() < expected_rom_size {
             println!(
@@ -174,19 +450,18 @@ impl MemoryBus {
                 expected_rom_size
             );
         }
-
-        // Determine RAM size and number of banks
-        let ram_size = match ram_size_code {
-            0x00 => 0,
-            0x01 => 2 * 1024,   // 2 KiB (rarely used?)
-            0x02 => 8 * 1024,   // 8 KiB (1 bank)
-            0x03 => 32 * 1024,  // 32 KiB (4 banks)
-            0x04 => 128 * 1024, // 128 KiB (16 banks)
-            0x05 => 64 * 1024,  // 64 KiB (8 banks)
-            _ => panic!("Unsupported RAM size code: {:02X}", ram_size_code),
-        };
+        if self.rom_size_policy == RomSizePolicy::FileSize && rom_data.len() != expected_rom_size {
+            // Partial trailing banks still count; their missing bytes read as 0xFF
+            self.num_rom_banks = rom_data.len().div_ceil(ROM_BANK_N_SIZE).max(2);
+            println!(
+                "Using {} ROM banks from the file size instead of the header's {}.",
+                self.num_rom_banks,
+                expected_rom_size / ROM_BANK_N_SIZE
+            );
+        }
 
         // Consistency checks
+        let ram_size = header.ram_size;
         if ram_size > 0 && !self.has_ram {
             println!(
                 "Warning: Cartridge header RAM size {:02X} indicates RAM, but type {:02X} usually lacks RAM.",
@@ -206,12 +481,8 @@ impl MemoryBus {
 
         // Store ROM data
         self.full_rom_data = rom_data.to_vec();
-        if self.full_rom_data.len() >= ROM_BANK_0_SIZE {
-            self.rom_bank_0
-                .copy_from_slice(&self.full_rom_data[0..ROM_BANK_0_SIZE]);
-        } else {
-            panic!("ROM is smaller than 16KB, cannot load into Bank 0.");
-        }
+        self.rom_bank_0
+            .copy_from_slice(&self.full_rom_data[0..ROM_BANK_0_SIZE]);
 
         // Initialize External RAM
         if self.has_ram && ram_size > 0 {
@@ -236,6 +507,22 @@ impl MemoryBus {
         self.rtc_latched = RtcRegisters::default();
         self.rtc_latch_state = 0;
         self.rtc_mapped_register = 0;
+        self.svbk = 0;
+        self.vbk = 0;
+        self.bg_palette_ram.fill(0xFF); // White, as the CGB boot ROM leaves them
+        self.obj_palette_ram.fill(0xFF);
+        self.bcps = 0;
+        self.ocps = 0;
+        self.double_speed = false;
+        self.speed_switch_armed = false;
+        self.ram_dirty = false;
+        self.save_requested = false;
+        self.dma_active = false;
+        self.dma_source = 0;
+        self.dma_bytes_copied = 0;
+        self.dma_cycles = 0;
+        self.serial_cycles_remaining = 0;
+        self.serial_output.clear();
 
         println!(
             "Loaded ROM: {} bytes. Type: {:?} ({:02X}), ROM Banks: {}, RAM Banks: {} ({} KB), Battery: {}",
@@ -247,10 +534,32 @@ impl MemoryBus {
             ram_size / 1024,
             self.has_battery
         );
+        Ok(())
+    }
+
+    /// Maps a 256-byte DMG boot ROM over 0x0000-0x00FF. It stays mapped until the
+    /// program writes a non-zero value to 0xFF50, after which cartridge ROM is visible.
+    pub fn load_boot_rom(&mut self, data: &[u8]) -> Result<(), String> {
+        let boot_rom: [u8; BOOT_ROM_SIZE] = data.try_into().map_err(|_| {
+            format!(
+                "Boot ROM must be exactly {} bytes, got {}",
+                BOOT_ROM_SIZE,
+                data.len()
+            )
+        })?;
+        self.boot_rom = Some(Box::new(boot_rom));
+        self.boot_rom_mapped = true;
+        Ok(())
     }
 
     // --- MBC Helper Logic --- (Kept internal to MemoryBus for now)
 
+    /// Wraps a selected ROM bank to the banks the cartridge has. Header sizes are powers
+    /// of two, where this is the same as dropping the unconnected high bank bits.
+    fn mask_rom_bank(&self, bank: usize) -> usize {
+        bank % self.num_rom_banks.max(1) // .max(1) guards against a zero bank count
+    }
+
     /// Updates the effective ROM bank for MBC1 based on current register values.
     fn update_mbc1_rom_bank(&mut self) {
         let mut bank = self.mbc1_rom_bank_lower as usize;
@@ -260,7 +569,7 @@ impl MemoryBus {
         if bank == 0 || bank == 0x20 || bank == 0x40 || bank == 0x60 {
             bank += 1;
         }
-        self.current_rom_bank = bank & (self.num_rom_banks.max(1) - 1); // .max(1) prevents subtract overflow if num_banks=0 somehow
+        self.current_rom_bank = self.mask_rom_bank(bank);
     }
 
     /// Updates the effective RAM bank for MBC1 based on current register values.
@@ -277,6 +586,27 @@ impl MemoryBus {
         }
     }
 
+    /// Handles the RAM enable register (0x0A in the low nibble enables).
+    fn set_ram_enabled(&mut self, value: u8) {
+        let was_enabled = self.ram_enabled;
+        self.ram_enabled = (value & 0x0F) == 0x0A;
+        // Games disable RAM once they finish saving
+        if was_enabled && !self.ram_enabled && self.ram_dirty {
+            self.save_requested = true;
+        }
+    }
+
+    /// MBC2 decodes 0x0000-0x3FFF by address bit 8: clear selects RAM enable,
+    /// set selects the 4-bit ROM bank number (0 maps to 1).
+    fn write_mbc2_control(&mut self, addr: u16, value: u8) {
+        if addr & 0x0100 == 0 {
+            self.set_ram_enabled(value);
+        } else {
+            let bank = (value & 0x0F) as usize;
+            self.current_rom_bank = self.mask_rom_bank(if bank == 0 { 1 } else { bank });
+        }
+    }
+
     // --- Interrupt Request Helper ---
     /// Sets the corresponding interrupt flag bit (0-4) in the IF register (0xFF0F).
     pub fn request_interrupt(&mut self, bit: u8) {
@@ -290,14 +620,70 @@ impl MemoryBus {
 
     // --- Read/Write ---
 
+    /// Returns `true` if the CPU may access `addr` right now. While an OAM DMA is
+    /// running the CPU can only reach 0xFF00-0xFFFF: the I/O registers, HRAM (where the
+    /// DMA wait loop lives) and IE, none of which sit on the bus the DMA copies over.
+    /// The PPU also locks VRAM during mode 3 and OAM during modes 2 and 3.
+    /// Internal consumers (PPU, timer, interrupt dispatch) use `peek_byte`/`get_io_reg`.
+    #[inline(always)]
+    fn cpu_can_access(&self, addr: u16) -> bool {
+        if self.dma_active {
+            return addr >= IO_REGISTERS_START;
+        }
+        match addr {
+            VRAM_START..=VRAM_END => self.ppu_mode() != 3,
+            OAM_START..=OAM_END => !self.oam_blocked(),
+            _ => true,
+        }
+    }
+
+    /// Current PPU mode as published by the PPU in STAT bits 0-1. Reported as 0
+    /// (HBlank) while the LCD is off, since the PPU then leaves VRAM and OAM alone.
+    #[inline(always)]
+    fn ppu_mode(&self) -> u8 {
+        let lcdc = self.io_registers[(LCDC_ADDR - IO_REGISTERS_START) as usize];
+        if lcdc & 0x80 == 0 {
+            return 0;
+        }
+        self.io_registers[(STAT_ADDR - IO_REGISTERS_START) as usize] & 0x03
+    }
+
+    /// Whether the PPU is reading OAM (modes 2 and 3).
+    #[inline(always)]
+    fn oam_blocked(&self) -> bool {
+        matches!(self.ppu_mode(), 2 | 3)
+    }
+
+    /// CPU-visible read. Respects bus conflicts (e.g. OAM DMA) and returns 0xFF when blocked.
     pub fn read_byte(&self, addr: u16) -> u8 {
+        let value = if self.cpu_can_access(addr) {
+            self.peek_byte(self.redirect_wave_ram(addr))
+        } else {
+            0xFF
+        };
+        if let Some(on_read) = &self.hooks.on_read {
+            on_read(addr, value);
+        }
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, value, false);
+        }
+        value
+    }
+
+    /// Reads a byte without CPU bus restrictions. Used by the PPU, DMA and debug views.
+    pub fn peek_byte(&self, addr: u16) -> u8 {
         // Make mutable for RTC latch read side-effect
         match addr {
             // ROM Bank 0 (Fixed)
-            ROM_BANK_0_START..=ROM_BANK_0_END => self.rom_bank_0[addr as usize],
+            ROM_BANK_0_START..=ROM_BANK_0_END => match &self.boot_rom {
+                Some(boot_rom) if self.boot_rom_mapped && (addr as usize) < BOOT_ROM_SIZE => {
+                    boot_rom[addr as usize]
+                }
+                _ => self.rom_bank_0[addr as usize],
+            },
             // ROM Bank N (Switchable)
             ROM_BANK_N_START..=ROM_BANK_N_END => {
-                let effective_rom_bank = self.current_rom_bank % self.num_rom_banks.max(1);
+                let effective_rom_bank = self.mask_rom_bank(self.current_rom_bank);
                 let rom_offset =
                     (effective_rom_bank * ROM_BANK_N_SIZE) + (addr - ROM_BANK_N_START) as usize;
                 if rom_offset < self.full_rom_data.len() {
@@ -307,10 +693,7 @@ impl MemoryBus {
                 }
             }
             // Video RAM (VRAM)
-            VRAM_START..=VRAM_END => {
-                // TODO: Proper PPU mode checking
-                self.vram[(addr - VRAM_START) as usize]
-            }
+            VRAM_START..=VRAM_END => self.peek_vram(self.vbk, addr),
             // External RAM / RTC Registers
             EXT_RAM_START..=EXT_RAM_END => {
                 if !self.ram_enabled {
@@ -323,6 +706,10 @@ impl MemoryBus {
                         // Reading latched RTC register
                         self.rtc_latched.read(self.rtc_mapped_register)
                     }
+                    MbcType::Mbc2 => {
+                        // 4-bit cells echoed across the whole region; upper nibble floats high
+                        self.external_ram[(addr as usize) & (MBC2_RAM_SIZE - 1)] | 0xF0
+                    }
                     _ => {
                         // Includes NoMbc RAM, Mbc1 RAM, and Mbc3 RAM access
                         if !self.has_ram || self.external_ram.is_empty() || self.num_ram_banks == 0
@@ -344,19 +731,16 @@ impl MemoryBus {
             WRAM_BANK_0_START..=WRAM_BANK_0_END => {
                 self.wram_bank_0[(addr - WRAM_BANK_0_START) as usize]
             }
-            // Work RAM Bank N (Fixed Bank 1 on DMG)
+            // Work RAM Bank N (Fixed Bank 1 on DMG, SVBK-selected on CGB)
             WRAM_BANK_N_START..=WRAM_BANK_N_END => {
-                self.wram_bank_n[(addr - WRAM_BANK_N_START) as usize]
+                self.wram_bank_n[self.wram_bank_index()][(addr - WRAM_BANK_N_START) as usize]
             }
             // Echo RAM
-            ECHO_RAM_START..=ECHO_RAM_END => self.read_byte(addr - 0x2000),
+            ECHO_RAM_START..=ECHO_RAM_END => self.peek_byte(addr - 0x2000),
             // OAM
-            OAM_START..=OAM_END => {
-                // TODO: Proper PPU mode checking
-                self.oam[(addr - OAM_START) as usize]
-            }
+            OAM_START..=OAM_END => self.oam[(addr - OAM_START) as usize],
             // Not Usable Area
-            NOT_USABLE_START..=NOT_USABLE_END => 0xFF, // Often reads 0xFF
+            NOT_USABLE_START..=NOT_USABLE_END => self.read_not_usable(addr),
             // I/O Registers
             IO_REGISTERS_START..=IO_REGISTERS_END => {
                 let offset = (addr - IO_REGISTERS_START) as usize;
@@ -364,7 +748,36 @@ impl MemoryBus {
                     P1_JOYP_ADDR => self.joypad.read_p1(), // Delegate to Joypad module
                     // Add reads for other registers that have side effects or specific behavior
                     STAT_ADDR => self.io_registers[offset] | 0x80, // Bit 7 always high
-                    DIV_ADDR | TIMA_ADDR | TMA_ADDR | TAC_ADDR | IF_ADDR | LCDC_ADDR |
+                    IF_ADDR => self.io_registers[offset] | 0xE0,   // Bits 5-7 unused, read as 1
+                    // Only bit 0 exists: set once the boot ROM has been unmapped
+                    BOOT_ROM_DISABLE_ADDR => {
+                        if self.boot_rom_mapped { 0xFE } else { 0xFF }
+                    }
+                    SVBK_ADDR => {
+                        // CGB: bank in bits 0-2, upper bits read as 1. Unmapped on DMG.
+                        if self.cgb_mode { 0xF8 | self.svbk } else { 0xFF }
+                    }
+                    VBK_ADDR => {
+                        // CGB: bank in bit 0, other bits read as 1. Unmapped on DMG.
+                        if self.cgb_mode { 0xFE | self.vbk } else { 0xFF }
+                    }
+                    BCPS_ADDR | OCPS_ADDR => {
+                        // CGB: bit 6 is unused and reads as 1. Unmapped on DMG.
+                        let index = if addr == BCPS_ADDR { self.bcps } else { self.ocps };
+                        if self.cgb_mode { index | 0x40 } else { 0xFF }
+                    }
+                    BCPD_ADDR => self.read_palette_data(&self.bg_palette_ram, self.bcps),
+                    OCPD_ADDR => self.read_palette_data(&self.obj_palette_ram, self.ocps),
+                    KEY1_ADDR => {
+                        // CGB: current speed in bit 7, armed switch in bit 0. Unmapped on DMG.
+                        if self.cgb_mode {
+                            0x7E | ((self.double_speed as u8) << 7)
+                                | self.speed_switch_armed as u8
+                        } else {
+                            0xFF
+                        }
+                    }
+                    DIV_ADDR | TIMA_ADDR | TMA_ADDR | TAC_ADDR | LCDC_ADDR |
                     SCY_ADDR | SCX_ADDR | LY_ADDR | LYC_ADDR | DMA_ADDR | BGP_ADDR |
                     OBP0_ADDR | OBP1_ADDR | WY_ADDR | WX_ADDR |
                     0xFF10..=0xFF26 | 0xFF30..=0xFF3F // Sound Regs placeholder
@@ -383,6 +796,17 @@ impl MemoryBus {
     }
 
     pub fn write_byte(&mut self, addr: u16, value: u8) {
+        if let Some(on_write) = &self.hooks.on_write {
+            on_write(addr, value);
+        }
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, value, true);
+        }
+        if !self.cpu_can_access(addr) {
+            return; // Dropped: the DMA owns the bus
+        }
+        self.last_write = Some(addr);
+        let addr = self.redirect_wave_ram(addr);
         match addr {
             // --- MBC Control Registers ---
             0x0000..=0x1FFF => {
@@ -391,15 +815,18 @@ impl MemoryBus {
                     MbcType::Mbc1 | MbcType::Mbc3 => {
                         // Only enable if cart has RAM or it's MBC3 (for RTC)
                         if self.has_ram || (self.has_battery && self.mbc_type == MbcType::Mbc3) {
-                            self.ram_enabled = (value & 0x0F) == 0x0A;
+                            self.set_ram_enabled(value);
                         }
                     }
+                    MbcType::Mbc2 => self.write_mbc2_control(addr, value),
+                    MbcType::Mbc5 if self.has_ram => self.set_ram_enabled(value),
                     _ => {}
                 }
             }
             0x2000..=0x3FFF => {
                 // ROM Bank Number (Lower)
                 match self.mbc_type {
+                    MbcType::Mbc2 => self.write_mbc2_control(addr, value),
                     MbcType::Mbc1 => {
                         let bank_low = value & 0x1F;
                         self.mbc1_rom_bank_lower = if bank_low == 0 { 1 } else { bank_low };
@@ -407,8 +834,19 @@ impl MemoryBus {
                     }
                     MbcType::Mbc3 => {
                         let bank = value & 0x7F;
-                        self.current_rom_bank = if bank == 0 { 1 } else { bank as usize };
-                        self.current_rom_bank &= self.num_rom_banks.max(1) - 1;
+                        self.current_rom_bank =
+                            self.mask_rom_bank(if bank == 0 { 1 } else { bank as usize });
+                    }
+                    MbcType::Mbc5 => {
+                        // 9-bit bank: low 8 bits at 0x2000-0x2FFF, bit 8 at 0x3000-0x3FFF.
+                        // Bank 0 is selectable; reads wrap it to the ROM size.
+                        if addr < 0x3000 {
+                            self.current_rom_bank =
+                                (self.current_rom_bank & 0x100) | value as usize;
+                        } else {
+                            self.current_rom_bank =
+                                (self.current_rom_bank & 0xFF) | (((value & 0x01) as usize) << 8);
+                        }
                     }
                     _ => {}
                 }
@@ -437,6 +875,15 @@ impl MemoryBus {
                         } else { /* Invalid */
                         }
                     }
+                    MbcType::Mbc5 => {
+                        // Up to 16 RAM banks (bit 3 drives the motor on rumble carts)
+                        self.current_ram_bank = (value & 0x0F) as usize;
+                        if self.num_ram_banks > 0 {
+                            self.current_ram_bank &= self.num_ram_banks - 1;
+                        } else {
+                            self.current_ram_bank = 0;
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -466,8 +913,8 @@ impl MemoryBus {
 
             // --- Normal Memory Areas ---
             VRAM_START..=VRAM_END => {
-                // TODO: PPU Mode check
-                self.vram[(addr - VRAM_START) as usize] = value;
+                // VBK only ever selects bank 1 in CGB mode
+                self.vram[self.vbk as usize][(addr - VRAM_START) as usize] = value;
             }
             EXT_RAM_START..=EXT_RAM_END => {
                 if !self.ram_enabled {
@@ -477,6 +924,11 @@ impl MemoryBus {
                     MbcType::Mbc3 if self.rtc_mapped_register >= 0x08 => {
                         // Writing to live RTC register
                         self.rtc.write(self.rtc_mapped_register, value);
+                        self.ram_dirty = true;
+                    }
+                    MbcType::Mbc2 => {
+                        self.external_ram[(addr as usize) & (MBC2_RAM_SIZE - 1)] = value & 0x0F;
+                        self.ram_dirty = true;
                     }
                     _ => {
                         // RAM access
@@ -489,6 +941,7 @@ impl MemoryBus {
                             (effective_ram_bank * EXT_RAM_SIZE) + (addr - EXT_RAM_START) as usize;
                         if ram_offset < self.external_ram.len() {
                             self.external_ram[ram_offset] = value;
+                            self.ram_dirty = true;
                         }
                     }
                 }
@@ -497,11 +950,11 @@ impl MemoryBus {
                 self.wram_bank_0[(addr - WRAM_BANK_0_START) as usize] = value;
             }
             WRAM_BANK_N_START..=WRAM_BANK_N_END => {
-                self.wram_bank_n[(addr - WRAM_BANK_N_START) as usize] = value;
+                let bank = self.wram_bank_index();
+                self.wram_bank_n[bank][(addr - WRAM_BANK_N_START) as usize] = value;
             }
             ECHO_RAM_START..=ECHO_RAM_END => self.write_byte(addr - 0x2000, value),
             OAM_START..=OAM_END => {
-                // TODO: PPU Mode check
                 self.oam[(addr - OAM_START) as usize] = value;
             }
             NOT_USABLE_START..=NOT_USABLE_END => { /* Write Ignored */ }
@@ -510,11 +963,15 @@ impl MemoryBus {
                 match addr {
                     P1_JOYP_ADDR => self.joypad.write_p1(value), // Delegate
                     DIV_ADDR => {
-                        // TODO: Reset internal timer divider counter
                         self.io_registers[offset] = 0; // Write resets register
+                        self.div_reset = true; // Timer clears its 16-bit counter on next step
+                    }
+                    TIMA_ADDR => {
+                        self.io_registers[offset] = value;
+                        self.tima_written = true; // Aborts a reload the Timer has pending
                     }
-                    TIMA_ADDR | TMA_ADDR | TAC_ADDR => {
-                        // TODO: Notify Timer component
+                    TMA_ADDR | TAC_ADDR => {
+                        // Picked up by the Timer on its next step
                         self.io_registers[offset] = value;
                     }
                     IF_ADDR => {
@@ -529,7 +986,6 @@ impl MemoryBus {
                     LCDC_ADDR | STAT_ADDR | SCY_ADDR | SCX_ADDR | LYC_ADDR | BGP_ADDR
                     | OBP0_ADDR | OBP1_ADDR | WY_ADDR | WX_ADDR => {
                         // TODO: Notify PPU component
-                        self.io_registers[offset] = value;
                         if addr == STAT_ADDR {
                             // Preserve read-only bits (lower 3, mode flags)
                             // Only bits 3-6 (interrupt enables) are writable
@@ -537,17 +993,86 @@ impl MemoryBus {
                             // Let PPU manage bits 0,1,2. We only write bits 3-6 here.
                             self.io_registers[offset] =
                                 (value & 0b0111_1000) | (self.io_registers[offset] & 0b1000_0111);
+                            self.stat_written = true;
+                        } else {
+                            self.io_registers[offset] = value;
+                        }
+                    }
+                    SVBK_ADDR => {
+                        if self.cgb_mode {
+                            self.svbk = value & 0x07;
+                        }
+                    }
+                    VBK_ADDR => {
+                        if self.cgb_mode {
+                            self.vbk = value & 0x01;
+                        }
+                    }
+                    BCPS_ADDR => {
+                        if self.cgb_mode {
+                            self.bcps = value & 0xBF;
+                        }
+                    }
+                    OCPS_ADDR => {
+                        if self.cgb_mode {
+                            self.ocps = value & 0xBF;
+                        }
+                    }
+                    BCPD_ADDR => {
+                        if self.cgb_mode {
+                            let palette_ram_accessible = self.ppu_mode() != 3;
+                            Self::write_palette_data(
+                                &mut self.bg_palette_ram,
+                                &mut self.bcps,
+                                value,
+                                palette_ram_accessible,
+                            );
+                        }
+                    }
+                    OCPD_ADDR => {
+                        if self.cgb_mode {
+                            let palette_ram_accessible = self.ppu_mode() != 3;
+                            Self::write_palette_data(
+                                &mut self.obj_palette_ram,
+                                &mut self.ocps,
+                                value,
+                                palette_ram_accessible,
+                            );
+                        }
+                    }
+                    KEY1_ADDR => {
+                        // Only bit 0 is writable; STOP then performs the switch
+                        if self.cgb_mode {
+                            self.speed_switch_armed = value & 0x01 != 0;
+                        }
+                    }
+                    BOOT_ROM_DISABLE_ADDR => {
+                        // One-way: once unmapped the boot ROM can't be brought back
+                        if value != 0 {
+                            self.boot_rom_mapped = false;
+                        }
+                    }
+                    SC_ADDR => {
+                        self.io_registers[offset] = value | 0x7E; // Bits 1-6 unused on DMG
+                        // Start on internal clock; with no link partner an external-clock
+                        // transfer never completes, as on hardware.
+                        if value & 0x81 == 0x81 {
+                            self.start_serial_transfer();
                         }
                     }
                     DMA_ADDR => {
                         self.io_registers[offset] = value;
                         self.perform_dma_transfer(value);
                     }
-                    0xFF10..=0xFF26 | 0xFF30..=0xFF3F => {
-                        // Sound Regs
-                        // TODO: Notify APU component
+                    0xFF10..=0xFF26 => {
+                        // Sound Regs: the APU applies the write (and its side effects,
+                        // e.g. the trigger bit) on its next step
+                        self.io_registers[offset] = value;
+                        self.apu_writes.push((addr, value));
+                    }
+                    0xFF30..=0xFF3F => {
+                        // Wave RAM
                         self.io_registers[offset] = value;
-                        // Some sound regs have write side effects (e.g., Trigger bit)
                     }
                     _ => {
                         // Default write for unhandled/simple IO regs
@@ -559,15 +1084,16 @@ impl MemoryBus {
                 self.hram[(addr - HRAM_START) as usize] = value;
             }
             INTERRUPT_ENABLE_REGISTER => {
-                self.interrupt_enable = value & 0x1F; // Only lower 5 bits used
+                // All 8 bits are stored and read back; only bits 0-4 select interrupts,
+                // and the CPU masks the rest when checking for pending ones.
+                self.interrupt_enable = value;
             }
         }
     }
 
-    /// Performs an OAM DMA transfer.
+    /// Starts an OAM DMA transfer. Bytes are copied one per machine cycle by `tick_dma`;
+    /// until all 160 have landed the CPU can only reach HRAM, the I/O registers and IE.
     fn perform_dma_transfer(&mut self, source_high_byte: u8) {
-        // TODO: This should block CPU access to most memory for ~160 machine cycles.
-        // This simplified version performs the copy instantly.
         let source_start_addr = (source_high_byte as u16) << 8;
         if source_start_addr >= 0xFE00 {
             // DMA from OAM/IO/HRAM/IE is often restricted or has weird behavior.
@@ -580,13 +1106,172 @@ impl MemoryBus {
             return;
         }
 
-        // Use read_byte to respect potential banking / read side effects (though DMA might bypass some?)
-        for i in 0..OAM_SIZE {
-            let byte_to_copy = self.read_byte(source_start_addr + i as u16);
-            // Direct write to OAM array, bypassing potential PPU lockouts? Check details.
-            self.oam[i] = byte_to_copy;
+        // Writing DMA again mid-transfer restarts it from the new source
+        self.dma_active = true;
+        self.dma_source = source_start_addr;
+        self.dma_bytes_copied = 0;
+        self.dma_cycles = 0;
+    }
+
+    /// Advances an in-progress OAM DMA by the given number of T-cycles,
+    /// copying every byte whose machine cycle has elapsed.
+    pub fn tick_dma(&mut self, cycles: u32) {
+        if !self.dma_active {
+            return;
+        }
+        self.dma_cycles += cycles;
+        let target = ((self.dma_cycles / OAM_DMA_CYCLES_PER_BYTE) as usize).min(OAM_SIZE);
+        while self.dma_bytes_copied < target {
+            let i = self.dma_bytes_copied;
+            // Use peek_byte to respect banking while bypassing the CPU's DMA bus lockout
+            self.oam[i] = self.peek_byte(self.dma_source + i as u16);
+            self.dma_bytes_copied += 1;
+        }
+        if self.dma_bytes_copied == OAM_SIZE {
+            self.dma_active = false;
+        }
+    }
+
+    /// Hands the outgoing SB byte to the serial callback and starts the 8-bit shift.
+    fn start_serial_transfer(&mut self) {
+        let data = self.io_registers[(SB_ADDR - IO_REGISTERS_START) as usize];
+        if let Some(callback) = self.serial_callback.0.as_mut() {
+            callback(data);
+        }
+        self.serial_cycles_remaining = SERIAL_TRANSFER_CYCLES;
+    }
+
+    /// Advances an in-progress serial transfer. On completion the sent byte is appended
+    /// to the serial output, SB holds 0xFF (nothing is connected), SC bit 7 clears and
+    /// the Serial interrupt is requested.
+    pub fn tick_serial(&mut self, cycles: u32) {
+        if self.serial_cycles_remaining == 0 {
+            return;
+        }
+        self.serial_cycles_remaining = self.serial_cycles_remaining.saturating_sub(cycles);
+        if self.serial_cycles_remaining == 0 {
+            let sb = (SB_ADDR - IO_REGISTERS_START) as usize;
+            self.serial_output.push(self.io_registers[sb]);
+            self.io_registers[sb] = 0xFF;
+            self.io_registers[(SC_ADDR - IO_REGISTERS_START) as usize] &= 0x7F;
+            self.request_interrupt(SERIAL_INTERRUPT_BIT);
+        }
+    }
+
+    /// Registers a callback that receives every byte the game transmits over serial
+    /// (e.g. the pass/fail text printed by Blargg's test ROMs).
+    pub fn set_serial_callback<F: FnMut(u8) + 'static>(&mut self, callback: F) {
+        self.serial_callback.0 = Some(Box::new(callback));
+    }
+
+    /// Removes and returns the serial callback, e.g. to move it onto a fresh bus.
+    pub fn take_serial_callback(&mut self) -> Option<SerialCallback> {
+        self.serial_callback.0.take()
+    }
+
+    /// Installs bus access hooks, replacing any existing ones.
+    pub fn set_hooks(&mut self, hooks: BusHooks) {
+        self.hooks = hooks;
+    }
+
+    /// Removes and returns the bus access hooks, e.g. to move them onto a fresh bus.
+    pub fn take_hooks(&mut self) -> BusHooks {
+        std::mem::take(&mut self.hooks)
+    }
+
+    /// Watches CPU accesses to `addr` (see [`WatchKind`]); hits are collected with
+    /// `take_watch_hit`. Returns `false` if this watchpoint was already set.
+    pub fn add_watchpoint(&mut self, addr: u16, kind: WatchKind) -> bool {
+        self.add_watch_range(addr..=addr, kind)
+    }
+
+    /// Watches CPU accesses to every address in `range`, like `add_watchpoint`.
+    pub fn add_watch_range(&mut self, range: RangeInclusive<u16>, kind: WatchKind) -> bool {
+        let watch = (range, kind);
+        if self.watchpoints.contains(&watch) {
+            return false;
+        }
+        self.watchpoints.push(watch);
+        true
+    }
+
+    /// Clears a watchpoint set with `add_watchpoint`. Returns `false` if it wasn't set.
+    pub fn remove_watchpoint(&mut self, addr: u16, kind: WatchKind) -> bool {
+        self.remove_watch_range(addr..=addr, kind)
+    }
+
+    /// Clears a watchpoint set with `add_watch_range`. Returns `false` if it wasn't set.
+    pub fn remove_watch_range(&mut self, range: RangeInclusive<u16>, kind: WatchKind) -> bool {
+        let watch = (range, kind);
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|other| *other != watch);
+        self.watchpoints.len() != count
+    }
+
+    /// Watched address ranges, in the order they were added.
+    pub fn watchpoints(&self) -> &[(RangeInclusive<u16>, WatchKind)] {
+        &self.watchpoints
+    }
+
+    /// Address of the most recent CPU write that wasn't blocked, if any.
+    pub fn last_write_addr(&self) -> Option<u16> {
+        self.last_write
+    }
+
+    /// Returns and clears the first watchpoint hit since the last call, if any.
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
+
+    /// Records a hit if a watchpoint matches this CPU access. Blocked accesses count too.
+    fn check_watchpoints(&self, addr: u16, value: u8, write: bool) {
+        if self.watch_hit.get().is_some() {
+            return;
+        }
+        let hit = self.watchpoints.iter().find(|(range, kind)| {
+            range.contains(&addr)
+                && match *kind {
+                    WatchKind::Read => !write,
+                    WatchKind::Write => write,
+                    WatchKind::WriteValue(watch_value) => write && value == watch_value,
+                }
+        });
+        if let Some(&(_, kind)) = hit {
+            self.watch_hit.set(Some(WatchHit { addr, kind, value }));
+        }
+    }
+
+    /// Set by the APU each step: the wave RAM byte (0-15) channel 3 is reading, or
+    /// `None` while it's stopped.
+    pub fn set_wave_ram_playing_byte(&mut self, index: Option<u8>) {
+        self.wave_ram_playing_byte = index;
+    }
+
+    /// While channel 3 plays, CPU accesses to any wave RAM address reach the byte the
+    /// channel is currently reading instead (CGB behavior; DMG mostly reads 0xFF).
+    #[inline(always)]
+    fn redirect_wave_ram(&self, addr: u16) -> u16 {
+        match self.wave_ram_playing_byte {
+            Some(index) if (WAVE_PATTERN_RAM_START..=WAVE_PATTERN_RAM_END).contains(&addr) => {
+                WAVE_PATTERN_RAM_START + index as u16
+            }
+            _ => addr,
         }
-        // Add CPU stall logic here or return required stall cycles.
+    }
+
+    /// Returns and clears the sound register writes made since the last call, in order.
+    pub fn take_apu_writes(&mut self) -> Vec<(u16, u8)> {
+        std::mem::take(&mut self.apu_writes)
+    }
+
+    /// Returns and clears the bytes sent over serial since the last call.
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.serial_output)
+    }
+
+    /// Whether an OAM DMA transfer currently owns the bus.
+    pub fn dma_active(&self) -> bool {
+        self.dma_active
     }
 
     /// Reads a 16-bit word (Little Endian).
@@ -612,19 +1297,21 @@ impl MemoryBus {
         }
     }
 
-    /// Called by the frontend when a key mapped to a Game Boy button is pressed down.
-    pub fn key_down(&mut self, key: Keycode) {
-        if self.joypad.key_down(key) {
-            self.request_interrupt(JOYPAD_INTERRUPT_BIT);
-        }
-        // Update P1 register reflecting the new button state immediately for polling reads
-        let p1_val = self.joypad.read_p1();
-        self.io_registers[(P1_JOYP_ADDR - IO_REGISTERS_START) as usize] = p1_val;
+    /// Presses a Game Boy button, requesting the Joypad interrupt if it wasn't already
+    /// held and its group is selected in P1.
+    pub fn press_button(&mut self, button: GbButton) {
+        self.set_button(button, true);
     }
 
-    /// Called by the frontend when a key mapped to a Game Boy button is released.
-    pub fn key_up(&mut self, key: Keycode) {
-        self.joypad.key_up(key);
+    /// Releases a Game Boy button.
+    pub fn release_button(&mut self, button: GbButton) {
+        self.set_button(button, false);
+    }
+
+    fn set_button(&mut self, button: GbButton, pressed: bool) {
+        if self.joypad.set_button(button, pressed) {
+            self.request_interrupt(JOYPAD_INTERRUPT_BIT);
+        }
         // Update P1 register reflecting the new button state immediately for polling reads
         let p1_val = self.joypad.read_p1();
         self.io_registers[(P1_JOYP_ADDR - IO_REGISTERS_START) as usize] = p1_val;
@@ -650,14 +1337,317 @@ impl MemoryBus {
             // Direct write, bypasses write_byte logic (use with caution!)
             self.io_registers[offset] = value;
         } else if addr == INTERRUPT_ENABLE_REGISTER {
-            self.interrupt_enable = value & 0x1F;
+            self.interrupt_enable = value;
         }
         // Add more cases (like IF register?) if needed by other components
     }
 
-    // Getter for VRAM needed by PPU
+    /// Selects how reads from 0xFEA0-0xFEFF behave (see [`NotUsableReads`]).
+    pub fn set_not_usable_reads(&mut self, mode: NotUsableReads) {
+        self.not_usable_reads = mode;
+    }
+
+    /// Selects where `load_rom` takes the ROM bank count from (see [`RomSizePolicy`]).
+    /// Must be set before the ROM is loaded.
+    pub fn set_rom_size_policy(&mut self, policy: RomSizePolicy) {
+        self.rom_size_policy = policy;
+    }
+
+    /// Refills WRAM, VRAM (both banks), OAM and HRAM as they'd be at power-on (see
+    /// [`BootRamInit`]). Call right after construction; anything written before is lost.
+    pub fn set_boot_ram_init(&mut self, init: BootRamInit) {
+        let mut rng_state = match init {
+            BootRamInit::Random(seed) => seed,
+            _ => 0,
+        };
+        for area in [
+            &mut self.wram_bank_0[..],
+            self.wram_bank_n.as_flattened_mut(),
+            self.vram.as_flattened_mut(),
+            &mut self.oam[..],
+            &mut self.hram[..],
+        ] {
+            for (offset, byte) in area.iter_mut().enumerate() {
+                *byte = match init {
+                    BootRamInit::Zero => 0x00,
+                    BootRamInit::Random(_) => splitmix64(&mut rng_state) as u8,
+                    BootRamInit::HardwarePattern if (offset / 8) % 2 == 0 => 0x00,
+                    BootRamInit::HardwarePattern => 0xFF,
+                };
+            }
+        }
+    }
+
+    /// Enables DMG hardware bugs that some games depend on: currently the STAT write
+    /// bug, where any STAT write in HBlank, VBlank or on an LY=LYC line raises a STAT
+    /// interrupt (Road Rash, Legend of Zerd). Never applies in CGB mode.
+    pub fn set_dmg_quirks(&mut self, enabled: bool) {
+        self.dmg_quirks = enabled;
+    }
+
+    /// Whether DMG hardware bugs are emulated (see `set_dmg_quirks`) for this cartridge.
+    pub fn dmg_quirks(&self) -> bool {
+        self.dmg_quirks && !self.cgb_mode
+    }
+
+    /// Value read from the prohibited region 0xFEA0-0xFEFF.
+    fn read_not_usable(&self, addr: u16) -> u8 {
+        match self.not_usable_reads {
+            NotUsableReads::OpenBus => 0xFF,
+            _ if self.oam_blocked() => 0xFF,
+            NotUsableReads::Dmg => 0x00,
+            NotUsableReads::Cgb => {
+                let nibble = (addr as u8) & 0xF0;
+                nibble | (nibble >> 4)
+            }
+        }
+    }
+
+    /// Whether the loaded cartridge runs in CGB mode (from the header's CGB flag).
+    pub fn is_cgb(&self) -> bool {
+        self.cgb_mode
+    }
+
+    /// Index into `wram_bank_n` of the bank mapped at 0xD000: SVBK's bank (0 meaning 1)
+    /// in CGB mode, otherwise always bank 1.
+    fn wram_bank_index(&self) -> usize {
+        if self.cgb_mode {
+            (self.svbk as usize).max(1) - 1
+        } else {
+            0
+        }
+    }
+
+    /// Reads a VRAM byte from the given bank regardless of VBK, as the PPU does. Bank 1
+    /// only exists in CGB mode; on DMG every bank number reads bank 0.
+    pub fn peek_vram(&self, bank: u8, addr: u16) -> u8 {
+        let bank = if self.cgb_mode { bank as usize & 1 } else { 0 };
+        self.vram[bank][(addr - VRAM_START) as usize]
+    }
+
+    /// RGB555 color `color` (0-3) of CGB background palette `palette` (0-7).
+    pub fn bg_palette_color(&self, palette: u8, color: u8) -> u16 {
+        Self::palette_color(&self.bg_palette_ram, palette, color)
+    }
+
+    /// RGB555 color `color` (0-3) of CGB object palette `palette` (0-7).
+    pub fn obj_palette_color(&self, palette: u8, color: u8) -> u16 {
+        Self::palette_color(&self.obj_palette_ram, palette, color)
+    }
+
+    fn palette_color(palette_ram: &[u8; CGB_PALETTE_RAM_SIZE], palette: u8, color: u8) -> u16 {
+        let offset = ((palette as usize & 0x07) * 4 + (color as usize & 0x03)) * 2;
+        u16::from_le_bytes([palette_ram[offset], palette_ram[offset + 1]]) & 0x7FFF
+    }
+
+    /// BCPD/OCPD read: the byte at the index register. Palette RAM can't be read while
+    /// the PPU is drawing (mode 3), and doesn't exist on DMG.
+    fn read_palette_data(&self, palette_ram: &[u8; CGB_PALETTE_RAM_SIZE], index: u8) -> u8 {
+        if !self.cgb_mode || self.ppu_mode() == 3 {
+            return 0xFF;
+        }
+        palette_ram[(index & 0x3F) as usize]
+    }
+
+    /// BCPD/OCPD write: stores the byte at the index register (unless the PPU is
+    /// drawing) and, with auto-increment on, advances the index either way.
+    fn write_palette_data(
+        palette_ram: &mut [u8; CGB_PALETTE_RAM_SIZE],
+        index: &mut u8,
+        value: u8,
+        accessible: bool,
+    ) {
+        if accessible {
+            palette_ram[(*index & 0x3F) as usize] = value;
+        }
+        if *index & 0x80 != 0 {
+            *index = 0x80 | ((*index + 1) & 0x3F);
+        }
+    }
+
+    /// Whether the CPU runs in CGB double-speed mode. The PPU and APU keep the base rate.
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// Called by STOP: toggles the CPU speed if a switch was armed through KEY1.
+    /// Returns whether the switch happened.
+    pub fn perform_speed_switch(&mut self) -> bool {
+        if !self.cgb_mode || !self.speed_switch_armed {
+            return false;
+        }
+        self.speed_switch_armed = false;
+        self.double_speed = !self.double_speed;
+        true
+    }
+
+    /// Writes battery-backed RAM to `path` as raw bytes, followed by the 48-byte RTC
+    /// block for MBC3+TIMER cartridges (the layout BGB/SameBoy use). Does nothing
+    /// for cartridges without a battery.
+    pub fn save_ram(&self, path: &Path) -> io::Result<()> {
+        if !self.has_battery {
+            return Ok(());
+        }
+        let mut data = self.external_ram.clone();
+        if self.has_rtc {
+            let mut rtc = self.rtc.clone();
+            rtc.update(); // Stamp the save with the current time
+            data.extend_from_slice(&rtc.to_save_bytes(&self.rtc_latched));
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        fs::write(path, data)
+    }
+
+    /// Loads battery-backed RAM (and RTC, if present) from `path`. A save shorter than
+    /// RAM only fills the start of it; extra bytes beyond RAM and the RTC block are ignored.
+    /// Does nothing for cartridges without a battery.
+    pub fn load_ram(&mut self, path: &Path) -> io::Result<()> {
+        if !self.has_battery {
+            return Ok(());
+        }
+        let data = fs::read(path)?;
+        if data.len() != self.external_ram.len()
+            && data.len() != self.external_ram.len() + RTC_SAVE_SIZE
+        {
+            println!(
+                "Warning: Save file size ({}) doesn't match cartridge RAM ({}); loading what fits.",
+                data.len(),
+                self.external_ram.len()
+            );
+        }
+        self.load_external_ram(&data);
+        if self.has_rtc
+            && let Some(rtc_bytes) = data.get(self.external_ram.len()..)
+            && let Some((live, latched)) = RtcRegisters::from_save_bytes(rtc_bytes)
+        {
+            self.rtc = live;
+            self.rtc_latched = latched;
+        }
+        self.ram_dirty = false;
+        Ok(())
+    }
+
+    /// Header bytes identifying the cartridge: title (0x0134-0x0143), header checksum
+    /// (0x014D) and global checksum (0x014E-0x014F). Save states record it so one made
+    /// with a different game is rejected.
+    pub(crate) fn cartridge_id(&self) -> [u8; 19] {
+        let mut id = [0u8; 19];
+        id[..16].copy_from_slice(&self.rom_bank_0[0x0134..=0x0143]);
+        id[16..].copy_from_slice(&self.rom_bank_0[0x014D..=0x014F]);
+        id
+    }
+
+    /// Checks that a bus just read from a save state fits `running`'s cartridge.
+    pub(crate) fn check_fits(&self, running: &MemoryBus) -> Result<(), String> {
+        if self.external_ram.len() != running.external_ram.len() {
+            return Err(format!(
+                "Save state RAM size ({}) doesn't match cartridge RAM ({})",
+                self.external_ram.len(),
+                running.external_ram.len()
+            ));
+        }
+        if self.boot_rom_mapped && running.boot_rom.is_none() {
+            return Err("Save state was made while the boot ROM was running".to_string());
+        }
+        Ok(())
+    }
+
+    /// Completes a bus just read from a save state with what states leave out, taken
+    /// from `running`, the bus of the game being played: the cartridge, settings,
+    /// frontend hooks, debugger state and the buttons held right now. Call
+    /// `check_fits` first.
+    pub(crate) fn restore_unsaved(&mut self, running: &mut MemoryBus) {
+        self.boot_rom = running.boot_rom.take();
+        self.rom_bank_0 = std::mem::replace(&mut running.rom_bank_0, empty_rom_bank_0());
+        self.full_rom_data = std::mem::take(&mut running.full_rom_data);
+        self.mbc_type = running.mbc_type;
+        self.has_ram = running.has_ram;
+        self.has_battery = running.has_battery;
+        self.has_rtc = running.has_rtc;
+        self.cgb_mode = running.cgb_mode;
+        self.not_usable_reads = running.not_usable_reads;
+        self.rom_size_policy = running.rom_size_policy;
+        self.dmg_quirks = running.dmg_quirks;
+        self.serial_callback = std::mem::take(&mut running.serial_callback);
+        self.hooks = running.take_hooks();
+        self.watchpoints = std::mem::take(&mut running.watchpoints);
+        self.serial_output = std::mem::take(&mut running.serial_output);
+        self.num_rom_banks = running.num_rom_banks;
+        self.num_ram_banks = running.num_ram_banks;
+        self.joypad.hold_buttons_of(&running.joypad);
+
+        // Keep registers within what the hardware can hold
+        self.svbk &= 0x07;
+        self.vbk &= 0x01;
+        self.bcps &= 0xBF;
+        self.ocps &= 0xBF;
+        self.dma_bytes_copied = self.dma_bytes_copied.min(OAM_SIZE);
+        // The clock kept running in real time since the state was saved
+        self.rtc.update();
+
+        // RAM now differs from the .sav on disk; flush it at the next opportunity
+        self.ram_dirty = !self.external_ram.is_empty();
+        self.save_requested = false;
+    }
+
+    /// Returns `true` (once) if DIV was written since the last call.
+    pub fn take_div_reset(&mut self) -> bool {
+        std::mem::take(&mut self.div_reset)
+    }
+
+    /// Returns `true` (once) if TIMA was written since the last call.
+    pub fn take_tima_write(&mut self) -> bool {
+        std::mem::take(&mut self.tima_written)
+    }
+
+    /// Returns `true` (once) if STAT was written since the last call.
+    pub fn take_stat_write(&mut self) -> bool {
+        std::mem::take(&mut self.stat_written)
+    }
+
+    /// Returns `true` once after the game disables external RAM following a write,
+    /// signalling the frontend to flush the save file.
+    pub fn take_save_request(&mut self) -> bool {
+        let requested = self.save_requested;
+        self.save_requested = false;
+        if requested {
+            self.ram_dirty = false;
+        }
+        requested
+    }
+
+    /// Size in bytes of the loaded ROM image.
+    pub fn total_rom_size(&self) -> usize {
+        self.full_rom_data.len()
+    }
+
+    /// Size in bytes of the cartridge's external RAM (0 if it has none).
+    pub fn total_ram_size(&self) -> usize {
+        self.external_ram.len()
+    }
+
+    /// Whether the loaded cartridge has battery-backed RAM that should be persisted.
+    pub fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    /// Raw contents of the cartridge's external RAM (empty if it has none).
+    pub fn external_ram(&self) -> &[u8] {
+        &self.external_ram
+    }
+
+    /// Restores external RAM from a save. Extra bytes are ignored; a short save
+    /// only overwrites the start of RAM.
+    pub fn load_external_ram(&mut self, data: &[u8]) {
+        let len = data.len().min(self.external_ram.len());
+        self.external_ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    // Getter for VRAM (bank 0) needed by PPU
     pub fn get_vram(&self) -> &[u8; VRAM_SIZE] {
-        &self.vram
+        &self.vram[0]
     }
     // Getter for OAM needed by PPU
     pub fn get_oam(&self) -> &[u8; OAM_SIZE] {
@@ -686,3 +1676,12 @@ impl fmt::Debug for MemoryBus {
             .finish_non_exhaustive()
     }
 }
+
+/// SplitMix64: a small, seedable generator for `BootRamInit::Random` (any seed works).
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
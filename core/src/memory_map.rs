@@ -5,6 +5,9 @@ pub const ROM_BANK_0_START: u16 = 0x0000;
 pub const ROM_BANK_0_END: u16 = 0x3FFF;
 pub const ROM_BANK_0_SIZE: usize = (ROM_BANK_0_END - ROM_BANK_0_START + 1) as usize; // 16 KiB
 
+// The DMG boot ROM overlays the start of bank 0 until 0xFF50 is written
+pub const BOOT_ROM_SIZE: usize = 0x100; // 256 bytes, 0x0000-0x00FF
+
 pub const ROM_BANK_N_START: u16 = 0x4000;
 pub const ROM_BANK_N_END: u16 = 0x7FFF;
 pub const ROM_BANK_N_SIZE: usize = (ROM_BANK_N_END - ROM_BANK_N_START + 1) as usize; // 16 KiB
@@ -24,6 +27,7 @@ pub const WRAM_BANK_0_SIZE: usize = (WRAM_BANK_0_END - WRAM_BANK_0_START + 1) as
 pub const WRAM_BANK_N_START: u16 = 0xD000;
 pub const WRAM_BANK_N_END: u16 = 0xDFFF;
 pub const WRAM_BANK_N_SIZE: usize = (WRAM_BANK_N_END - WRAM_BANK_N_START + 1) as usize; // 4 KiB (Bank 1 for DMG/CGB compat)
+pub const WRAM_SWITCHABLE_BANKS: usize = 7; // CGB banks 1-7 selected by SVBK; DMG only has bank 1
 
 pub const ECHO_RAM_START: u16 = 0xE000;
 pub const ECHO_RAM_END: u16 = 0xFDFF;
@@ -60,6 +64,8 @@ pub const TMA_ADDR: u16 = 0xFF06; // Timer Modulo (R/W)
 pub const TAC_ADDR: u16 = 0xFF07; // Timer Control (R/W)
 // 0xFF08 to 0xFF0E - Unused
 pub const IF_ADDR: u16 = 0xFF0F; // Interrupt Flag (R/W)
+/// IF as the DMG boot ROM leaves it: VBlank requested, unused bits 5-7 high.
+pub const POST_BOOT_IF: u8 = 0xE1;
 
 // --- Sound Registers ---
 // Range: 0xFF10 - 0xFF26 (NR registers), 0xFF30 - 0xFF3F (Wave Pattern RAM)
@@ -103,21 +109,23 @@ pub const OBP0_ADDR: u16 = 0xFF48; // Object Palette 0 Data (R/W) - Non CGB
 pub const OBP1_ADDR: u16 = 0xFF49; // Object Palette 1 Data (R/W) - Non CGB
 pub const WY_ADDR: u16 = 0xFF4A; // Window Y Position (R/W)
 pub const WX_ADDR: u16 = 0xFF4B; // Window X Position plus 7 (R/W)
-// 0xFF4C - Unused (Often KEY1 on CGB for Speed Switch)
-// 0xFF4D - KEY1 (CGB Speed Switch)
+// 0xFF4C - Unused
+pub const KEY1_ADDR: u16 = 0xFF4D; // CGB Speed Switch (bit 7 current speed, bit 0 armed)
 // 0xFF4E - Unused
-// 0xFF4F - VBK (CGB VRAM Bank Select)
-// 0xFF50 - Boot ROM Disable (Write-only)
+pub const VBK_ADDR: u16 = 0xFF4F; // CGB VRAM Bank Select (bit 0)
+pub const BOOT_ROM_DISABLE_ADDR: u16 = 0xFF50; // Boot ROM Disable (non-zero write unmaps it)
 // 0xFF51 - HDMA1 (CGB HDMA Source High)
 // 0xFF52 - HDMA2 (CGB HDMA Source Low)
 // 0xFF53 - HDMA3 (CGB HDMA Destination High)
 // 0xFF54 - HDMA4 (CGB HDMA Destination Low)
 // 0xFF55 - HDMA5 (CGB HDMA Length/Mode/Start)
-// 0xFF68 - BCPS/BGPI (CGB Background Palette Index)
-// 0xFF69 - BCPD/BGPD (CGB Background Palette Data)
-// 0xFF6A - OCPS/OBPI (CGB Object Palette Index)
-// 0xFF6B - OCPD/OBPD (CGB Object Palette Data)
-// 0xFF70 - SVBK (CGB WRAM Bank Select)
+pub const BCPS_ADDR: u16 = 0xFF68; // CGB Background Palette Index (bit 7 auto-increment)
+pub const BCPD_ADDR: u16 = 0xFF69; // CGB Background Palette Data
+pub const OCPS_ADDR: u16 = 0xFF6A; // CGB Object Palette Index (bit 7 auto-increment)
+pub const OCPD_ADDR: u16 = 0xFF6B; // CGB Object Palette Data
+/// CGB palette RAM per layer: 8 palettes of 4 little-endian RGB555 colors.
+pub const CGB_PALETTE_RAM_SIZE: usize = 64;
+pub const SVBK_ADDR: u16 = 0xFF70; // WRAM Bank Select (R/W) - CGB only
 // ... other CGB registers up to 0xFF7F
 
 // --- Interrupt Bits (for IF Register 0xFF0F and IE Register 0xFFFF) ---
@@ -0,0 +1,69 @@
+//! Display colors for the four DMG shades, and `.pal` palette files.
+
+use std::fs;
+use std::path::Path;
+
+/// A color with 8 bits per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+}
+
+/// Colors for DMG shades 0-3, lightest first.
+pub type Palette = [Rgb; 4];
+
+/// The original DMG's green LCD.
+pub const DEFAULT_PALETTE: Palette = [
+    Rgb::new(0x9B, 0xBC, 0x0F), // Lightest Green (Color 0 / White)
+    Rgb::new(0x8B, 0xAC, 0x0F), // Light Green   (Color 1 / Light Gray)
+    Rgb::new(0x30, 0x62, 0x30), // Dark Green    (Color 2 / Dark Gray)
+    Rgb::new(0x0F, 0x38, 0x0F), // Darkest Green (Color 3 / Black)
+];
+
+/// Parses a `.pal` palette: exactly four `RRGGBB` hex colors, one per line, lightest first.
+/// A leading `#` is optional; blank lines and lines starting with `;` are ignored.
+pub fn parse_palette(text: &str) -> Result<Palette, String> {
+    let colors = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        .map(parse_hex_color)
+        .collect::<Result<Vec<Rgb>, String>>()?;
+
+    colors.try_into().map_err(|colors: Vec<Rgb>| {
+        format!("Palette needs exactly 4 colors, found {}", colors.len())
+    })
+}
+
+/// Reads and parses a `.pal` file.
+pub fn load_palette(path: &Path) -> Result<Palette, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read palette '{}': {}", path.display(), e))?;
+    parse_palette(&text).map_err(|e| format!("Invalid palette '{}': {}", path.display(), e))
+}
+
+/// Expands a CGB RGB555 color (red in bits 0-4) to 8 bits per channel.
+pub fn rgb555_to_rgb(rgb555: u16) -> Rgb {
+    let channel = |shift: u16| {
+        let c5 = ((rgb555 >> shift) & 0x1F) as u8;
+        (c5 << 3) | (c5 >> 2)
+    };
+    Rgb::new(channel(0), channel(5), channel(10))
+}
+
+fn parse_hex_color(line: &str) -> Result<Rgb, String> {
+    let hex = line.strip_prefix('#').unwrap_or(line);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not an RRGGBB hex color", line));
+    }
+    let rgb = u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+    Ok(Rgb::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
@@ -0,0 +1,63 @@
+//! Minimal PNG encoder for screenshots: 8-bit RGBA, no interlacing, filter type 0.
+
+use flate2::Compression;
+use flate2::Crc;
+use flate2::write::ZlibEncoder;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// IHDR color type for truecolor with alpha.
+const COLOR_TYPE_RGBA: u8 = 6;
+
+/// Encodes `rgba` (`width * height` pixels of 4 bytes, row-major) as a PNG file.
+///
+/// Panics if `rgba` is not exactly `width * height * 4` bytes.
+pub fn encode_rgba(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let row_len = width as usize * 4;
+    assert_eq!(
+        rgba.len(),
+        row_len * height as usize,
+        "encode_rgba expects a {}x{} RGBA buffer",
+        width,
+        height
+    );
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, COLOR_TYPE_RGBA, 0, 0, 0]); // Depth, color, compression, filter, interlace
+
+    // Each scanline starts with its filter type; 0 leaves the bytes as they are
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in rgba.chunks_exact(row_len.max(1)) {
+        encoder
+            .write_all(&[0])
+            .expect("writing to a Vec can't fail");
+        encoder.write_all(row).expect("writing to a Vec can't fail");
+    }
+    let idat = encoder.finish().expect("writing to a Vec can't fail");
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &idat);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Writes `rgba` to `path` as a PNG (see `encode_rgba`).
+pub fn write_rgba(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    fs::write(path, encode_rgba(width, height, rgba))
+}
+
+/// Appends a chunk: length, type, data and the CRC of type and data.
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
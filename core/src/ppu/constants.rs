@@ -19,11 +19,10 @@ pub const VRAM_DEBUG_BUFFER_SIZE: usize = VRAM_DEBUG_WIDTH * VRAM_DEBUG_HEIGHT;
 pub const DOTS_PER_SCANLINE: u32 = 456;
 pub const SCANLINES_PER_FRAME: u8 = 154; // 144 visible + 10 VBlank
 
-// Mode Durations (approximate, Mode 3 varies slightly)
+// Mode Durations. Mode 3 runs until the pixel FIFO has output the line, so it is
+// lengthened by SCX fine scroll, the window and sprites; Mode 0 takes the rest.
 pub const MODE2_OAM_SCAN_DOTS: u32 = 80;
 pub const MODE3_VRAM_READ_DOTS: u32 = 172; // Minimum duration
-// Mode 0 duration varies based on Mode 3, ensures total is DOTS_PER_SCANLINE
-// MODE0_HBLANK_DOTS = DOTS_PER_SCANLINE - MODE2_OAM_SCAN_DOTS - MODE3_VRAM_READ_DOTS
 
 // --- PPU Modes (Values for STAT register bits 0-1) ---
 pub const HBLANK_MODE: u8 = 0;
@@ -63,7 +62,16 @@ pub const STAT_LYC_EQ_LY_IE: u8 = 6; // LYC=LY Coincidence Interrupt Enable
 // Bit 7: Unused (Always reads 1)
 
 // --- OAM Attribute Flags (Bit positions in OAM byte 3) ---
+pub const OAM_PALETTE_NUM_CGB_MASK: u8 = 0x07; // CGB: Palette Number (OBJ palette 0-7)
+pub const OAM_VRAM_BANK: u8 = 3; // CGB: Tile VRAM Bank (0=Bank 0, 1=Bank 1)
 pub const OAM_PALETTE_NUM_DMG: u8 = 4; // DMG: Palette Number (0=OBP0, 1=OBP1)
 pub const OAM_X_FLIP: u8 = 5; // Horizontal Flip (0=Normal, 1=Flipped)
 pub const OAM_Y_FLIP: u8 = 6; // Vertical Flip (0=Normal, 1=Flipped)
 pub const OAM_BG_WIN_PRIORITY: u8 = 7; // BG/Window Priority (0=Sprite above BG/Win, 1=Sprite behind BG colors 1-3)
+
+// --- CGB BG Map Attribute Flags (VRAM bank 1, same layout as the tile maps) ---
+pub const BG_ATTR_PALETTE_MASK: u8 = 0x07; // BG palette 0-7
+pub const BG_ATTR_VRAM_BANK: u8 = 3; // Tile VRAM Bank (0=Bank 0, 1=Bank 1)
+pub const BG_ATTR_X_FLIP: u8 = 5; // Horizontal Flip
+pub const BG_ATTR_Y_FLIP: u8 = 6; // Vertical Flip
+pub const BG_ATTR_PRIORITY: u8 = 7; // BG colors 1-3 drawn over sprites (unless LCDC bit 0 is clear)
@@ -45,8 +45,8 @@ pub(super) fn render_vram_debug(
                 break; // Stop processing this tile if address is invalid
             }
 
-            let byte1 = memory_bus.read_byte(row_addr);
-            let byte2 = memory_bus.read_byte(row_addr + 1);
+            let byte1 = memory_bus.peek_vram(0, row_addr);
+            let byte2 = memory_bus.peek_vram(0, row_addr + 1);
 
             for x_in_tile in 0..8u8 {
                 // Extract the color index for this pixel (Bit 7 left, Bit 0 right)
@@ -0,0 +1,413 @@
+//! Mode 3 pixel pipeline: a background fetcher feeds a pixel FIFO that shifts out one
+//! pixel per dot. Mode 3 lasts until the last pixel of the line is out, so the SCX
+//! fine scroll, the window and sprite fetches all lengthen it (and shorten HBlank).
+
+use super::constants::*;
+use super::render::{self, SpriteInfo};
+use super::state::PpuState;
+use crate::memory_bus::MemoryBus;
+use crate::memory_map;
+use serde::{Deserialize, Serialize};
+
+/// Dots one background tile fetch takes: tile ID, low and high data byte, two dots each.
+const TILE_FETCH_DOTS: u8 = 6;
+/// Dots a sprite fetch stalls the pipeline for.
+const SPRITE_FETCH_DOTS: u8 = 6;
+/// A sprite fetch waits until the background fetch has got this far.
+const SPRITE_FETCH_READY_DOTS: u8 = 4;
+/// Maximum number of sprites the OAM scan selects per line.
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+// Sprite FIFO entries pack the color index (bits 0-1) with these flags; 0 = transparent.
+// In CGB mode they also carry the OBJ palette and the sprite's OAM index.
+const OBJ_PIXEL_OBP1: u16 = 1 << 2;
+const OBJ_PIXEL_BG_PRIORITY: u16 = 1 << 3;
+const OBJ_PIXEL_CGB_PALETTE_SHIFT: u16 = 4; // Bits 4-6
+const OBJ_PIXEL_OAM_INDEX_SHIFT: u16 = 8; // Bits 8-13
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct PixelFifo {
+    lx: u8,            // Next screen column to output
+    discard: u8,       // Fine-scroll pixels (SCX % 8) still to drop
+    first_fetch: bool, // The first tile fetch of a line is thrown away
+    fetch_dots: u8,    // Dots into the current tile fetch; 6 = waiting to push
+    fetcher_x: u8,     // Tile column (0-31) the fetcher reads next, relative to SCX / the window
+    in_window: bool,   // The fetcher has switched to the window for the rest of the line
+    tile_id: u8,
+    tile_attr: u8, // CGB: the fetched tile's BG map attributes (VRAM bank 1)
+    tile_lo: u8,
+    tile_hi: u8,
+    bg_lo: u8, // Background FIFO bit planes; bit 7 is the next pixel
+    bg_hi: u8,
+    bg_len: u8,
+    bg_attr: u8,                    // CGB: attributes of the tile in the background FIFO
+    obj: [u16; 8],                  // Sprite FIFO, aligned with the next 8 background pixels
+    sprites: Vec<SpriteInfo>,       // The line's sprites from the OAM scan, in priority order
+    sprites_fetched: u16,           // One bit per entry of `sprites` already fetched
+    sprite_fetch: Option<(u8, u8)>, // Sprite being fetched: (index into `sprites`, dots done)
+}
+
+impl PixelFifo {
+    pub(super) fn new() -> Self {
+        PixelFifo {
+            lx: 0,
+            discard: 0,
+            first_fetch: true,
+            fetch_dots: 0,
+            fetcher_x: 0,
+            in_window: false,
+            tile_id: 0,
+            tile_attr: 0,
+            tile_lo: 0,
+            tile_hi: 0,
+            bg_lo: 0,
+            bg_hi: 0,
+            bg_len: 0,
+            bg_attr: 0,
+            obj: [0; 8],
+            sprites: Vec::new(),
+            sprites_fetched: 0,
+            sprite_fetch: None,
+        }
+    }
+
+    /// Resets the pipeline for a new line at the start of Mode 3. The OAM scan result
+    /// and the SCX fine scroll are latched here.
+    pub(super) fn start_line(&mut self, state: &PpuState, memory_bus: &MemoryBus) {
+        let sprites = if (state.lcdc & (1 << LCDC_OBJ_ENABLE)) != 0 {
+            render::fetch_scanline_sprites(state, memory_bus)
+        } else {
+            Vec::new()
+        };
+        *self = PixelFifo {
+            discard: memory_bus.peek_byte(memory_map::SCX_ADDR) % 8,
+            sprites,
+            ..PixelFifo::new()
+        };
+    }
+
+    /// Advances the pipeline by one dot, writing any pixel shifted out into `line` (and
+    /// its RGB555 color into `cgb_line` in CGB mode).
+    /// Returns `true` once the whole line has been output, i.e. Mode 3 is over.
+    pub(super) fn tick(
+        &mut self,
+        line: &mut [u8],
+        cgb_line: &mut [u16],
+        state: &PpuState,
+        memory_bus: &MemoryBus,
+    ) -> bool {
+        self.start_window(state, memory_bus);
+        if self.sprite_fetch.is_none() {
+            self.sprite_fetch = self.next_sprite(state).map(|index| (index, 0));
+        }
+
+        // A sprite fetch borrows the VRAM bus once the background fetch is nearly done;
+        // until then the fetcher keeps going but no pixels are shifted out.
+        if let Some((index, dots)) = self.sprite_fetch
+            && self.fetch_dots >= SPRITE_FETCH_READY_DOTS
+        {
+            if dots + 1 == SPRITE_FETCH_DOTS {
+                self.load_sprite(index, state, memory_bus);
+                self.sprite_fetch = None;
+            } else {
+                self.sprite_fetch = Some((index, dots + 1));
+            }
+            return false;
+        }
+
+        self.step_fetcher(state, memory_bus);
+        // The fetcher's push may have just made the sprite under `lx` visible
+        if self.sprite_fetch.is_none() {
+            self.sprite_fetch = self.next_sprite(state).map(|index| (index, 0));
+        }
+        if self.sprite_fetch.is_some() {
+            return false;
+        }
+        self.shift_out(line, cgb_line, state, memory_bus)
+    }
+
+    /// Whether the window was drawn on (some of) the current line.
+    pub(super) fn in_window(&self) -> bool {
+        self.in_window
+    }
+
+    /// Switches the fetcher to the window once the output reaches WX - 7. The FIFO is
+    /// cleared and refilled from the window's first tile, which costs a full fetch.
+    fn start_window(&mut self, state: &PpuState, memory_bus: &MemoryBus) {
+        // Wait for the first background tile to be ready, so a window at the left
+        // edge still pays for its own fetch
+        let bg_ready = self.bg_len > 0 || self.fetch_dots == TILE_FETCH_DOTS;
+        if self.in_window || self.first_fetch || !bg_ready {
+            return;
+        }
+        let lcdc = state.lcdc;
+        // In CGB mode LCDC bit 0 is the BG priority master switch and doesn't hide the window
+        let window_enabled = (lcdc & (1 << LCDC_WINDOW_ENABLE)) != 0
+            && (memory_bus.is_cgb() || (lcdc & (1 << LCDC_BG_WIN_ENABLE_PRIORITY)) != 0);
+        let wx = memory_bus.peek_byte(memory_map::WX_ADDR);
+        if window_enabled && state.window_y_triggered && self.lx >= wx.saturating_sub(7) {
+            self.in_window = true;
+            self.fetcher_x = 0;
+            self.fetch_dots = 0;
+            self.bg_len = 0;
+            // With WX < 7 the window starts left of the screen: its first 7 - WX
+            // pixels are shifted out unseen, replacing any SCX fine scroll
+            self.discard = 7u8.saturating_sub(wx);
+        }
+    }
+
+    /// The first sprite not yet fetched that starts at or left of the current column.
+    /// Sprites are only fetched while background pixels are waiting to be shifted out.
+    fn next_sprite(&self, state: &PpuState) -> Option<u8> {
+        if self.bg_len == 0 || self.discard > 0 || (state.lcdc & (1 << LCDC_OBJ_ENABLE)) == 0 {
+            return None;
+        }
+        self.sprites
+            .iter()
+            .enumerate()
+            .find(|(index, sprite)| {
+                self.sprites_fetched & (1 << index) == 0
+                    && sprite.x_pos as u16 <= self.lx as u16 + 8
+            })
+            .map(|(index, _)| index as u8)
+    }
+
+    /// Mixes a fetched sprite's row into the sprite FIFO. Slots already holding an
+    /// opaque pixel keep it: earlier sprites have priority. In CGB mode priority goes
+    /// by OAM index instead, so a sprite earlier in OAM overwrites a later one.
+    fn load_sprite(&mut self, index: u8, state: &PpuState, memory_bus: &MemoryBus) {
+        self.sprites_fetched |= 1 << index;
+        let sprite = &self.sprites[index as usize];
+        let cgb = memory_bus.is_cgb();
+        for col in 0..8u8 {
+            // Columns left of the current one (sprites hanging off the left edge) are clipped
+            let screen_x = sprite.x_pos as i16 - 8 + col as i16;
+            let Ok(slot) = usize::try_from(screen_x - self.lx as i16) else {
+                continue;
+            };
+            if slot >= self.obj.len() {
+                continue;
+            }
+            let existing = self.obj[slot];
+            if existing != 0
+                && (!cgb || (existing >> OBJ_PIXEL_OAM_INDEX_SHIFT) as u8 <= sprite.oam_index)
+            {
+                continue;
+            }
+            let col_in_tile = if sprite.x_flip { 7 - col } else { col };
+            let color_index = render::get_sprite_tile_pixel_index(
+                sprite,
+                col_in_tile,
+                state.current_scanline,
+                memory_bus,
+            );
+            if color_index != 0 {
+                let mut pixel = color_index as u16;
+                if sprite.obp1 {
+                    pixel |= OBJ_PIXEL_OBP1;
+                }
+                if sprite.bg_priority {
+                    pixel |= OBJ_PIXEL_BG_PRIORITY;
+                }
+                pixel |= (sprite.cgb_palette as u16) << OBJ_PIXEL_CGB_PALETTE_SHIFT;
+                pixel |= (sprite.oam_index as u16) << OBJ_PIXEL_OAM_INDEX_SHIFT;
+                self.obj[slot] = pixel;
+            }
+        }
+    }
+
+    /// One dot of the background fetcher: tile ID (plus its CGB attributes), low byte and
+    /// high byte are read on the second dot of their step, then the row is pushed as
+    /// soon as the FIFO is empty.
+    fn step_fetcher(&mut self, state: &PpuState, memory_bus: &MemoryBus) {
+        if self.fetch_dots < TILE_FETCH_DOTS {
+            self.fetch_dots += 1;
+            let bank = (self.tile_attr >> BG_ATTR_VRAM_BANK) & 1;
+            match self.fetch_dots {
+                2 => {
+                    let map_addr = self.tile_map_addr(state, memory_bus);
+                    self.tile_id = memory_bus.peek_vram(0, map_addr);
+                    self.tile_attr = if memory_bus.is_cgb() {
+                        memory_bus.peek_vram(1, map_addr)
+                    } else {
+                        0
+                    };
+                }
+                4 => {
+                    let row_addr = self.tile_row_addr(state, memory_bus);
+                    self.tile_lo = memory_bus.peek_vram(bank, row_addr);
+                }
+                6 => {
+                    let row_addr = self.tile_row_addr(state, memory_bus);
+                    self.tile_hi = memory_bus.peek_vram(bank, row_addr + 1);
+                    if self.first_fetch {
+                        // The line's first fetch is discarded and the same tile fetched again
+                        self.first_fetch = false;
+                        self.fetch_dots = 0;
+                    }
+                }
+                _ => {}
+            }
+        } else if self.bg_len == 0 {
+            if self.tile_attr & (1 << BG_ATTR_X_FLIP) != 0 {
+                self.bg_lo = self.tile_lo.reverse_bits();
+                self.bg_hi = self.tile_hi.reverse_bits();
+            } else {
+                self.bg_lo = self.tile_lo;
+                self.bg_hi = self.tile_hi;
+            }
+            self.bg_attr = self.tile_attr;
+            self.bg_len = 8;
+            self.fetch_dots = 0;
+            self.fetcher_x = self.fetcher_x.wrapping_add(1);
+        }
+    }
+
+    /// The tile map entry the fetcher reads, from the window or the scrolled background.
+    fn tile_map_addr(&self, state: &PpuState, memory_bus: &MemoryBus) -> u16 {
+        let (map_area_bit, map_y, map_x) = if self.in_window {
+            (
+                LCDC_WINDOW_MAP_AREA,
+                state.window_line,
+                self.fetcher_x.wrapping_mul(8),
+            )
+        } else {
+            let scy = memory_bus.peek_byte(memory_map::SCY_ADDR);
+            let scx = memory_bus.peek_byte(memory_map::SCX_ADDR);
+            (
+                LCDC_BG_MAP_AREA,
+                state.current_scanline.wrapping_add(scy),
+                (scx & !7).wrapping_add(self.fetcher_x.wrapping_mul(8)),
+            )
+        };
+        let map_base_addr = if (state.lcdc & (1 << map_area_bit)) == 0 {
+            0x9800
+        } else {
+            0x9C00
+        };
+        map_base_addr + (map_y / 8) as u16 * 32 + (map_x / 8) as u16
+    }
+
+    /// Address of the low data byte of the fetched tile's current row.
+    fn tile_row_addr(&self, state: &PpuState, memory_bus: &MemoryBus) -> u16 {
+        let row_in_tile = if self.in_window {
+            state.window_line
+        } else {
+            state
+                .current_scanline
+                .wrapping_add(memory_bus.peek_byte(memory_map::SCY_ADDR))
+        } % 8;
+        let row_in_tile = if self.tile_attr & (1 << BG_ATTR_Y_FLIP) != 0 {
+            7 - row_in_tile
+        } else {
+            row_in_tile
+        };
+        render::calculate_tile_data_addr(self.tile_id, state.lcdc, memory_bus)
+            + row_in_tile as u16 * 2
+    }
+
+    /// Shifts one pixel out of the FIFOs, dropping it while fine scroll is pending.
+    /// Palettes are read as the pixel is output, so mid-line palette writes take effect.
+    fn shift_out(
+        &mut self,
+        line: &mut [u8],
+        cgb_line: &mut [u16],
+        state: &PpuState,
+        memory_bus: &MemoryBus,
+    ) -> bool {
+        if self.bg_len == 0 {
+            return false;
+        }
+        let bg_index = ((self.bg_hi >> 7) << 1) | (self.bg_lo >> 7);
+        self.bg_lo <<= 1;
+        self.bg_hi <<= 1;
+        self.bg_len -= 1;
+        if self.discard > 0 {
+            self.discard -= 1;
+            return false;
+        }
+        let obj_pixel = self.obj[0];
+        self.obj.rotate_left(1);
+        self.obj[7] = 0;
+
+        if memory_bus.is_cgb() {
+            self.mix_cgb_pixel(bg_index, obj_pixel, line, cgb_line, state, memory_bus);
+            self.lx += 1;
+            return self.lx as usize == GB_WIDTH;
+        }
+
+        // With LCDC bit 0 clear the background/window layer is color 0
+        let bg_enabled = (state.lcdc & (1 << LCDC_BG_WIN_ENABLE_PRIORITY)) != 0;
+        let bg_index = if bg_enabled { bg_index } else { 0 };
+        let mut color =
+            render::get_color_from_palette(bg_index, memory_bus.peek_byte(memory_map::BGP_ADDR));
+
+        // A sprite pixel wins unless it's behind the background and that isn't color 0
+        let obj_index = (obj_pixel & 0b11) as u8;
+        if obj_index != 0
+            && (!bg_enabled || obj_pixel & OBJ_PIXEL_BG_PRIORITY == 0 || bg_index == 0)
+        {
+            let palette_addr = if obj_pixel & OBJ_PIXEL_OBP1 != 0 {
+                memory_map::OBP1_ADDR
+            } else {
+                memory_map::OBP0_ADDR
+            };
+            color = render::get_color_from_palette(obj_index, memory_bus.peek_byte(palette_addr));
+        }
+
+        line[self.lx as usize] = color;
+        self.lx += 1;
+        self.lx as usize == GB_WIDTH
+    }
+
+    /// CGB mode pixel mixing. LCDC bit 0 clear puts every sprite on top; otherwise BG
+    /// color 0 is always behind, and colors 1-3 cover the sprite if either the BG map
+    /// attribute or the OAM attribute asks for BG priority. `line` gets the raw color
+    /// index, `cgb_line` the RGB555 color from palette RAM.
+    fn mix_cgb_pixel(
+        &self,
+        bg_index: u8,
+        obj_pixel: u16,
+        line: &mut [u8],
+        cgb_line: &mut [u16],
+        state: &PpuState,
+        memory_bus: &MemoryBus,
+    ) {
+        let bg_master_priority = (state.lcdc & (1 << LCDC_BG_WIN_ENABLE_PRIORITY)) != 0;
+        let obj_index = (obj_pixel & 0b11) as u8;
+        let bg_on_top = bg_index != 0
+            && (self.bg_attr & (1 << BG_ATTR_PRIORITY) != 0
+                || obj_pixel & OBJ_PIXEL_BG_PRIORITY != 0);
+        let (index, color) = if obj_index != 0 && (!bg_master_priority || !bg_on_top) {
+            let palette = (obj_pixel >> OBJ_PIXEL_CGB_PALETTE_SHIFT) as u8 & 0x07;
+            (obj_index, memory_bus.obj_palette_color(palette, obj_index))
+        } else {
+            let palette = self.bg_attr & BG_ATTR_PALETTE_MASK;
+            (bg_index, memory_bus.bg_palette_color(palette, bg_index))
+        };
+        line[self.lx as usize] = index;
+        cgb_line[self.lx as usize] = color;
+    }
+
+    /// Checks pipeline state read from a save state.
+    pub(super) fn validate_loaded(&mut self) -> Result<(), String> {
+        if self.sprites.len() > MAX_SPRITES_PER_LINE {
+            return Err(format!(
+                "Invalid sprite count in save state: {}",
+                self.sprites.len()
+            ));
+        }
+        if let Some((index, _)) = self.sprite_fetch
+            && index as usize >= self.sprites.len()
+        {
+            return Err(format!("Invalid sprite fetch in save state: {}", index));
+        }
+        if self.lx as usize > GB_WIDTH || self.bg_len > 8 || self.fetch_dots > TILE_FETCH_DOTS {
+            return Err("Invalid pixel FIFO state in save state".to_string());
+        }
+        for sprite in &mut self.sprites {
+            sprite.validate_loaded();
+        }
+        Ok(())
+    }
+}
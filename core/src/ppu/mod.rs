@@ -1,29 +1,49 @@
 use crate::memory_bus::MemoryBus;
 use crate::memory_map; // Use memory_map constants directly
+use crate::state::big_array;
+use serde::{Deserialize, Serialize};
 
 mod constants;
 mod debug;
+mod fifo;
 mod render;
 mod state;
 
 // Re-export public constants and types
 use constants::*; // Use internal constants
-pub use constants::{GB_HEIGHT, GB_WIDTH, VRAM_DEBUG_HEIGHT, VRAM_DEBUG_WIDTH};
+pub use constants::{
+    DOTS_PER_SCANLINE, FRAME_BUFFER_SIZE, GB_HEIGHT, GB_WIDTH, HBLANK_MODE, OAM_SCAN_MODE,
+    SCANLINES_PER_FRAME, VBLANK_MODE, VRAM_DEBUG_HEIGHT, VRAM_DEBUG_WIDTH, VRAM_READ_MODE,
+};
+use fifo::PixelFifo;
 use state::PpuState;
 
-/// Represents the Picture Processing Unit (PPU) of the Game Boy.
+/// Represents the Picture Processing Unit (PPU) of the Game Boy. Save states hold the
+/// timing state, the pixel pipeline and the current frame.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Ppu {
+    #[serde(with = "big_array")]
     frame_buffer: Box<[u8; FRAME_BUFFER_SIZE]>, // Use Box for heap allocation
+    #[serde(with = "big_array")]
+    cgb_frame_buffer: Box<[u16; FRAME_BUFFER_SIZE]>, // RGB555 colors, only drawn in CGB mode
+    #[serde(skip, default = "empty_vram_debug_buffer")]
     vram_debug_buffer: Box<[u8; VRAM_DEBUG_BUFFER_SIZE]>, // Use Box for heap allocation
     state: PpuState,
+    fifo: PixelFifo,
+}
+
+fn empty_vram_debug_buffer() -> Box<[u8; VRAM_DEBUG_BUFFER_SIZE]> {
+    Box::new([0; VRAM_DEBUG_BUFFER_SIZE])
 }
 
 impl Ppu {
     pub fn new() -> Self {
         Ppu {
             frame_buffer: Box::new([0; FRAME_BUFFER_SIZE]),
-            vram_debug_buffer: Box::new([0; VRAM_DEBUG_BUFFER_SIZE]),
+            cgb_frame_buffer: Box::new([0x7FFF; FRAME_BUFFER_SIZE]),
+            vram_debug_buffer: empty_vram_debug_buffer(),
             state: PpuState::new(),
+            fifo: PixelFifo::new(),
         }
     }
 
@@ -32,11 +52,40 @@ impl Ppu {
         &self.frame_buffer
     }
 
+    /// Get a reference to the CGB frame buffer: one RGB555 color per pixel (red in bits
+    /// 0-4, green 5-9, blue 10-14). Only updated when the cartridge runs in CGB mode;
+    /// the shade frame buffer then holds raw color indices instead.
+    pub fn get_cgb_frame_buffer(&self) -> &[u16; FRAME_BUFFER_SIZE] {
+        &self.cgb_frame_buffer
+    }
+
     /// Get a reference to the VRAM debug view buffer.
     pub fn get_vram_debug_buffer(&self) -> &[u8; VRAM_DEBUG_BUFFER_SIZE] {
         &self.vram_debug_buffer
     }
 
+    /// Gets the current PPU mode (0 = HBlank, 1 = VBlank, 2 = OAM scan, 3 = VRAM read).
+    pub fn mode(&self) -> u8 {
+        self.state.mode()
+    }
+
+    /// Gets the scanline the PPU is currently on (LY).
+    pub fn scanline(&self) -> u8 {
+        self.state.scanline()
+    }
+
+    /// Gets the length in dots of the current line's Mode 3 (or the last line's once
+    /// in HBlank): 172 plus the SCX fine scroll, window and sprite penalties.
+    pub fn mode3_dots(&self) -> u32 {
+        self.state.mode3_dots()
+    }
+
+    /// Checks the timing and pipeline state of a PPU just read from a save state.
+    pub(crate) fn validate_loaded(&mut self) -> Result<(), String> {
+        self.state.validate_loaded()?;
+        self.fifo.validate_loaded()
+    }
+
     /// Call this periodically (e.g., once per frame) to update the VRAM debug view.
     pub fn update_vram_debug_buffer(&mut self, memory_bus: &MemoryBus) {
         debug::render_vram_debug(&mut self.vram_debug_buffer, memory_bus);
@@ -44,11 +93,13 @@ impl Ppu {
 
     /// Steps the PPU by the given number of T-cycles. Handles timing, mode transitions,
     /// rendering, and interrupt requests.
-    pub fn step(&mut self, cycles: u32, memory_bus: &mut MemoryBus) {
+    /// Returns `true` if this step entered VBlank, i.e. a complete frame is in the buffer.
+    pub fn step(&mut self, cycles: u32, memory_bus: &mut MemoryBus) -> bool {
         // --- Read LCDC and STAT ---
         // Caching these helps avoid frequent bus reads within the step logic.
-        self.state.lcdc = memory_bus.read_byte(memory_map::LCDC_ADDR);
-        self.state.stat = memory_bus.read_byte(memory_map::STAT_ADDR);
+        self.state.lcdc = memory_bus.peek_byte(memory_map::LCDC_ADDR);
+        self.state.stat = memory_bus.peek_byte(memory_map::STAT_ADDR);
+        let stat_written = memory_bus.take_stat_write();
 
         // --- Check if LCD is enabled ---
         if (self.state.lcdc & (1 << LCDC_LCD_ENABLE)) == 0 {
@@ -59,7 +110,7 @@ impl Ppu {
             {
                 self.state.reset_for_lcd_off();
                 // Write initial state to registers when LCD turns off
-                memory_bus.write_byte(memory_map::LY_ADDR, 0);
+                memory_bus.set_io_reg_direct(memory_map::LY_ADDR, 0);
                 // Preserve IE bits, force mode to 0 (HBLANK), clear coincidence flag
                 let stat_to_write = (self.state.stat & 0b1111_1000) | HBLANK_MODE;
                 memory_bus.set_io_reg_direct(memory_map::STAT_ADDR, stat_to_write); // Use direct write if available to bypass PPU write checks
@@ -67,8 +118,22 @@ impl Ppu {
                 // Clear frame buffer? Optional. Some games rely on VRAM content.
                 // self.frame_buffer.fill(0);
             }
-            return; // Do nothing else if LCD is off
+            return false; // Do nothing else if LCD is off
+        }
+
+        // --- Apply LYC/STAT writes made since the last step ---
+        // The coincidence flag and the STAT line react to them at once, before any dots
+        // run, so a match the game creates by writing LYC isn't lost to a line change
+        self.check_lyc_coincidence(memory_bus);
+        if stat_written && memory_bus.dmg_quirks() {
+            // DMG STAT write bug: for a moment every source looks enabled, so writing
+            // STAT in HBlank, VBlank or on an LY=LYC line raises a STAT interrupt
+            let glitch_sources = (1 << STAT_MODE_0_HBLANK_IE)
+                | (1 << STAT_MODE_1_VBLANK_IE)
+                | (1 << STAT_LYC_EQ_LY_IE);
+            self.update_stat_interrupt_line(memory_bus, self.state.stat | glitch_sources);
         }
+        self.update_stat_interrupt_line(memory_bus, self.state.stat);
 
         // --- Advance PPU timing ---
         self.state.dots += cycles;
@@ -80,45 +145,49 @@ impl Ppu {
                 if self.state.dots >= MODE2_OAM_SCAN_DOTS {
                     self.state.dots -= MODE2_OAM_SCAN_DOTS;
                     self.state.ppu_mode = VRAM_READ_MODE; // Transition to Mode 3
+                    self.state.mode3_dots = 0;
+                    // Once LY has matched WY the window can be drawn for the rest of the
+                    // frame, even if WY changes afterwards
+                    if memory_bus.peek_byte(memory_map::WY_ADDR) == self.state.current_scanline {
+                        self.state.window_y_triggered = true;
+                    }
+                    self.fifo.start_line(&self.state, memory_bus);
                 }
             }
             VRAM_READ_MODE => {
-                // Mode 3
-                // Mode 3 duration can vary slightly based on sprites, etc.
-                // For simplicity, use the minimum duration. A more accurate PPU
-                // might calculate the exact end point.
-                if self.state.dots >= MODE3_VRAM_READ_DOTS {
-                    self.state.dots -= MODE3_VRAM_READ_DOTS;
-                    self.state.ppu_mode = HBLANK_MODE; // Transition to Mode 0
-
-                    // --- Render the scanline just before entering HBlank ---
-                    let y = self.state.current_scanline as usize;
-                    if y < GB_HEIGHT {
-                        let start_index = y * GB_WIDTH;
-                        let end_index = start_index + GB_WIDTH;
-                        // Get a mutable slice for the current line
-                        let line_buffer_slice = &mut self.frame_buffer[start_index..end_index];
-                        // Convert slice to array reference (requires exact size match)
-                        if let Ok(line_buffer_array) = line_buffer_slice.try_into() {
-                            render::render_scanline(line_buffer_array, &self.state, memory_bus);
-                        } else {
-                            // Handle error: slice length didn't match array size (shouldn't happen here)
-                            log::error!("Failed to get line buffer slice for rendering!");
+                // Mode 3: the pixel FIFO outputs one pixel per dot (when not stalled)
+                // and the mode ends with the line's last pixel
+                let start_index = self.state.current_scanline as usize * GB_WIDTH;
+                let line = &mut self.frame_buffer[start_index..start_index + GB_WIDTH];
+                let cgb_line = &mut self.cgb_frame_buffer[start_index..start_index + GB_WIDTH];
+                while self.state.dots > 0 {
+                    self.state.dots -= 1;
+                    self.state.mode3_dots += 1;
+                    if self.fifo.tick(line, cgb_line, &self.state, memory_bus) {
+                        self.state.ppu_mode = HBLANK_MODE; // Transition to Mode 0
+                        // The window line counter only advances on lines that drew the
+                        // window, so turning it off for a few lines resumes where it left off
+                        if self.fifo.in_window() {
+                            self.state.window_line += 1;
                         }
+                        break;
                     }
                 }
             }
             HBLANK_MODE => {
                 // Mode 0
-                // Mode 0 ends when the total dots for the scanline are reached
-                if self.state.dots >= DOTS_PER_SCANLINE {
-                    self.state.dots %= DOTS_PER_SCANLINE; // Keep leftover dots for next line
+                // Mode 0 ends when the total dots for the scanline are reached.
+                // Modes 2 and 3 already consumed their dots, so only the remainder is left.
+                let hblank_dots = DOTS_PER_SCANLINE - MODE2_OAM_SCAN_DOTS - self.state.mode3_dots;
+                if self.state.dots >= hblank_dots {
+                    self.state.dots -= hblank_dots; // Keep leftover dots for next line
                     self.state.current_scanline += 1;
 
                     // Check for end of visible frame -> VBlank start
                     if self.state.current_scanline == GB_HEIGHT as u8 {
                         self.state.ppu_mode = VBLANK_MODE; // Transition to Mode 1
                         self.state.vblank_just_occurred = true; // Signal VBlank interrupt
+                        self.state.reset_window();
                     // Option: Render VRAM debug view once per frame here
                     // self.update_vram_debug_buffer(memory_bus);
                     } else {
@@ -152,12 +221,17 @@ impl Ppu {
         self.update_stat_register(memory_bus);
 
         // --- Handle Interrupt Requests ---
+        // Sample the flag first: requesting the VBlank interrupt clears it
+        let entered_vblank = self.state.vblank_just_occurred;
         self.check_and_request_interrupts(memory_bus);
+        entered_vblank
     }
 
     /// Checks LYC=LY coincidence and updates the internal flag.
+    /// Runs in the same step that advances LY, so a LYC=LY STAT interrupt is raised on
+    /// the step the new line begins rather than one step later.
     fn check_lyc_coincidence(&mut self, memory_bus: &MemoryBus) {
-        let lyc = memory_bus.read_byte(memory_map::LYC_ADDR);
+        let lyc = memory_bus.peek_byte(memory_map::LYC_ADDR);
         self.state.lyc_eq_ly = self.state.current_scanline == lyc;
     }
 
@@ -189,7 +263,12 @@ impl Ppu {
         // --- Check STAT Interrupt Conditions ---
         // Use the *cached* STAT register value read at the start of step()
         // to check the *enabled* interrupts.
-        let stat_reg = self.state.stat;
+        self.update_stat_interrupt_line(memory_bus, self.state.stat);
+    }
+
+    /// Recomputes the STAT interrupt line from the sources enabled in `stat_reg` and
+    /// requests the interrupt if it just went high.
+    fn update_stat_interrupt_line(&mut self, memory_bus: &mut MemoryBus, stat_reg: u8) {
         let mut stat_interrupt_now = false;
 
         // LYC=LY interrupt enabled and condition met?
@@ -209,7 +288,9 @@ impl Ppu {
             stat_interrupt_now = true;
         }
 
-        // Request STAT interrupt only on the rising edge (when the condition *becomes* true)
+        // Request STAT interrupt only on the rising edge (when the condition *becomes* true).
+        // This is "STAT blocking": e.g. HBlank on line LYC-1 followed by LYC=LY on line LYC
+        // keeps the line high, so only one interrupt fires until every source drops.
         if stat_interrupt_now && !self.state.stat_interrupt_line {
             Self::request_interrupt(memory_bus, memory_map::LCD_STAT_INTERRUPT_BIT);
         }
@@ -220,7 +301,7 @@ impl Ppu {
     /// Helper to request an interrupt by setting the corresponding bit in the IF register.
     #[inline]
     fn request_interrupt(memory_bus: &mut MemoryBus, bit: u8) {
-        let current_if = memory_bus.read_byte(memory_map::IF_ADDR);
+        let current_if = memory_bus.get_io_reg(memory_map::IF_ADDR);
         // Use direct write to avoid potential side effects of a normal write_byte
         memory_bus.set_io_reg_direct(memory_map::IF_ADDR, current_if | (1 << bit));
     }
@@ -1,11 +1,15 @@
 use super::constants::*;
+use serde::{Deserialize, Serialize};
 
 /// Holds the internal state of the PPU, primarily related to timing and modes.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PpuState {
     pub(super) dots: u32, // Current dot within the scanline (T-cycle counter)
     pub(super) current_scanline: u8, // Current scanline (LY register value, 0-153)
     pub(super) ppu_mode: u8, // Current PPU mode (0, 1, 2, 3)
+    pub(super) mode3_dots: u32, // Length of the current line's Mode 3 so far (final once in HBlank)
+    pub(super) window_line: u8, // Window internal line counter: window rows drawn so far this frame
+    pub(super) window_y_triggered: bool, // LY has matched WY this frame, so the window may be drawn
     pub(super) lyc_eq_ly: bool, // Status of LYC == LY comparison
     pub(super) stat_interrupt_line: bool, // Tracks the state of the STAT interrupt line (high/low)
     pub(super) vblank_just_occurred: bool, // Flag to signal VBlank interrupt on mode transition
@@ -19,6 +23,9 @@ impl PpuState {
             dots: 0,
             current_scanline: 0,
             ppu_mode: OAM_SCAN_MODE, // Start in OAM scan? Or Mode 0? Check boot sequence. Let's assume OAM.
+            mode3_dots: MODE3_VRAM_READ_DOTS,
+            window_line: 0,
+            window_y_triggered: false,
             lyc_eq_ly: false,
             stat_interrupt_line: false,
             vblank_just_occurred: false,
@@ -27,22 +34,50 @@ impl PpuState {
         }
     }
 
+    /// Checks timing/mode state read from a save state.
+    pub(super) fn validate_loaded(&self) -> Result<(), String> {
+        if self.mode3_dots > DOTS_PER_SCANLINE - MODE2_OAM_SCAN_DOTS {
+            return Err(format!(
+                "Invalid Mode 3 length in save state: {}",
+                self.mode3_dots
+            ));
+        }
+        if self.ppu_mode > VRAM_READ_MODE {
+            return Err(format!("Invalid PPU mode in save state: {}", self.ppu_mode));
+        }
+        Ok(())
+    }
+
     /// Resets the PPU state when the LCD is turned off.
     pub(super) fn reset_for_lcd_off(&mut self) {
         self.dots = 0;
         self.current_scanline = 0;
         // Set mode to HBLANK? Or VBLANK? Pandocs implies LY=0, Mode=0 when LCD off.
         self.ppu_mode = HBLANK_MODE;
+        self.mode3_dots = MODE3_VRAM_READ_DOTS;
+        self.reset_window();
         self.lyc_eq_ly = false;
         self.stat_interrupt_line = false;
         // Don't reset lcdc/stat caches here, they get updated from bus
     }
 
+    /// Starts a new frame's window: the WY match and the line counter are per frame.
+    pub(super) fn reset_window(&mut self) {
+        self.window_line = 0;
+        self.window_y_triggered = false;
+    }
+
     /// Gets the current PPU mode.
     pub fn mode(&self) -> u8 {
         self.ppu_mode
     }
 
+    /// Gets the length in dots of the current line's Mode 3, or the last line's
+    /// once it has ended.
+    pub fn mode3_dots(&self) -> u32 {
+        self.mode3_dots
+    }
+
     /// Gets the current scanline (LY).
     pub fn scanline(&self) -> u8 {
         self.current_scanline
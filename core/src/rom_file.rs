@@ -0,0 +1,139 @@
+//! Reading ROM images from disk: plain `.gb`/`.gbc` files, or the first one inside a
+//! `.zip` archive. Only what zipped ROMs need is supported: stored and deflated
+//! entries, no ZIP64 or encryption.
+
+use flate2::Crc;
+use flate2::read::DeflateDecoder;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// File extensions accepted as ROM images (e.g. for drag and drop).
+pub const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "zip"];
+
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4B50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4B50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4B50;
+/// End of central directory record size, without its trailing comment.
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Returns `true` if `path` looks like a ROM image we can load (by extension).
+pub fn is_rom_path(path: &Path) -> bool {
+    extension_is(path, &ROM_EXTENSIONS)
+}
+
+/// Reads the ROM at `path`, unpacking it first if it's a `.zip`.
+pub fn read_rom(path: &Path) -> Result<Vec<u8>, String> {
+    let data =
+        fs::read(path).map_err(|e| format!("Failed to read ROM '{}': {}", path.display(), e))?;
+    if extension_is(path, &["zip"]) {
+        extract_rom(&data).map_err(|e| format!("Failed to unzip '{}': {}", path.display(), e))
+    } else {
+        Ok(data)
+    }
+}
+
+/// Returns the contents of the first `.gb` or `.gbc` file in a zip archive.
+pub fn extract_rom(archive: &[u8]) -> Result<Vec<u8>, String> {
+    let end = find_end_of_central_directory(archive)?;
+    let entry_count = read_u16(archive, end + 10)?;
+    let mut offset = read_u32(archive, end + 16)? as usize;
+
+    for _ in 0..entry_count {
+        if read_u32(archive, offset)? != CENTRAL_DIRECTORY_SIGNATURE {
+            return Err("Corrupt central directory".to_string());
+        }
+        let method = read_u16(archive, offset + 10)?;
+        let crc = read_u32(archive, offset + 16)?;
+        let compressed_size = read_u32(archive, offset + 20)? as usize;
+        let size = read_u32(archive, offset + 24)? as usize;
+        let name_len = read_u16(archive, offset + 28)? as usize;
+        let extra_len = read_u16(archive, offset + 30)? as usize;
+        let comment_len = read_u16(archive, offset + 32)? as usize;
+        let local_header = read_u32(archive, offset + 42)? as usize;
+        let name = bytes(archive, offset + 46, name_len)?;
+        offset += 46 + name_len + extra_len + comment_len;
+
+        let name = String::from_utf8_lossy(name);
+        if !extension_is(Path::new(name.as_ref()), &["gb", "gbc"]) {
+            continue;
+        }
+
+        // Sizes come from the central directory: local headers may leave them zeroed
+        if read_u32(archive, local_header)? != LOCAL_HEADER_SIGNATURE {
+            return Err(format!("Corrupt local header for '{}'", name));
+        }
+        let data_start = local_header
+            + 30
+            + read_u16(archive, local_header + 26)? as usize
+            + read_u16(archive, local_header + 28)? as usize;
+        let compressed = bytes(archive, data_start, compressed_size)?;
+        let rom = match method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => {
+                let mut rom = Vec::with_capacity(size);
+                DeflateDecoder::new(compressed)
+                    .read_to_end(&mut rom)
+                    .map_err(|e| format!("Failed to inflate '{}': {}", name, e))?;
+                rom
+            }
+            _ => {
+                return Err(format!(
+                    "'{}' uses unsupported compression method {}",
+                    name, method
+                ));
+            }
+        };
+
+        let mut actual_crc = Crc::new();
+        actual_crc.update(&rom);
+        if rom.len() != size || actual_crc.sum() != crc {
+            return Err(format!("'{}' is corrupt (size or CRC mismatch)", name));
+        }
+        return Ok(rom);
+    }
+    Err("No .gb or .gbc file in the archive".to_string())
+}
+
+fn extension_is(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            extensions
+                .iter()
+                .any(|allowed| ext.eq_ignore_ascii_case(allowed))
+        })
+}
+
+/// The end of central directory record sits at the very end, before a comment of up
+/// to 64 KB, so it's searched for backwards.
+fn find_end_of_central_directory(archive: &[u8]) -> Result<usize, String> {
+    let last = archive
+        .len()
+        .checked_sub(END_OF_CENTRAL_DIRECTORY_SIZE)
+        .ok_or("Not a zip archive (too short)")?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last)
+        .rev()
+        .find(|&offset| read_u32(archive, offset) == Ok(END_OF_CENTRAL_DIRECTORY_SIGNATURE))
+        .ok_or_else(|| "Not a zip archive (no end of central directory)".to_string())
+}
+
+fn bytes(archive: &[u8], offset: usize, len: usize) -> Result<&[u8], String> {
+    offset
+        .checked_add(len)
+        .and_then(|end| archive.get(offset..end))
+        .ok_or_else(|| "Truncated zip archive".to_string())
+}
+
+fn read_u16(archive: &[u8], offset: usize) -> Result<u16, String> {
+    let b = bytes(archive, offset, 2)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(archive: &[u8], offset: usize) -> Result<u32, String> {
+    let b = bytes(archive, offset, 4)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
@@ -1,7 +1,11 @@
+use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Size of the RTC block appended to battery saves of MBC3+TIMER cartridges.
+pub const RTC_SAVE_SIZE: usize = 48;
+
 /// Represents the Real-Time Clock registers for MBC3.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct RtcRegisters {
     seconds: u8, // 0x08 (0-59)
     minutes: u8, // 0x09 (0-59)
@@ -82,6 +86,64 @@ impl RtcRegisters {
                   (self.dh & RtcRegisters::DAY_OVERFLOW_BIT); // Keep potentially set Overflow bit
     }
 
+    /// Serializes the live and latched registers in the 48-byte block that BGB, VBA-M
+    /// and SameBoy append to `.sav` files: ten little-endian u32 register values
+    /// (live S/M/H/DL/DH, then latched) followed by a u64 UNIX timestamp.
+    pub fn to_save_bytes(&self, latched: &RtcRegisters) -> [u8; RTC_SAVE_SIZE] {
+        let mut bytes = [0u8; RTC_SAVE_SIZE];
+        let regs = [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.dl,
+            self.dh,
+            latched.seconds,
+            latched.minutes,
+            latched.hours,
+            latched.dl,
+            latched.dh,
+        ];
+        for (i, reg) in regs.iter().enumerate() {
+            bytes[i * 4..i * 4 + 4].copy_from_slice(&u32::from(*reg).to_le_bytes());
+        }
+        bytes[40..48].copy_from_slice(&self.last_updated_secs.to_le_bytes());
+        bytes
+    }
+
+    /// Restores (live, latched) registers from a save block written by `to_save_bytes`.
+    /// The live clock is advanced by the real time elapsed since the save was written.
+    /// Accepts the older 44-byte variant with a 32-bit timestamp.
+    pub fn from_save_bytes(bytes: &[u8]) -> Option<(RtcRegisters, RtcRegisters)> {
+        if bytes.len() < 44 {
+            return None;
+        }
+        let reg = |i: usize| bytes[i * 4];
+        let timestamp = if bytes.len() >= RTC_SAVE_SIZE {
+            u64::from_le_bytes(bytes[40..48].try_into().ok()?)
+        } else {
+            u64::from(u32::from_le_bytes(bytes[40..44].try_into().ok()?))
+        };
+
+        let mut live = RtcRegisters {
+            seconds: reg(0),
+            minutes: reg(1),
+            hours: reg(2),
+            dl: reg(3),
+            dh: reg(4),
+            last_updated_secs: timestamp,
+        };
+        live.update();
+        let latched = RtcRegisters {
+            seconds: reg(5),
+            minutes: reg(6),
+            hours: reg(7),
+            dl: reg(8),
+            dh: reg(9),
+            last_updated_secs: timestamp,
+        };
+        Some((live, latched))
+    }
+
     /// Reads the value of a selected RTC register.
     pub fn read(&self, reg_select: u8) -> u8 {
         match reg_select {
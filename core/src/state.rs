@@ -0,0 +1,100 @@
+//! Save state format: [`STATE_MAGIC`], a little-endian [`STATE_VERSION`], then the
+//! components encoded with bincode through their serde derives.
+//!
+//! Bump [`STATE_VERSION`] whenever a saved struct gains, loses or reorders a field, so
+//! older states are rejected instead of misread.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Identifies a save state file.
+pub const STATE_MAGIC: &[u8; 8] = b"BOBASTAT";
+/// Layout version of the save state format.
+pub const STATE_VERSION: u32 = 13;
+
+/// Arrays serde can't derive for (it stops at 32 elements), boxed or not.
+pub trait BigArray: Sized {
+    type Item;
+    fn as_slice(&self) -> &[Self::Item];
+    fn from_vec(items: Vec<Self::Item>) -> Option<Self>;
+}
+
+impl<T, const N: usize> BigArray for [T; N] {
+    type Item = T;
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+    fn from_vec(items: Vec<T>) -> Option<Self> {
+        items.try_into().ok()
+    }
+}
+
+impl<T, const N: usize> BigArray for Box<[T; N]> {
+    type Item = T;
+    fn as_slice(&self) -> &[T] {
+        &self[..]
+    }
+    fn from_vec(items: Vec<T>) -> Option<Self> {
+        items.try_into().ok()
+    }
+}
+
+/// `#[serde(with = "crate::state::big_array")]` for a [`BigArray`] field.
+pub mod big_array {
+    use super::*;
+
+    pub fn serialize<A, S>(array: &A, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        A: BigArray,
+        A::Item: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(array.as_slice())
+    }
+
+    pub fn deserialize<'de, A, D>(deserializer: D) -> Result<A, D::Error>
+    where
+        A: BigArray,
+        A::Item: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let items = Vec::<A::Item>::deserialize(deserializer)?;
+        let len = items.len();
+        A::from_vec(items).ok_or_else(|| D::Error::custom(format!("wrong array length {}", len)))
+    }
+}
+
+/// `#[serde(with = "crate::state::big_array_2d")]` for a boxed array of arrays, stored
+/// flattened.
+pub mod big_array_2d {
+    use super::*;
+
+    pub fn serialize<T, S, const N: usize, const M: usize>(
+        array: &[[T; N]; M],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(array.as_flattened())
+    }
+
+    pub fn deserialize<'de, T, D, const N: usize, const M: usize>(
+        deserializer: D,
+    ) -> Result<Box<[[T; N]; M]>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let items = Vec::<T>::deserialize(deserializer)?;
+        let len = items.len();
+        let mut items = items.into_iter();
+        let rows = (0..M)
+            .map(|_| <[T; N]>::try_from(items.by_ref().take(N).collect::<Vec<T>>()).ok())
+            .collect::<Option<Vec<[T; N]>>>()
+            .filter(|_| items.next().is_none());
+        rows.and_then(|rows| rows.into_boxed_slice().try_into().ok())
+            .ok_or_else(|| D::Error::custom(format!("wrong array length {}", len)))
+    }
+}
@@ -0,0 +1,108 @@
+use crate::memory_bus::MemoryBus;
+use crate::memory_map::{DIV_ADDR, TAC_ADDR, TIMA_ADDR, TIMER_INTERRUPT_BIT, TMA_ADDR};
+use serde::{Deserialize, Serialize};
+
+/// Divider bit whose falling edge clocks TIMA, indexed by TAC bits 0-1.
+/// 00: 4096 Hz, 01: 262144 Hz, 10: 65536 Hz, 11: 16384 Hz.
+const TAC_DIVIDER_BITS: [u8; 4] = [9, 3, 5, 7];
+const TAC_ENABLE: u8 = 0x04;
+/// T-cycles TIMA reads 0x00 after overflowing before TMA is loaded into it.
+const TIMA_RELOAD_DELAY: u8 = 4;
+
+/// Represents the Game Boy timer (DIV, TIMA, TMA, TAC).
+///
+/// DIV is the upper byte of a free-running 16-bit counter that advances every T-cycle.
+/// TIMA is incremented whenever the counter bit selected by TAC falls from 1 to 0.
+/// On overflow TIMA holds 0x00 for four T-cycles before it is reloaded from TMA and
+/// the interrupt is requested; writing TIMA during that window cancels the reload.
+///
+/// Like the hardware, TIMA is clocked by the falling edge of `enable AND selected bit`,
+/// so resetting DIV or rewriting TAC while that signal is high also bumps TIMA.
+///
+/// Save states hold the internal counter and reload state; DIV/TIMA/TMA/TAC themselves
+/// live in the bus' I/O registers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Timer {
+    divider: u16,
+    reload_delay: u8, // T-cycles left until the pending TMA reload, 0 if none
+    tac: u8,          // TAC as of the last step, to catch edges caused by TAC writes
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Timer {
+            // Matches the post-boot DIV value MemoryBus::new() starts with
+            divider: 0xAC00,
+            reload_delay: 0,
+            tac: 0,
+        }
+    }
+
+    /// Current value of the internal 16-bit divider counter.
+    pub fn divider(&self) -> u16 {
+        self.divider
+    }
+
+    /// Steps the timer by the given number of T-cycles, updating DIV/TIMA and
+    /// requesting the Timer interrupt when TIMA overflows.
+    pub fn step(&mut self, cycles: u32, memory_bus: &mut MemoryBus) {
+        let signal_before = Self::timer_signal(self.divider, self.tac);
+
+        // Any write to DIV clears the whole internal counter
+        if memory_bus.take_div_reset() {
+            self.divider = 0;
+        }
+        // A CPU write to TIMA during the overflow window wins over the reload
+        if memory_bus.take_tima_write() {
+            self.reload_delay = 0;
+        }
+        self.tac = memory_bus.get_io_reg(TAC_ADDR);
+
+        // DIV reset or TAC change pulled the signal low: that's a TIMA tick too
+        if signal_before && !Self::timer_signal(self.divider, self.tac) {
+            self.increment_tima(memory_bus);
+        }
+
+        for _ in 0..cycles {
+            if self.reload_delay > 0 {
+                self.reload_delay -= 1;
+                if self.reload_delay == 0 {
+                    // TMA is read now, so a TMA write during the window is picked up
+                    let tma = memory_bus.get_io_reg(TMA_ADDR);
+                    memory_bus.set_io_reg_direct(TIMA_ADDR, tma);
+                    memory_bus.request_interrupt(TIMER_INTERRUPT_BIT);
+                }
+            }
+
+            let before = Self::timer_signal(self.divider, self.tac);
+            self.divider = self.divider.wrapping_add(1);
+            if before && !Self::timer_signal(self.divider, self.tac) {
+                self.increment_tima(memory_bus);
+            }
+        }
+
+        memory_bus.set_io_reg_direct(DIV_ADDR, (self.divider >> 8) as u8);
+    }
+
+    /// The line feeding TIMA: TAC enable ANDed with the divider bit TAC selects.
+    #[inline]
+    fn timer_signal(divider: u16, tac: u8) -> bool {
+        let bit = TAC_DIVIDER_BITS[(tac & 0x03) as usize];
+        tac & TAC_ENABLE != 0 && (divider >> bit) & 1 == 1
+    }
+
+    /// Increments TIMA, starting the delayed TMA reload on overflow.
+    fn increment_tima(&mut self, memory_bus: &mut MemoryBus) {
+        let (tima, overflowed) = memory_bus.get_io_reg(TIMA_ADDR).overflowing_add(1);
+        memory_bus.set_io_reg_direct(TIMA_ADDR, tima);
+        if overflowed {
+            self.reload_delay = TIMA_RELOAD_DELAY;
+        }
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
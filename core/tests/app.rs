@@ -0,0 +1,241 @@
+//! The SDL frontend. Its modules are pulled in by path, so this only builds with the
+//! `sdl` feature (and needs SDL2 to link).
+#![cfg(feature = "sdl")]
+
+#[allow(dead_code)]
+#[path = "../src/app/constants.rs"]
+mod constants;
+#[allow(dead_code, unused_imports)]
+#[path = "../src/app/drawing.rs"]
+mod drawing;
+#[allow(dead_code)]
+#[path = "../src/app/emulator.rs"]
+mod emulator;
+#[allow(dead_code)]
+#[path = "../src/app/gif_recorder.rs"]
+mod gif_recorder;
+#[allow(dead_code)]
+#[path = "../src/app/input.rs"]
+mod input;
+#[allow(dead_code)]
+#[path = "../src/app/keymap.rs"]
+mod keymap;
+#[allow(dead_code)]
+#[path = "../src/app/sdl_setup.rs"]
+mod sdl_setup;
+#[allow(dead_code)]
+#[path = "../src/app/text_cache.rs"]
+mod text_cache;
+
+use boba::memory_bus::MemoryBus;
+use boba::palette::DEFAULT_PALETTE;
+use boba::ppu::{HBLANK_MODE, OAM_SCAN_MODE, VBLANK_MODE, VRAM_READ_MODE};
+use drawing::ppu_mode_label;
+use emulator::{Emulator, EmulatorBuilder};
+use gif_recorder::rgba_to_palette_indices;
+use input::{InputAction, InputState, actions_for_events};
+use keymap::KeyMap;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const P1: u16 = 0xFF00;
+
+fn key_down(keycode: Keycode, scancode: Scancode) -> Event {
+    Event::KeyDown {
+        timestamp: 0,
+        window_id: 1,
+        keycode: Some(keycode),
+        scancode: Some(scancode),
+        keymod: Mod::NOMOD,
+        repeat: false,
+    }
+}
+
+fn drop_file(filename: &str) -> Event {
+    Event::DropFile {
+        timestamp: 0,
+        window_id: 1,
+        filename: filename.to_string(),
+    }
+}
+
+#[test]
+fn dropped_rom_is_kept_when_other_events_follow_it() {
+    let mut input = InputState::new(KeyMap::default(), None);
+    let mut memory_bus = MemoryBus::new();
+    let events = [
+        drop_file("game.gb"),
+        Event::Window {
+            timestamp: 0,
+            window_id: 1,
+            win_event: WindowEvent::SizeChanged(800, 600),
+        },
+        drop_file("notes.txt"),            // Not a ROM, so no action
+        key_down(Keycode::Z, Scancode::Z), // Joypad only
+        key_down(Keycode::F5, Scancode::F5),
+        key_down(Keycode::F11, Scancode::F11),
+    ];
+
+    assert_eq!(
+        actions_for_events(events, &mut input, &mut memory_bus),
+        [
+            InputAction::LoadRom(PathBuf::from("game.gb")),
+            InputAction::Resized,
+            InputAction::SaveState,
+            InputAction::ToggleFullscreen,
+        ]
+    );
+
+    // The key press in the middle still reached the joypad: A reads low in P1
+    memory_bus.write_byte(P1, 0x10);
+    assert_eq!(memory_bus.read_byte(P1) & 0x01, 0);
+}
+
+#[test]
+fn fast_runs_draw_only_every_nth_frame() {
+    for (speed, interval) in [(0.5, 1), (1.0, 1), (4.0, 1), (4.1, 2), (8.0, 2), (30.0, 8)] {
+        assert_eq!(constants::draw_interval(speed), interval, "{}x", speed);
+    }
+}
+
+#[test]
+fn each_ppu_mode_gets_its_own_label_and_color() {
+    assert_eq!(
+        ppu_mode_label(HBLANK_MODE),
+        ("HBlank", constants::PPU_MODE_HBLANK_COLOR)
+    );
+    assert_eq!(
+        ppu_mode_label(VBLANK_MODE),
+        ("VBlank", constants::PPU_MODE_VBLANK_COLOR)
+    );
+    assert_eq!(
+        ppu_mode_label(OAM_SCAN_MODE),
+        ("OAM", constants::PPU_MODE_OAM_COLOR)
+    );
+    assert_eq!(
+        ppu_mode_label(VRAM_READ_MODE),
+        ("VRAM", constants::PPU_MODE_VRAM_COLOR)
+    );
+    assert_eq!(ppu_mode_label(4).0, "???");
+}
+
+/// Writes `rom` as `game.gb` into a fresh directory of its own, so the `.sav` and
+/// `.stateN` files written next to it don't collide between tests.
+fn rom_file(test: &str, rom: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("boba_app_{}_{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("game.gb");
+    fs::write(&path, rom).unwrap();
+    path
+}
+
+/// A 32 KB MBC1+RAM+BATTERY cartridge with 8 KB of RAM that counts up in A forever
+/// (INC A; JR -3 at 0x0100).
+fn battery_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0x3C, 0x18, 0xFD]);
+    rom[0x147] = 0x03;
+    rom[0x149] = 0x02;
+    rom
+}
+
+fn running_emulator(rom_path: &Path) -> Emulator {
+    let mut emulator = EmulatorBuilder::new()
+        .skip_boot_rom(true)
+        .build(rom_path)
+        .unwrap();
+    emulator.stepping = false;
+    emulator
+}
+
+#[test]
+fn battery_ram_survives_ejecting_and_reinserting_through_the_sav_file() {
+    let rom_path = rom_file("sav", &battery_rom());
+    let mut emulator = running_emulator(&rom_path);
+    emulator.memory_bus.write_byte(0x0000, 0x0A); // Enable cartridge RAM
+    emulator.memory_bus.write_byte(0xA000, 0x5A);
+    emulator.memory_bus.write_byte(0xBFFF, 0xA5);
+
+    emulator.eject_cartridge().unwrap();
+    let sav = fs::read(rom_path.with_extension("sav")).unwrap();
+    assert_eq!(sav.len(), 0x2000);
+    assert_eq!((sav[0], sav[0x1FFF]), (0x5A, 0xA5));
+
+    for mut emulator in [emulator, running_emulator(&rom_path)] {
+        emulator.insert_cartridge(&rom_path).unwrap();
+        emulator.memory_bus.write_byte(0x0000, 0x0A);
+        assert_eq!(emulator.memory_bus.read_byte(0xA000), 0x5A);
+        assert_eq!(emulator.memory_bus.read_byte(0xBFFF), 0xA5);
+    }
+}
+
+#[test]
+fn state_slots_are_separate_state_n_files_next_to_the_rom() {
+    let rom_path = rom_file("slots", &battery_rom());
+    let mut emulator = running_emulator(&rom_path);
+    assert!(emulator.set_state_slot(constants::STATE_SLOTS).is_err());
+    assert_eq!(emulator.state_slot(), 0);
+
+    emulator.run_frame().unwrap();
+    emulator.set_state_slot(3).unwrap();
+    let state_path = emulator.save_state_file().unwrap();
+    assert_eq!(state_path, rom_path.with_file_name("game.state3"));
+    let saved = emulator.cpu.snapshot();
+
+    emulator.run_frame().unwrap();
+    assert_ne!(emulator.cpu.snapshot(), saved);
+    emulator.set_state_slot(4).unwrap();
+    assert!(emulator.load_state_file().is_err()); // Nothing saved there yet
+    assert_ne!(emulator.cpu.snapshot(), saved);
+
+    emulator.set_state_slot(3).unwrap();
+    assert_eq!(emulator.load_state_file().unwrap(), state_path);
+    assert_eq!(emulator.cpu.snapshot(), saved);
+}
+
+#[test]
+fn gif_frames_use_each_shades_palette_index() {
+    let shades = [0u8, 1, 2, 3, 3, 2, 1, 0];
+    let mut reversed = DEFAULT_PALETTE;
+    reversed.reverse();
+    for palette in [DEFAULT_PALETTE, reversed] {
+        // Shades as `render_into` draws them
+        let rgba: Vec<u8> = shades
+            .iter()
+            .flat_map(|&shade| {
+                let color = palette[shade as usize];
+                [color.r, color.g, color.b, 0xFF]
+            })
+            .collect();
+        assert_eq!(rgba_to_palette_indices(&rgba, &palette), shades);
+    }
+
+    // A slightly tinted pixel still lands on the nearest shade
+    let dark = DEFAULT_PALETTE[2];
+    let tinted = [dark.r + 4, dark.g.saturating_sub(3), dark.b + 2, 0xFF];
+    assert_eq!(rgba_to_palette_indices(&tinted, &DEFAULT_PALETTE), [2]);
+}
+
+#[test]
+fn a_font_that_wont_load_leaves_the_debug_text_off_instead_of_failing() {
+    let ttf_context = sdl2::ttf::init().unwrap();
+    let missing = Path::new("no/such/font.ttf");
+    assert!(sdl_setup::load_debug_font(&ttf_context, Some(missing), &[]).is_none());
+    assert!(sdl_setup::load_debug_font(&ttf_context, None, b"not a font").is_none());
+
+    // A missing --font still falls back to the built-in one
+    assert!(
+        sdl_setup::load_debug_font(&ttf_context, Some(missing), constants::DEBUG_FONT).is_some()
+    );
+}
+
+#[test]
+fn the_embedded_font_loads_from_memory() {
+    assert!(!constants::DEBUG_FONT.is_empty());
+    let ttf_context = sdl2::ttf::init().unwrap();
+    let font = sdl_setup::load_debug_font(&ttf_context, None, constants::DEBUG_FONT).unwrap();
+    assert!(font.height() > 0);
+}
@@ -0,0 +1,300 @@
+use boba::apu::SAMPLE_RATE;
+use boba::emulator::Emulator;
+
+const NR10: u16 = 0xFF10;
+const NR11: u16 = 0xFF11;
+const NR12: u16 = 0xFF12;
+const NR13: u16 = 0xFF13;
+const NR14: u16 = 0xFF14;
+const NR21: u16 = 0xFF16;
+const NR22: u16 = 0xFF17;
+const NR23: u16 = 0xFF18;
+const NR24: u16 = 0xFF19;
+const NR30: u16 = 0xFF1A;
+const NR32: u16 = 0xFF1C;
+const NR33: u16 = 0xFF1D;
+const NR34: u16 = 0xFF1E;
+const NR42: u16 = 0xFF21;
+const NR43: u16 = 0xFF22;
+const NR44: u16 = 0xFF23;
+const WAVE_RAM: u16 = 0xFF30;
+const NR50: u16 = 0xFF24;
+const NR51: u16 = 0xFF25;
+const NR52: u16 = 0xFF26;
+
+/// Cycles per `Apu::step` call, about what one instruction takes.
+const STEP_CYCLES: u32 = 16;
+
+/// An emulator with the APU powered on and only `channel` routed to both outputs.
+fn emulator_with_output(channel: u8) -> Emulator {
+    let mut emulator = Emulator::from_rom_bytes(&vec![0u8; 0x8000], true).unwrap();
+    write(&mut emulator, NR52, 0x80);
+    write(&mut emulator, NR50, 0x77);
+    write(&mut emulator, NR51, 0x11 << (channel - 1));
+    emulator
+}
+
+/// Writes an APU register the way the CPU does and lets the APU pick it up.
+fn write(emulator: &mut Emulator, addr: u16, value: u8) {
+    emulator.memory_bus.write_byte(addr, value);
+    run(emulator, 0);
+}
+
+fn run(emulator: &mut Emulator, cycles: u32) {
+    let Emulator {
+        apu, memory_bus, ..
+    } = emulator;
+    apu.step(cycles % STEP_CYCLES, memory_bus);
+    for _ in 0..cycles / STEP_CYCLES {
+        apu.step(STEP_CYCLES, memory_bus);
+    }
+}
+
+/// Triggers channel 1 at full volume with the given NR11 duty and 11-bit frequency.
+fn trigger_ch1(emulator: &mut Emulator, duty: u8, frequency: u16) {
+    write(emulator, NR11, duty << 6);
+    write(emulator, NR12, 0xF0); // Volume 15, envelope off
+    write(emulator, NR13, frequency as u8);
+    write(emulator, NR14, 0x80 | (frequency >> 8) as u8); // Trigger
+}
+
+/// Triggers channel 2 at full volume, with NR21's duty and length bits given as-is.
+fn trigger_ch2(emulator: &mut Emulator, nr21: u8, frequency: u16, length_enable: bool) {
+    write(emulator, NR21, nr21);
+    write(emulator, NR22, 0xF0); // Volume 15, envelope off
+    write(emulator, NR23, frequency as u8);
+    let length_bit = if length_enable { 0x40 } else { 0x00 };
+    write(emulator, NR24, 0x80 | length_bit | (frequency >> 8) as u8);
+}
+
+/// Fraction of left-channel samples during which the pulse was high. The DAC maps
+/// volume 15 to the negative end of its range and 0 to the positive end.
+fn high_fraction(samples: &[f32]) -> f32 {
+    let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+    left.iter().filter(|&&sample| sample < 0.0).count() as f32 / left.len() as f32
+}
+
+#[test]
+fn triggered_channel_1_plays_its_duty_cycle() {
+    // Frequency 1751 is a 441 Hz tone: 100 samples per period at 44.1 kHz
+    for (duty, expected) in [(0, 0.125), (1, 0.25), (2, 0.5), (3, 0.75)] {
+        let mut emulator = emulator_with_output(1);
+        trigger_ch1(&mut emulator, duty, 1751);
+        assert!(emulator.apu.channel_active(1));
+        assert_eq!(emulator.memory_bus.read_byte(NR52) & 0x01, 0x01);
+
+        emulator.apu.take_samples();
+        run(&mut emulator, 4_194_304 / 10);
+        let samples = emulator.apu.take_samples();
+        assert!(samples.len().abs_diff((SAMPLE_RATE / 10 * 2) as usize) <= 2);
+        assert!(samples.iter().any(|&sample| sample != 0.0));
+
+        let fraction = high_fraction(&samples);
+        assert!(
+            (fraction - expected).abs() < 0.02,
+            "duty {}: high {:.3} of the time, expected {}",
+            duty,
+            fraction,
+            expected
+        );
+    }
+}
+
+#[test]
+fn sweep_raises_the_pitch_until_it_overflows() {
+    let mut emulator = emulator_with_output(1);
+    write(&mut emulator, NR10, 0x11); // Period 1, add, shift 1
+    trigger_ch1(&mut emulator, 2, 1024);
+    assert!(emulator.apu.channel_active(1));
+
+    // 1024 -> 1536 on the first sweep clock; the next would pass 2047, so the
+    // overflow check right after that clock turns the channel off. Sweep clocks come
+    // at 128 Hz, so a tenth of a second is plenty.
+    run(&mut emulator, 4_194_304 / 10);
+    assert!(!emulator.apu.channel_active(1));
+    assert_eq!(emulator.memory_bus.read_byte(NR52) & 0x01, 0x00);
+}
+
+#[test]
+fn trigger_with_overflowing_sweep_disables_channel_1() {
+    let mut emulator = emulator_with_output(1);
+    write(&mut emulator, NR10, 0x01); // Period 0, add, shift 1
+    trigger_ch1(&mut emulator, 2, 1400); // 1400 + 700 > 2047
+    assert!(!emulator.apu.channel_active(1));
+}
+
+#[test]
+fn subtracting_sweep_never_overflows() {
+    let mut emulator = emulator_with_output(1);
+    write(&mut emulator, NR10, 0x19); // Period 1, subtract, shift 1
+    trigger_ch1(&mut emulator, 2, 2000);
+    run(&mut emulator, 4_194_304 / 10);
+    assert!(emulator.apu.channel_active(1));
+}
+
+#[test]
+fn triggered_channel_2_plays_a_periodic_pulse() {
+    let mut emulator = emulator_with_output(2);
+    trigger_ch2(&mut emulator, 0x80, 1751, false); // 50% duty at 441 Hz
+    assert!(emulator.apu.channel_active(2));
+    assert_eq!(emulator.memory_bus.read_byte(NR52) & 0x02, 0x02);
+
+    emulator.apu.take_samples();
+    run(&mut emulator, 4_194_304 / 10);
+    let samples = emulator.apu.take_samples();
+    let fraction = high_fraction(&samples);
+    assert!(
+        (fraction - 0.5).abs() < 0.02,
+        "high {:.3} of the time",
+        fraction
+    );
+
+    // 100 samples per period: the waveform repeats itself one period later
+    let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+    let matching = (0..left.len() - 100)
+        .filter(|&i| (left[i] < 0.0) == (left[i + 100] < 0.0))
+        .count();
+    assert!(matching as f32 / (left.len() - 100) as f32 > 0.95);
+}
+
+#[test]
+fn channel_2_length_counter_stops_it() {
+    let mut emulator = emulator_with_output(2);
+    trigger_ch2(&mut emulator, 0x80 | 32, 1751, true); // Length 64 - 32 = 32 ticks at 256 Hz
+
+    run(&mut emulator, 4_194_304 / 10); // 100 ms of the 125 ms
+    assert!(emulator.apu.channel_active(2));
+    run(&mut emulator, 4_194_304 / 20);
+    assert!(!emulator.apu.channel_active(2));
+    assert_eq!(emulator.memory_bus.read_byte(NR52) & 0x02, 0x00);
+}
+
+#[test]
+fn retriggering_channel_2_restarts_its_envelope_and_length() {
+    let mut emulator = emulator_with_output(2);
+    trigger_ch2(&mut emulator, 0x80 | 32, 1751, true); // Length 32 ticks at 256 Hz
+    write(&mut emulator, NR22, 0xF1); // Volume 15, down one step every 1/64 s
+    write(&mut emulator, NR24, 0xC0 | (1751 >> 8) as u8); // Retrigger to pick it up
+    assert_eq!(emulator.apu.channel_volume(2), 15);
+
+    // Mid-play: the envelope has decayed and the length counted down
+    run(&mut emulator, 4_194_304 / 10);
+    let volume = emulator.apu.channel_volume(2);
+    let length = emulator.apu.channel_length(2);
+    assert!((1..15).contains(&volume), "volume {}", volume);
+    assert!((1..32).contains(&length), "length {}", length);
+
+    // A retrigger restarts the envelope, but a running length counter keeps counting
+    write(&mut emulator, NR24, 0xC0 | (1751 >> 8) as u8);
+    assert!(emulator.apu.channel_active(2));
+    assert_eq!(emulator.apu.channel_volume(2), 15);
+    assert_eq!(emulator.apu.channel_length(2), length);
+
+    // Once it has expired and stopped the channel, a retrigger reloads it in full
+    run(&mut emulator, 4_194_304 / 10);
+    assert!(!emulator.apu.channel_active(2));
+    assert!(emulator.apu.channel_volume(2) < 15);
+    write(&mut emulator, NR24, 0xC0 | (1751 >> 8) as u8);
+    assert!(emulator.apu.channel_active(2));
+    assert_eq!(emulator.apu.channel_volume(2), 15);
+    assert_eq!(emulator.apu.channel_length(2), 64);
+}
+
+/// Loads wave RAM with two rising ramps (0, 1, ... 15, 0, 1, ... 15), then powers the
+/// DAC and triggers channel 3 at its lowest frequency with the given NR32 level.
+fn play_wave_ramp(emulator: &mut Emulator, nr32: u8) {
+    for i in 0..16 {
+        let byte = (((i * 2 % 16) << 4) | ((i * 2 + 1) % 16)) as u8;
+        write(emulator, WAVE_RAM + i, byte);
+    }
+    write(emulator, NR30, 0x80);
+    write(emulator, NR32, nr32);
+    write(emulator, NR33, 0x00);
+    write(emulator, NR34, 0x80); // Trigger, frequency 0: 4096 cycles per sample
+}
+
+/// The left-channel output of channel 3 as digital levels (0-15), with runs of the same
+/// level collapsed. Level 0 is the loudest positive output, which every test reaches.
+fn wave_levels(samples: &[f32]) -> Vec<u8> {
+    let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+    let full_scale = left.iter().copied().fold(f32::MIN, f32::max);
+    let mut levels: Vec<u8> = left
+        .iter()
+        .map(|&sample| ((1.0 - sample / full_scale) * 7.5).round() as u8)
+        .collect();
+    levels.dedup();
+    levels
+}
+
+#[test]
+fn channel_3_plays_wave_ram_at_full_volume() {
+    let mut emulator = emulator_with_output(3);
+    play_wave_ramp(&mut emulator, 0x20); // 100%
+    assert!(emulator.apu.channel_active(3));
+    assert_eq!(emulator.memory_bus.read_byte(NR52) & 0x04, 0x04);
+
+    emulator.apu.take_samples();
+    run(&mut emulator, 4_194_304 / 10);
+    let levels = wave_levels(&emulator.apu.take_samples());
+
+    assert!(levels.len() > 32, "only {} level changes", levels.len());
+    assert_eq!(levels.iter().max(), Some(&15));
+    for pair in levels.windows(2) {
+        assert_eq!(pair[1], (pair[0] + 1) % 16, "levels: {:?}", levels);
+    }
+}
+
+#[test]
+fn channel_3_volume_shift_halves_the_wave() {
+    let mut emulator = emulator_with_output(3);
+    play_wave_ramp(&mut emulator, 0x40); // 50%: each sample shifted right once
+
+    emulator.apu.take_samples();
+    run(&mut emulator, 4_194_304 / 10);
+    let levels = wave_levels(&emulator.apu.take_samples());
+
+    assert_eq!(levels.iter().max(), Some(&7));
+    for pair in levels.windows(2) {
+        assert_eq!(pair[1], (pair[0] + 1) % 8, "levels: {:?}", levels);
+    }
+}
+
+/// Cycles per LFSR clock with NR43 divisor code 0 (8) and clock shift 8.
+const NOISE_PERIOD: u32 = 8 << 8;
+
+/// The first 64 values of LFSR bit 0 after a trigger reloads it with all ones.
+const LFSR_15BIT: &str = "1111111111111110000000000000010000000000000110000000000001010000";
+const LFSR_7BIT: &str = "1111111000000100000110000101000111100100010110011101010011111010";
+
+/// Triggers channel 4 at full volume with `nr43`, runs it for 64 LFSR clocks and
+/// reads LFSR bit 0 back from the output in the middle of each clock. The channel
+/// outputs its volume while bit 0 is clear, which the DAC makes negative.
+fn noise_bits(nr43: u8) -> String {
+    let mut emulator = emulator_with_output(4);
+    write(&mut emulator, NR42, 0xF0); // Volume 15, envelope off
+    write(&mut emulator, NR43, nr43);
+    write(&mut emulator, NR44, 0x80); // Trigger
+    assert!(emulator.apu.channel_active(4));
+
+    emulator.apu.take_samples();
+    run(&mut emulator, NOISE_PERIOD * 65);
+    let samples = emulator.apu.take_samples();
+    let cycles_per_sample = 4_194_304.0 / SAMPLE_RATE as f64;
+    (0..64)
+        .map(|clock| {
+            let middle = (clock as f64 + 0.5) * NOISE_PERIOD as f64;
+            let index = (middle / cycles_per_sample) as usize;
+            if samples[index * 2] > 0.0 { '1' } else { '0' }
+        })
+        .collect()
+}
+
+#[test]
+fn channel_4_follows_the_15_bit_lfsr_sequence() {
+    assert_eq!(noise_bits(0x80), LFSR_15BIT); // Shift 8, 15-bit, divisor 8
+}
+
+#[test]
+fn channel_4_follows_the_7_bit_lfsr_sequence() {
+    assert_eq!(noise_bits(0x88), LFSR_7BIT); // Shift 8, 7-bit, divisor 8
+}
@@ -0,0 +1,132 @@
+use boba::emulator::{BreakReason, Emulator};
+
+/// A ROM that jumps to 0x0150 and counts up in A forever (INC A at 0x0150).
+fn counting_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]); // JP 0x0150
+    rom[0x150..0x153].copy_from_slice(&[0x3C, 0x18, 0xFD]); // INC A; JR -3
+    rom
+}
+
+#[test]
+fn run_frame_stops_at_breakpoint() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    emulator.add_breakpoint(0x0151);
+
+    assert_eq!(
+        emulator.run_frame_until_breakpoint(),
+        Ok(Some(BreakReason::Breakpoint(0x0151)))
+    );
+    assert_eq!(emulator.cpu.pc(), 0x0151);
+    let (a, ..) = emulator.cpu.registers();
+
+    // Resuming runs the instruction at the breakpoint, then stops on the next pass
+    assert_eq!(
+        emulator.run_frame_until_breakpoint(),
+        Ok(Some(BreakReason::Breakpoint(0x0151)))
+    );
+    assert_eq!(emulator.cpu.pc(), 0x0151);
+    assert_eq!(emulator.cpu.registers().0, a.wrapping_add(1));
+}
+
+#[test]
+fn removed_breakpoint_no_longer_stops() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    assert!(emulator.add_breakpoint(0x0151));
+    assert!(!emulator.add_breakpoint(0x0151));
+    assert!(emulator.remove_breakpoint(0x0151));
+    assert!(!emulator.remove_breakpoint(0x0151));
+
+    assert_eq!(emulator.run_frame_until_breakpoint(), Ok(None));
+}
+
+/// A ROM whose subroutine at 0x0200 calls another at 0x0210 before returning.
+fn nested_call_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x105].copy_from_slice(&[0xCD, 0x00, 0x02, 0x04, 0x18]); // CALL 0x0200; INC B; JR
+    rom[0x105] = 0xFE; // ...-2
+    rom[0x200..0x205].copy_from_slice(&[0xCD, 0x10, 0x02, 0x0C, 0xC9]); // CALL 0x0210; INC C; RET
+    rom[0x210..0x212].copy_from_slice(&[0x14, 0xC9]); // INC D; RET
+    rom
+}
+
+#[test]
+fn step_out_runs_past_nested_calls_to_the_callers_return() {
+    let mut emulator = Emulator::from_rom_bytes(&nested_call_rom(), true).unwrap();
+    emulator.step_instruction().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0200);
+
+    emulator.step_out().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0103);
+    assert_eq!(emulator.cpu.sp(), 0xFFFE);
+    let (_, _, b, c, d, ..) = emulator.cpu.registers();
+    assert_eq!((b, c, d), (0x00, 0x14, 0x01)); // C starts at 0x13 after boot
+}
+
+#[test]
+fn step_out_of_a_nested_call_stops_in_its_caller() {
+    let mut emulator = Emulator::from_rom_bytes(&nested_call_rom(), true).unwrap();
+    emulator.step_instruction().unwrap();
+    emulator.step_instruction().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0210);
+
+    emulator.step_out().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0203);
+    assert_eq!(emulator.cpu.registers().3, 0x13); // INC C hasn't run
+}
+
+/// A ROM that calls a subroutine recursing B times, then RST 0x08 (INC D; RET).
+fn recursive_call_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x08..0x0A].copy_from_slice(&[0x14, 0xC9]); // INC D; RET
+    rom[0x100..0x109].copy_from_slice(&[
+        0x06, 0x03, // LD B,3
+        0xCD, 0x00, 0x02, // CALL 0x0200
+        0xCF, // RST 0x08 (0x0105)
+        0x1C, // INC E
+        0x18, 0xFE, // JR -2
+    ]);
+    rom[0x200..0x207].copy_from_slice(&[
+        0x05, // DEC B
+        0x28, 0x03, // JR Z,+3
+        0xCD, 0x00, 0x02, // CALL 0x0200
+        0xC9, // RET (0x0206)
+    ]);
+    rom
+}
+
+#[test]
+fn step_over_runs_a_call_or_rst_to_completion() {
+    let mut emulator = Emulator::from_rom_bytes(&recursive_call_rom(), true).unwrap();
+    emulator.step_instruction().unwrap();
+
+    emulator.step_over().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0105);
+    assert_eq!(emulator.cpu.sp(), 0xFFFE);
+    assert_eq!(emulator.cpu.registers().2, 0x00);
+
+    emulator.step_over().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0106);
+    assert_eq!(emulator.cpu.registers().4, 0x01);
+
+    // Anything else is a single step
+    emulator.step_over().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0107);
+    assert_eq!(emulator.cpu.registers().5, 0xD9); // E starts at 0xD8 after boot
+}
+
+#[test]
+fn step_over_a_recursive_call_stops_at_its_own_return() {
+    let mut emulator = Emulator::from_rom_bytes(&recursive_call_rom(), true).unwrap();
+    for _ in 0..4 {
+        emulator.step_instruction().unwrap(); // LD B,3; CALL; DEC B; JR Z
+    }
+    assert_eq!(emulator.cpu.pc(), 0x0203);
+    let sp = emulator.cpu.sp();
+
+    // The inner calls return to 0x0206 too, but deeper in the stack
+    emulator.step_over().unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0206);
+    assert_eq!(emulator.cpu.sp(), sp);
+    assert_eq!(emulator.cpu.registers().2, 0x00);
+}
@@ -0,0 +1,418 @@
+use boba::cpu::Cpu;
+use boba::emulator::{Emulator, EmulatorBuilder};
+use boba::memory_bus::MemoryBus;
+use boba::memory_map::POST_BOOT_IF;
+
+const FLAG_Z: u8 = 0x80;
+const FLAG_N: u8 = 0x40;
+const FLAG_H: u8 = 0x20;
+const FLAG_C: u8 = 0x10;
+
+const IE: u16 = 0xFFFF;
+const IF: u16 = 0xFF0F;
+const TIMER_INTERRUPT: u8 = 0x04;
+const TIMER_VECTOR: usize = 0x0050;
+
+/// A 32 KB ROM with `code` at the 0x0100 entry point.
+fn program_rom(code: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(code);
+    rom
+}
+
+/// Builds an emulator (past the boot ROM) whose program at 0x0100 is `code`.
+fn emulator_running(code: &[u8]) -> Emulator {
+    Emulator::from_rom_bytes(&program_rom(code), true).unwrap()
+}
+
+/// Executes `count` instructions.
+fn run(emulator: &mut Emulator, count: usize) {
+    for _ in 0..count {
+        emulator.step_instruction().unwrap();
+    }
+}
+
+fn flags(emulator: &Emulator) -> u8 {
+    emulator.cpu.registers().1
+}
+
+fn word_at(emulator: &Emulator, addr: u16) -> u16 {
+    u16::from_le_bytes([
+        emulator.memory_bus.read_byte(addr),
+        emulator.memory_bus.read_byte(addr.wrapping_add(1)),
+    ])
+}
+
+fn hl(emulator: &Emulator) -> u16 {
+    let (_, _, _, _, _, _, h, l) = emulator.cpu.registers();
+    u16::from_be_bytes([h, l])
+}
+
+#[test]
+fn inc_hl_indirect_wraps_to_zero_and_keeps_carry() {
+    let mut emulator = emulator_running(&[
+        0x21, 0x00, 0xC0, // LD HL,0xC000
+        0x36, 0xFF, // LD (HL),0xFF
+        0x37, // SCF
+        0x34, // INC (HL)
+    ]);
+    run(&mut emulator, 4);
+
+    assert_eq!(emulator.memory_bus.read_byte(0xC000), 0x00);
+    assert_eq!(flags(&emulator), FLAG_Z | FLAG_H | FLAG_C);
+}
+
+#[test]
+fn dec_hl_indirect_wraps_to_ff_and_keeps_carry() {
+    let mut emulator = emulator_running(&[
+        0x21, 0x00, 0xC0, // LD HL,0xC000
+        0x36, 0x00, // LD (HL),0x00
+        0x37, // SCF
+        0x35, // DEC (HL)
+    ]);
+    run(&mut emulator, 4);
+
+    assert_eq!(emulator.memory_bus.read_byte(0xC000), 0xFF);
+    assert_eq!(flags(&emulator), FLAG_N | FLAG_H | FLAG_C);
+}
+
+#[test]
+fn ld_a_hl_increment_reads_before_incrementing() {
+    let mut emulator = emulator_running(&[
+        0x21, 0x00, 0xC0, // LD HL,0xC000
+        0x36, 0x42, // LD (HL),0x42
+        0x2A, // LD A,(HL+)
+    ]);
+    run(&mut emulator, 3);
+
+    assert_eq!(emulator.cpu.registers().0, 0x42);
+    assert_eq!(hl(&emulator), 0xC001);
+}
+
+#[test]
+fn ld_hl_decrement_a_writes_before_decrementing() {
+    let mut emulator = emulator_running(&[
+        0x21, 0x00, 0xC0, // LD HL,0xC000
+        0x3E, 0x99, // LD A,0x99
+        0x32, // LD (HL-),A
+    ]);
+    run(&mut emulator, 3);
+
+    assert_eq!(emulator.memory_bus.read_byte(0xC000), 0x99);
+    assert_eq!(hl(&emulator), 0xBFFF);
+}
+
+#[test]
+fn interrupt_dispatch_pushes_the_next_pc_and_reti_returns_to_it() {
+    let mut rom = program_rom(&[
+        0xFB, // EI
+        0x00, // NOP
+        0x00, // NOP: the interrupt is taken before this one
+    ]);
+    rom[TIMER_VECTOR] = 0xD9; // RETI
+    let mut emulator = Emulator::from_rom_bytes(&rom, true).unwrap();
+    run(&mut emulator, 2);
+    emulator.memory_bus.write_byte(IE, TIMER_INTERRUPT);
+    emulator.memory_bus.write_byte(IF, TIMER_INTERRUPT);
+    let pc = emulator.cpu.pc();
+    let sp = emulator.cpu.sp();
+
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), TIMER_VECTOR as u16);
+    assert_eq!(emulator.cpu.sp(), sp.wrapping_sub(2));
+    assert_eq!(word_at(&emulator, emulator.cpu.sp()), pc);
+    assert!(!emulator.cpu.ime());
+
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), pc);
+    assert_eq!(emulator.cpu.sp(), sp);
+    assert!(emulator.cpu.ime());
+}
+
+#[test]
+fn rst_jumps_to_its_vector_and_pushes_the_next_pc() {
+    for (opcode, vector) in [
+        (0xC7, 0x00),
+        (0xCF, 0x08),
+        (0xD7, 0x10),
+        (0xDF, 0x18),
+        (0xE7, 0x20),
+        (0xEF, 0x28),
+        (0xF7, 0x30),
+        (0xFF, 0x38),
+    ] {
+        let mut emulator = emulator_running(&[opcode]);
+        let sp = emulator.cpu.sp();
+        run(&mut emulator, 1);
+
+        assert_eq!(emulator.cpu.pc(), vector, "RST {:02X}H", vector);
+        assert_eq!(emulator.cpu.sp(), sp.wrapping_sub(2));
+        assert_eq!(word_at(&emulator, emulator.cpu.sp()), 0x0101);
+    }
+}
+
+#[test]
+fn add_a_hl_indirect_reads_the_selected_rom_bank() {
+    let code = [
+        0x21, 0x00, 0x40, // LD HL,0x4000
+        0x3E, 0x02, // LD A,2
+        0xEA, 0x00, 0x20, // LD (0x2000),A: select ROM bank 2
+        0x3E, 0x01, // LD A,1
+        0x86, // ADD A,(HL)
+        0x3E, 0x03, // LD A,3
+        0xEA, 0x00, 0x20, // LD (0x2000),A: select ROM bank 3
+        0x3E, 0x01, // LD A,1
+        0x86, // ADD A,(HL)
+    ];
+    let mut rom = vec![0u8; 0x20000]; // 128 KB: 8 banks
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    rom[0x147] = 0x01; // MBC1
+    rom[0x148] = 0x02; // 128 KB
+    rom[2 * 0x4000] = 0x20;
+    rom[3 * 0x4000] = 0x30;
+    let mut emulator = Emulator::from_rom_bytes(&rom, true).unwrap();
+
+    run(&mut emulator, 5);
+    assert_eq!(emulator.cpu.registers().0, 0x21);
+    run(&mut emulator, 4);
+    assert_eq!(emulator.cpu.registers().0, 0x31);
+}
+
+/// Enables and requests the timer interrupt. With IME off, a HALT after this hits the
+/// HALT bug.
+const REQUEST_TIMER_INTERRUPT: [u8; 6] = [
+    0x3E,
+    TIMER_INTERRUPT, // LD A,0x04
+    0xE0,
+    0xFF, // LDH (IE),A
+    0xE0,
+    0x0F, // LDH (IF),A
+];
+
+#[test]
+fn halt_bug_runs_the_next_instruction_twice() {
+    let mut code = REQUEST_TIMER_INTERRUPT.to_vec();
+    code.extend([
+        0x76, // HALT (0x0106)
+        0x04, // INC B (0x0107)
+        0x00, // NOP
+    ]);
+    let mut emulator = emulator_running(&code);
+    run(&mut emulator, 4);
+    assert!(!emulator.cpu.halted());
+    assert_eq!(emulator.cpu.pc(), 0x0107);
+
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), 0x0107); // PC didn't move past INC B
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), 0x0108);
+    assert_eq!(emulator.cpu.registers().2, 0x02);
+}
+
+#[test]
+fn halt_bug_reads_the_opcode_again_as_an_operand() {
+    let mut code = REQUEST_TIMER_INTERRUPT.to_vec();
+    code.extend([
+        0x76, // HALT
+        0x3E, 0x14, // LD A,0x14, run as LD A,0x3E then INC D
+    ]);
+    let mut emulator = emulator_running(&code);
+    run(&mut emulator, 6);
+
+    let (a, _, _, _, d, _, _, _) = emulator.cpu.registers();
+    assert_eq!(a, 0x3E);
+    assert_eq!(d, 0x01);
+    assert_eq!(emulator.cpu.pc(), 0x0109);
+}
+
+#[test]
+fn halt_with_ime_off_wakes_without_servicing_the_interrupt() {
+    let mut emulator = emulator_running(&[
+        0x3E,
+        TIMER_INTERRUPT, // LD A,0x04
+        0xE0,
+        0xFF, // LDH (IE),A
+        0x76, // HALT (0x0104)
+        0x04, // INC B (0x0105)
+        0x00, // NOP
+    ]);
+    run(&mut emulator, 3);
+    run(&mut emulator, 10); // Nothing pending: stays halted
+    assert!(emulator.cpu.halted());
+    assert_eq!(emulator.cpu.pc(), 0x0105);
+
+    emulator.memory_bus.write_byte(IF, TIMER_INTERRUPT);
+    run(&mut emulator, 1);
+    assert!(!emulator.cpu.halted());
+    run(&mut emulator, 1);
+    // Carries on after the HALT, running INC B once, with the interrupt left pending
+    assert_eq!(emulator.cpu.pc(), 0x0106);
+    assert_eq!(emulator.cpu.registers().2, 0x01);
+    assert_eq!(
+        emulator.memory_bus.read_byte(IF) & TIMER_INTERRUPT,
+        TIMER_INTERRUPT
+    );
+}
+
+#[test]
+fn ei_services_an_interrupt_only_after_the_next_instruction() {
+    let mut emulator = emulator_running(&[
+        0x3E,
+        TIMER_INTERRUPT, // LD A,0x04
+        0xE0,
+        0xFF, // LDH (IE),A
+        0xFB, // EI
+        0xE0,
+        0x0F, // LDH (IF),A: the interrupt is pending from here
+        0x04, // INC B (0x0107)
+    ]);
+    run(&mut emulator, 3);
+    assert!(!emulator.cpu.ime()); // Not until the instruction after EI has started
+
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), 0x0107);
+    assert_eq!(
+        emulator.memory_bus.read_byte(IF) & TIMER_INTERRUPT,
+        TIMER_INTERRUPT
+    );
+
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), TIMER_VECTOR as u16);
+    assert_eq!(word_at(&emulator, emulator.cpu.sp()), 0x0107);
+    assert_eq!(emulator.cpu.registers().2, 0x00); // INC B hasn't run
+}
+
+#[test]
+fn ei_then_di_services_no_interrupt() {
+    let mut code = REQUEST_TIMER_INTERRUPT.to_vec();
+    code.extend([
+        0xFB, // EI
+        0xF3, // DI
+        0x04, // INC B
+    ]);
+    let mut emulator = emulator_running(&code);
+    run(&mut emulator, 6);
+
+    assert_eq!(emulator.cpu.pc(), 0x0109);
+    assert_eq!(emulator.cpu.registers().2, 0x01);
+    assert!(!emulator.cpu.ime());
+    assert_eq!(
+        emulator.memory_bus.read_byte(IF) & TIMER_INTERRUPT,
+        TIMER_INTERRUPT
+    );
+}
+
+#[test]
+fn ei_then_halt_services_the_interrupt_and_returns_to_the_halt() {
+    let mut code = REQUEST_TIMER_INTERRUPT.to_vec();
+    code.extend([
+        0xFB, // EI
+        0x76, // HALT (0x0107)
+        0x04, // INC B
+    ]);
+    let mut rom = program_rom(&code);
+    rom[TIMER_VECTOR] = 0xD9; // RETI
+    let mut emulator = Emulator::from_rom_bytes(&rom, true).unwrap();
+    run(&mut emulator, 5);
+    assert!(!emulator.cpu.halted());
+
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), TIMER_VECTOR as u16);
+    assert_eq!(word_at(&emulator, emulator.cpu.sp()), 0x0107);
+
+    // Back at the HALT with nothing pending, it halts this time
+    run(&mut emulator, 2);
+    assert_eq!(emulator.cpu.pc(), 0x0108);
+    assert!(emulator.cpu.halted());
+    assert_eq!(emulator.cpu.registers().2, 0x00);
+}
+
+#[test]
+fn snapshots_match_after_the_same_instructions_and_diverge_after_different_ones() {
+    let code = [
+        0x3E, 0x05, // LD A,5
+        0x3C, // INC A
+    ];
+    let mut first = emulator_running(&code);
+    let mut second = emulator_running(&code);
+    run(&mut first, 2);
+    run(&mut second, 2);
+    assert_eq!(first.cpu.snapshot(), second.cpu.snapshot());
+
+    let mut other = emulator_running(&[
+        0x3E, 0x05, // LD A,5
+        0x3D, // DEC A
+    ]);
+    run(&mut other, 2);
+    assert_ne!(first.cpu.snapshot(), other.cpu.snapshot());
+}
+
+#[test]
+fn snapshot_tells_a_pending_ei_apart() {
+    let mut ei = emulator_running(&[0xFB]); // EI
+    let mut nop = emulator_running(&[0x00]); // NOP
+    run(&mut ei, 1);
+    run(&mut nop, 1);
+
+    // Same registers, but only one of them enables IME after the next instruction
+    assert_eq!(ei.cpu.registers(), nop.cpu.registers());
+    assert_ne!(ei.cpu.snapshot(), nop.cpu.snapshot());
+}
+
+#[test]
+fn skipping_the_boot_rom_leaves_the_configured_if() {
+    assert_eq!(emulator_running(&[]).memory_bus.read_byte(IF), POST_BOOT_IF);
+    for value in [0x00, 0x01, 0x1F] {
+        let emulator = EmulatorBuilder::new()
+            .post_boot_if(value)
+            .build(&program_rom(&[]))
+            .unwrap();
+        assert_eq!(emulator.memory_bus.read_byte(IF), 0xE0 | value);
+    }
+
+    let mut memory_bus = MemoryBus::new();
+    memory_bus.load_rom(&program_rom(&[])).unwrap();
+    Cpu::initialize_post_boot_io(&mut memory_bus);
+    assert_eq!(memory_bus.read_byte(IF), POST_BOOT_IF);
+}
+
+#[test]
+fn a_hand_wired_cpu_and_bus_start_where_the_emulator_does() {
+    let rom = program_rom(&[]);
+    let emulator = Emulator::from_rom_bytes(&rom, true).unwrap();
+    let cpu = Cpu::new(true);
+    let mut memory_bus = MemoryBus::new();
+    memory_bus.load_rom(&rom).unwrap();
+    Cpu::initialize_post_boot_io(&mut memory_bus);
+
+    assert_eq!(cpu.snapshot(), emulator.cpu.snapshot());
+    assert_eq!(
+        cpu.registers(),
+        (0x01, 0xB0, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D)
+    );
+    assert_eq!((cpu.pc(), cpu.sp()), (0x0100, 0xFFFE));
+    for addr in (0xFF00..=0xFF7F).chain([0xFFFF]) {
+        assert_eq!(
+            memory_bus.peek_byte(addr),
+            emulator.memory_bus.peek_byte(addr),
+            "{:#06X}",
+            addr
+        );
+    }
+}
+
+#[test]
+fn cb_prefixed_swap_a_is_two_bytes_long() {
+    let mut emulator = emulator_running(&[
+        0x3E, 0x5A, // LD A,0x5A
+        0xCB, 0x37, // SWAP A (0x0102)
+        0x04, // INC B (0x0104)
+    ]);
+    run(&mut emulator, 2);
+    assert_eq!(emulator.cpu.pc(), 0x0104);
+    assert_eq!(emulator.cpu.registers().0, 0xA5);
+
+    run(&mut emulator, 1);
+    assert_eq!(emulator.cpu.pc(), 0x0105);
+    assert_eq!(emulator.cpu.registers().2, 0x01);
+}
@@ -0,0 +1,216 @@
+use boba::emulator::Emulator;
+use boba::memory_bus::{BusHooks, MemoryBus};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Mode 2 (OAM scan) takes the first 80 dots of a line; Mode 3 (drawing) follows.
+const OAM_SCAN_DOTS: u32 = 80;
+
+/// Builds an emulator whose first instruction (at 0x0100) is `code`.
+fn emulator_running(code: &[u8]) -> Emulator {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x100 + code.len()].copy_from_slice(code);
+    Emulator::from_rom_bytes(&rom, true).unwrap()
+}
+
+/// Like `emulator_running`, with the PPU `dot` dots into a visible line.
+fn emulator_at_line_dot(code: &[u8], dot: u32) -> Emulator {
+    let mut emulator = emulator_running(code);
+
+    let Emulator {
+        ppu, memory_bus, ..
+    } = &mut emulator;
+    while ppu.mode() != 0 {
+        ppu.step(1, memory_bus);
+    }
+    while ppu.mode() != 2 {
+        ppu.step(1, memory_bus);
+    }
+    ppu.step(dot - 1, memory_bus);
+    emulator
+}
+
+/// Runs one instruction, clocking the PPU through the ticker, and returns the stack
+/// writes it made with the PPU mode each one saw, plus the instruction's cycle count.
+fn stack_writes_with_ppu_mode(emulator: &mut Emulator) -> (Vec<(u16, u8)>, u16) {
+    let mode = Rc::new(Cell::new(emulator.ppu.mode()));
+    let writes = Rc::new(RefCell::new(Vec::new()));
+    let (hook_mode, hook_writes) = (mode.clone(), writes.clone());
+    emulator.memory_bus.set_hooks(BusHooks {
+        on_read: None,
+        on_write: Some(Box::new(move |addr, _| {
+            hook_writes.borrow_mut().push((addr, hook_mode.get()));
+        })),
+    });
+
+    let Emulator {
+        cpu,
+        ppu,
+        memory_bus,
+        ..
+    } = emulator;
+    let cycles = cpu
+        .step_with_ticker(memory_bus, &mut |bus: &mut MemoryBus, cycles| {
+            ppu.step(cycles, bus);
+            mode.set(ppu.mode());
+        })
+        .unwrap();
+    let writes = writes.borrow().clone();
+    (writes, cycles)
+}
+
+#[test]
+fn call_pushes_see_ppu_advance_between_accesses() {
+    // CALL writes 16 and 20 cycles in, so these straddle the end of OAM scan
+    let mut emulator = emulator_at_line_dot(&[0xCD, 0x50, 0x01], OAM_SCAN_DOTS - 18); // CALL 0x0150
+    let sp = emulator.cpu.sp();
+
+    let (writes, cycles) = stack_writes_with_ppu_mode(&mut emulator);
+
+    assert_eq!(cycles, 24);
+    assert_eq!(
+        writes,
+        vec![(sp.wrapping_sub(1), 2), (sp.wrapping_sub(2), 3)]
+    );
+    assert_eq!(emulator.cpu.pc(), 0x0150);
+}
+
+#[test]
+fn push_writes_see_ppu_advance_between_accesses() {
+    // PUSH writes 8 and 12 cycles in
+    let mut emulator = emulator_at_line_dot(&[0xC5], OAM_SCAN_DOTS - 10); // PUSH BC
+    let sp = emulator.cpu.sp();
+
+    let (writes, cycles) = stack_writes_with_ppu_mode(&mut emulator);
+
+    assert_eq!(cycles, 16);
+    assert_eq!(
+        writes,
+        vec![(sp.wrapping_sub(1), 2), (sp.wrapping_sub(2), 3)]
+    );
+}
+
+#[test]
+fn step_without_ticker_keeps_full_instruction_cost() {
+    let mut emulator = emulator_running(&[0xCD, 0x50, 0x01]); // CALL 0x0150
+    assert_eq!(emulator.cpu.step(&mut emulator.memory_bus), Ok(24));
+    assert_eq!(emulator.cpu.pc(), 0x0150);
+}
+
+/// T-cycles per opcode from the standard timing tables, with conditional branches not
+/// taken. 0 marks the unused opcodes, which aren't run.
+#[rustfmt::skip]
+const OPCODE_CYCLES: [u16; 256] = [
+//  x0  x1  x2  x3  x4  x5  x6  x7  x8  x9  xA  xB  xC  xD  xE  xF
+     4, 12,  8,  8,  4,  4,  8,  4, 20,  8,  8,  8,  4,  4,  8,  4, // 0x
+     4, 12,  8,  8,  4,  4,  8,  4, 12,  8,  8,  8,  4,  4,  8,  4, // 1x
+     8, 12,  8,  8,  4,  4,  8,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 2x
+     8, 12,  8,  8, 12, 12, 12,  4,  8,  8,  8,  8,  4,  4,  8,  4, // 3x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 4x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 5x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 6x
+     8,  8,  8,  8,  8,  8,  4,  8,  4,  4,  4,  4,  4,  4,  8,  4, // 7x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 8x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // 9x
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // Ax
+     4,  4,  4,  4,  4,  4,  8,  4,  4,  4,  4,  4,  4,  4,  8,  4, // Bx
+     8, 12, 12, 16, 12, 16,  8, 16,  8, 16, 12,  8, 12, 24,  8, 16, // Cx
+     8, 12, 12,  0, 12, 16,  8, 16,  8, 16, 12,  0, 12,  0,  8, 16, // Dx
+    12, 12,  8,  0,  0, 16,  8, 16, 16,  4, 16,  0,  0,  0,  8, 16, // Ex
+    12, 12,  8,  4,  0, 16,  8, 16, 12,  8, 16,  4,  0,  0,  8, 16, // Fx
+];
+
+/// Conditional branches: opcode, T-cycles when taken, and whether they branch on a set
+/// flag (Z, C) rather than a clear one (NZ, NC).
+const TAKEN_CYCLES: [(u8, u16, bool); 16] = [
+    (0x20, 12, false),
+    (0x28, 12, true),
+    (0x30, 12, false),
+    (0x38, 12, true), // JR cc
+    (0xC0, 20, false),
+    (0xC8, 20, true),
+    (0xD0, 20, false),
+    (0xD8, 20, true), // RET cc
+    (0xC2, 16, false),
+    (0xCA, 16, true),
+    (0xD2, 16, false),
+    (0xDA, 16, true), // JP cc
+    (0xC4, 24, false),
+    (0xCC, 24, true),
+    (0xD4, 24, false),
+    (0xDC, 24, true), // CALL cc
+];
+
+/// Runs `setup` (untimed), then `code`, and returns what `code`'s first instruction
+/// cost, checking `step`'s return value against the CPU's cycle counter.
+fn instruction_cycles(setup: &[u8], code: &[u8]) -> u16 {
+    let program = [setup, code].concat();
+    let mut emulator = emulator_running(&program);
+    for _ in 0..setup.len() {
+        emulator.cpu.step(&mut emulator.memory_bus).unwrap(); // Setup is all 1-byte
+    }
+    let before = emulator.cpu.total_cycles();
+    let cycles = emulator.cpu.step(&mut emulator.memory_bus).unwrap();
+    assert_eq!(emulator.cpu.total_cycles() - before, cycles as u64);
+    cycles
+}
+
+/// After the boot ROM F is 0xB0: Z and C set. `OR A` with A=1 clears both.
+const FLAGS_SET: &[u8] = &[];
+const FLAGS_CLEAR: &[u8] = &[0xB7]; // OR A
+
+#[test]
+fn every_opcode_takes_its_documented_cycles() {
+    for opcode in 0..=0xFFu8 {
+        let expected = OPCODE_CYCLES[opcode as usize];
+        if expected == 0 || opcode == 0xCB {
+            continue;
+        }
+        let on_set_flag = TAKEN_CYCLES
+            .iter()
+            .find(|&&(conditional, _, _)| conditional == opcode)
+            .map(|&(_, _, on_set)| on_set);
+        // Conditional branches run here with their condition false
+        let setup = match on_set_flag {
+            Some(true) => FLAGS_CLEAR,
+            _ => FLAGS_SET,
+        };
+        assert_eq!(
+            instruction_cycles(setup, &[opcode, 0x00, 0x00]),
+            expected,
+            "opcode {:02X}",
+            opcode
+        );
+    }
+}
+
+#[test]
+fn taken_branches_take_their_documented_cycles() {
+    for (opcode, expected, on_set_flag) in TAKEN_CYCLES {
+        let setup = if on_set_flag { FLAGS_SET } else { FLAGS_CLEAR };
+        assert_eq!(
+            instruction_cycles(setup, &[opcode, 0x00, 0x00]),
+            expected,
+            "opcode {:02X} taken",
+            opcode
+        );
+    }
+}
+
+#[test]
+fn every_cb_opcode_takes_its_documented_cycles() {
+    for cb_opcode in 0..=0xFFu8 {
+        // (HL) operands need a memory access or two more: BIT only reads
+        let expected = match (cb_opcode & 0x07 == 6, cb_opcode >> 6) {
+            (false, _) => 8,
+            (true, 1) => 12,
+            (true, _) => 16,
+        };
+        assert_eq!(
+            instruction_cycles(FLAGS_SET, &[0xCB, cb_opcode]),
+            expected,
+            "opcode CB {:02X}",
+            cb_opcode
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use boba::cpu::Cpu;
+use boba::memory_bus::MemoryBus;
+
+#[test]
+fn disassemble_range_walks_instruction_boundaries() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x150..0x156].copy_from_slice(&[
+        0x00, // NOP
+        0x01, 0x34, 0x12, // LD BC,0x1234
+        0x18, 0xFE, // JR -2
+    ]);
+    let mut bus = MemoryBus::new();
+    bus.load_rom(&rom).unwrap();
+    let cpu = Cpu::new(true);
+
+    let lines = cpu.disassemble_range(0x0150, 3, &bus);
+    let addresses: Vec<(u16, u8)> = lines.iter().map(|&(addr, _, len)| (addr, len)).collect();
+    assert_eq!(addresses, [(0x0150, 1), (0x0151, 3), (0x0154, 2)]);
+    assert_eq!(lines[0].1, "NOP");
+    assert_eq!(lines[1].1, "LD BC, 0x1234");
+    assert_eq!(lines[2].1, "JR 0x0154");
+}
+
+#[test]
+fn relative_jumps_show_their_target() {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x150..0x154].copy_from_slice(&[
+        0x20, 0x05, // JR NZ,+5
+        0x18, 0xFC, // JR -4
+    ]);
+    let mut bus = MemoryBus::new();
+    bus.load_rom(&rom).unwrap();
+    let cpu = Cpu::new(true);
+
+    assert_eq!(cpu.disassemble_instruction(0x0150, &bus).0, "JR NZ, 0x0157");
+    assert_eq!(cpu.disassemble_instruction(0x0152, &bus).0, "JR 0x0150");
+}
+
+#[test]
+fn every_opcode_disassembles_to_a_length_of_one_to_three() {
+    let cpu = Cpu::new(true);
+    for opcode in 0..=0xFFu8 {
+        for next in [0x00, 0xFF] {
+            let mut rom = vec![0u8; 0x8000];
+            rom[0x150..0x153].copy_from_slice(&[opcode, next, next]);
+            let mut bus = MemoryBus::new();
+            bus.load_rom(&rom).unwrap();
+
+            let (text, length) = cpu.disassemble_instruction(0x0150, &bus);
+            assert!(
+                (1..=3).contains(&length),
+                "{:02X} {:02X} ({}) has length {}",
+                opcode,
+                next,
+                text,
+                length
+            );
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use boba::apu::SAMPLE_RATE;
+use boba::cpu::CpuSnapshot;
+use boba::emulator::{Emulator, EmulatorBuilder, FRAMES_PER_SECOND};
+use boba::memory_bus::BusHooks;
+use boba::ppu::FRAME_BUFFER_SIZE;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A 32 KB ROM-only cartridge that counts up in A forever (INC A; JR -3 at 0x0100).
+fn counting_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0x3C, 0x18, 0xFD]);
+    rom
+}
+
+/// CPU T-cycles one `run_frame` takes (it stops on the first instruction boundary past
+/// the budget).
+fn cycles_in_one_frame(emulator: &mut Emulator) -> u64 {
+    let before = emulator.cpu.total_cycles();
+    emulator.run_frame().unwrap();
+    emulator.cpu.total_cycles() - before
+}
+
+#[test]
+fn fast_forward_speeds_multiply_the_frame_budget() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    let normal = emulator.cycles_per_frame();
+    assert_eq!(normal, 69905);
+
+    for speed in [1.0, 2.0, 4.0, 1.0] {
+        emulator.set_clock_multiplier(speed).unwrap();
+        assert_eq!(emulator.clock_multiplier(), speed);
+        let budget = (normal as f64 * speed) as u64;
+        assert_eq!(emulator.cycles_per_frame() as u64, budget, "{}x", speed);
+        let cycles = cycles_in_one_frame(&mut emulator);
+        assert!(
+            (budget..budget + 12).contains(&cycles),
+            "{}x ran {}",
+            speed,
+            cycles
+        );
+    }
+}
+
+#[test]
+fn invalid_clock_multiplier_is_rejected_and_keeps_the_speed() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    emulator.set_clock_multiplier(2.0).unwrap();
+    for multiplier in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(emulator.set_clock_multiplier(multiplier).is_err());
+        assert!(
+            EmulatorBuilder::new()
+                .clock_multiplier(multiplier)
+                .build_empty()
+                .is_err()
+        );
+    }
+    assert_eq!(emulator.clock_multiplier(), 2.0);
+    assert_eq!(emulator.cycles_per_frame(), 139810);
+}
+
+const TIMA: u16 = 0xFF05;
+const TMA: u16 = 0xFF06;
+const TAC: u16 = 0xFF07;
+
+/// Timer increments during one frame with the timer at 4096 Hz (every 1024 cycles).
+fn timer_ticks_in_one_frame(emulator: &mut Emulator) -> u32 {
+    emulator.memory_bus.write_byte(TMA, 0x00);
+    emulator.memory_bus.write_byte(TIMA, 0x00);
+    emulator.memory_bus.write_byte(TAC, 0x04);
+    emulator.run_frame().unwrap();
+    emulator.memory_bus.read_byte(TIMA) as u32
+}
+
+#[test]
+fn doubling_the_clock_doubles_the_frame_budget_and_timer_ticks() {
+    let build = |multiplier| {
+        EmulatorBuilder::new()
+            .clock_multiplier(multiplier)
+            .build(&counting_rom())
+            .unwrap()
+    };
+    let mut normal = build(1.0);
+    let mut doubled = build(2.0);
+    assert_eq!(doubled.cycles_per_frame(), 2 * normal.cycles_per_frame());
+
+    let normal_ticks = timer_ticks_in_one_frame(&mut normal);
+    let doubled_ticks = timer_ticks_in_one_frame(&mut doubled);
+    assert_eq!(normal_ticks, 69905 / 1024);
+    assert!(
+        (2 * normal_ticks..=2 * normal_ticks + 1).contains(&doubled_ticks),
+        "{} ticks at 2x, {} at 1x",
+        doubled_ticks,
+        normal_ticks
+    );
+}
+
+#[test]
+fn audio_keeps_its_sample_rate_at_any_clock_speed() {
+    let frames_per_run_frame = SAMPLE_RATE as usize / FRAMES_PER_SECOND as usize;
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    for multiplier in [1.0, 2.0, 0.5, 4.0] {
+        emulator.set_clock_multiplier(multiplier).unwrap();
+        emulator.run_frame().unwrap();
+        emulator.apu.take_samples();
+        emulator.run_frame().unwrap();
+        let stereo_frames = emulator.apu.take_samples().len() / 2;
+        assert!(
+            (frames_per_run_frame - 1..=frames_per_run_frame + 1).contains(&stereo_frames),
+            "{}x: {} frames",
+            multiplier,
+            stereo_frames
+        );
+    }
+}
+
+const LCDC: u16 = 0xFF40;
+const LY: u16 = 0xFF44;
+const FRAME_CYCLES: u64 = 70224;
+
+#[test]
+fn run_until_vblank_stops_as_line_144_begins() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    emulator.run_until_vblank().unwrap();
+    for _ in 0..2 {
+        let before = emulator.cpu.total_cycles();
+        let frame = emulator.run_until_vblank().unwrap();
+        assert_eq!(frame.len(), FRAME_BUFFER_SIZE);
+        let cycles = emulator.cpu.total_cycles() - before;
+        assert!(
+            (FRAME_CYCLES - 8..FRAME_CYCLES + 8).contains(&cycles),
+            "a frame took {}",
+            cycles
+        );
+        assert_eq!(emulator.memory_bus.read_byte(LY), 144);
+        assert_eq!(emulator.ppu.mode(), 1);
+    }
+}
+
+#[test]
+fn run_until_vblank_gives_up_after_a_frame_with_the_lcd_off() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    emulator.memory_bus.write_byte(LCDC, 0x00);
+    let before = emulator.cpu.total_cycles();
+    emulator.run_until_vblank().unwrap();
+    let cycles = emulator.cpu.total_cycles() - before;
+    assert!(
+        (FRAME_CYCLES..FRAME_CYCLES + 12).contains(&cycles),
+        "ran {}",
+        cycles
+    );
+    assert_eq!(emulator.memory_bus.read_byte(LY), 0);
+}
+
+#[test]
+fn inserting_a_cartridge_restarts_on_the_new_rom() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    emulator.run_frame().unwrap();
+    assert_ne!(emulator.cpu.pc(), 0x0100);
+
+    let mut other = vec![0u8; 0x8000];
+    other[0x100..0x103].copy_from_slice(&[0x3D, 0x18, 0xFD]); // DEC A; JR -3
+    other[0x4000] = 0x42;
+    emulator.load_rom(&other).unwrap();
+    assert_eq!(emulator.cpu.pc(), 0x0100);
+    assert_eq!(emulator.cpu.total_cycles(), 0);
+    for addr in [0x0100, 0x0101, 0x0102, 0x4000] {
+        assert_eq!(emulator.memory_bus.read_byte(addr), other[addr as usize]);
+    }
+    let a = emulator.cpu.registers().0;
+    emulator.step_instruction().unwrap();
+    assert_eq!(emulator.cpu.registers().0, a.wrapping_sub(1));
+
+    // Ejecting leaves nothing of the old ROM behind
+    emulator.eject_cartridge();
+    assert_eq!(emulator.cpu.pc(), 0x0100);
+    for addr in [0x0100, 0x4000] {
+        assert_ne!(emulator.memory_bus.read_byte(addr), other[addr as usize]);
+    }
+}
+
+/// A ROM that keeps writing a rising counter across VRAM, so every frame looks
+/// different (LD HL,0x8000; loop: INC A; LD (HL+),A; RES 5,H; JR loop).
+fn vram_scribbling_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x109].copy_from_slice(&[0x21, 0x00, 0x80, 0x3C, 0x22, 0xCB, 0xAC, 0x18, 0xFA]);
+    rom
+}
+
+/// Everything `run_frame` leaves behind that a save state must reproduce.
+fn run_and_observe(emulator: &mut Emulator, frames: usize) -> (Vec<u8>, CpuSnapshot, u64, u8) {
+    for _ in 0..frames {
+        emulator.run_frame().unwrap();
+    }
+    (
+        emulator.frame_buffer().to_vec(),
+        emulator.cpu.snapshot(),
+        emulator.cpu.total_cycles(),
+        emulator.memory_bus.read_byte(TIMA),
+    )
+}
+
+#[test]
+fn loading_a_state_replays_the_same_frames() {
+    let mut emulator = Emulator::from_rom_bytes(&vram_scribbling_rom(), true).unwrap();
+    emulator.memory_bus.write_byte(TAC, 0x05);
+    emulator.run_frame().unwrap();
+    let state = emulator.save_state();
+    let at_save = emulator.frame_buffer().to_vec();
+
+    let first_run = run_and_observe(&mut emulator, 3);
+    assert_ne!(first_run.0, at_save, "the frames should change");
+
+    emulator.load_state(&state).unwrap();
+    assert_eq!(emulator.frame_buffer(), &at_save[..]);
+    let second_run = run_and_observe(&mut emulator, 3);
+    assert_eq!(
+        first_run, second_run,
+        "the continuation differs after loading"
+    );
+}
+
+#[test]
+fn invalid_states_are_rejected_and_keep_the_game_running() {
+    let mut emulator = Emulator::from_rom_bytes(&vram_scribbling_rom(), true).unwrap();
+    emulator.run_frame().unwrap();
+    let state = emulator.save_state();
+
+    let mut other_game = vram_scribbling_rom();
+    other_game[0x134..0x138].copy_from_slice(b"TEST");
+    let mut other = Emulator::from_rom_bytes(&other_game, true).unwrap();
+    other.run_frame().unwrap();
+    let before = run_and_observe(&mut other, 0);
+
+    let mut bad_magic = state.clone();
+    bad_magic[0] ^= 0xFF;
+    let mut bad_version = state.clone();
+    bad_version[8] = bad_version[8].wrapping_add(1);
+    for data in [
+        bad_magic,
+        bad_version,
+        state[..state.len() / 2].to_vec(),
+        state.clone(),
+    ] {
+        assert!(other.load_state(&data).is_err());
+    }
+    assert_eq!(run_and_observe(&mut other, 0), before);
+    assert_ne!(run_and_observe(&mut other, 1), before);
+}
+
+#[test]
+fn bus_hooks_see_only_the_games_own_accesses_in_order() {
+    let accesses = Rc::new(RefCell::new(Vec::new()));
+    let (reads, writes) = (accesses.clone(), accesses.clone());
+    let mut emulator = EmulatorBuilder::new().build_empty().unwrap();
+    emulator.memory_bus.set_hooks(BusHooks {
+        on_read: Some(Box::new(move |addr, value| {
+            reads.borrow_mut().push(('r', addr, value))
+        })),
+        on_write: Some(Box::new(move |addr, value| {
+            writes.borrow_mut().push(('w', addr, value))
+        })),
+    });
+
+    let mut rom = vec![0u8; 0x8000];
+    // LD A,(0xC000); LD (0xC001),A
+    rom[0x100..0x106].copy_from_slice(&[0xFA, 0x00, 0xC0, 0xEA, 0x01, 0xC0]);
+    emulator.load_rom(&rom).unwrap();
+    assert_eq!(*accesses.borrow(), [], "post-boot setup fired the hooks");
+
+    let value = emulator.memory_bus.peek_byte(0xC000);
+    emulator.step_instruction().unwrap();
+    emulator.step_instruction().unwrap();
+    assert_eq!(
+        *accesses.borrow(),
+        [
+            ('r', 0x0100, 0xFA),
+            ('r', 0x0101, 0x00),
+            ('r', 0x0102, 0xC0),
+            ('r', 0xC000, value),
+            ('r', 0x0103, 0xEA),
+            ('r', 0x0104, 0x01),
+            ('r', 0x0105, 0xC0),
+            ('w', 0xC001, value),
+        ]
+    );
+}
+
+const SB: u16 = 0xFF01;
+const SC: u16 = 0xFF02;
+const IF: u16 = 0xFF0F;
+
+#[test]
+fn serial_transfer_hands_the_byte_to_the_callback_and_completes() {
+    let mut rom = vec![0u8; 0x8000];
+    // LD A,'A'; LDH (SB),A; LD A,0x81; LDH (SC),A; loop: JR loop
+    rom[0x100..0x10A]
+        .copy_from_slice(&[0x3E, 0x41, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE]);
+    let mut emulator = Emulator::from_rom_bytes(&rom, true).unwrap();
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let callback_sent = sent.clone();
+    emulator
+        .memory_bus
+        .set_serial_callback(move |byte| callback_sent.borrow_mut().push(byte));
+    emulator.memory_bus.write_byte(IF, 0x00);
+
+    emulator.run_frame().unwrap();
+    assert_eq!(*sent.borrow(), [0x41]);
+    assert_eq!(emulator.take_serial_output(), "A");
+    assert_eq!(emulator.memory_bus.read_byte(SB), 0xFF); // Nothing on the other end
+    assert_eq!(emulator.memory_bus.read_byte(SC) & 0x80, 0);
+    assert_eq!(emulator.memory_bus.read_byte(IF) & 0x08, 0x08);
+}
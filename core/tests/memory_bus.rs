@@ -0,0 +1,337 @@
+use boba::emulator::Emulator;
+use boba::memory_bus::{BootRamInit, MemoryBus, NotUsableReads};
+
+/// A cartridge of `banks` 16 KB ROM banks, each starting with its bank number (low
+/// byte, then high byte), with the given header type and size codes.
+fn cartridge(cartridge_type: u8, rom_size_code: u8, ram_size_code: u8, banks: usize) -> Vec<u8> {
+    let mut rom = vec![0u8; banks * 0x4000];
+    for bank in 0..banks {
+        rom[bank * 0x4000] = bank as u8;
+        rom[bank * 0x4000 + 1] = (bank >> 8) as u8;
+    }
+    rom[0x147] = cartridge_type;
+    rom[0x148] = rom_size_code;
+    rom[0x149] = ram_size_code;
+    rom
+}
+
+/// A 32 KB ROM-only cartridge, flagged CGB-enhanced if `cgb`.
+fn plain_bus(cgb: bool) -> MemoryBus {
+    let mut rom = cartridge(0x00, 0x00, 0x00, 2);
+    rom[0x143] = if cgb { 0x80 } else { 0x00 };
+    bus_with(&rom)
+}
+
+fn bus_with(rom: &[u8]) -> MemoryBus {
+    let mut bus = MemoryBus::new();
+    bus.load_rom(rom).unwrap();
+    bus
+}
+
+/// The bank number tagged at the start of whichever bank is mapped at 0x4000.
+fn mapped_rom_bank(bus: &MemoryBus) -> usize {
+    bus.read_byte(0x4000) as usize | (bus.read_byte(0x4001) as usize) << 8
+}
+
+#[test]
+fn mbc2_ram_keeps_the_low_nibble_and_echoes_across_the_ram_area() {
+    let mut bus = bus_with(&cartridge(0x06, 0x03, 0x00, 16));
+    bus.write_byte(0x0000, 0x0A); // Address bit 8 clear: RAM enable
+    bus.write_byte(0xA000, 0xAB);
+    assert_eq!(bus.read_byte(0xA000), 0xFB);
+    assert_eq!(bus.read_byte(0xA200), 0xFB, "512-byte RAM repeats");
+    assert_eq!(bus.read_byte(0xBE00), 0xFB);
+}
+
+#[test]
+fn mbc2_control_writes_are_decoded_by_address_bit_8() {
+    let mut bus = bus_with(&cartridge(0x05, 0x03, 0x00, 16));
+    bus.write_byte(0x0100, 0x0A); // Bit 8 set: ROM bank, not RAM enable
+    assert_eq!(mapped_rom_bank(&bus), 0x0A);
+    bus.write_byte(0xA000, 0x05);
+    assert_eq!(bus.read_byte(0xA000), 0xFF, "RAM is still disabled");
+
+    bus.write_byte(0x2000, 0x0A); // Bit 8 clear, even in the upper half
+    assert_eq!(mapped_rom_bank(&bus), 0x0A);
+    bus.write_byte(0xA000, 0x05);
+    assert_eq!(bus.read_byte(0xA000), 0xF5);
+
+    bus.write_byte(0x3F00, 0x03); // Bit 8 set: bank 3
+    assert_eq!(mapped_rom_bank(&bus), 0x03);
+    bus.write_byte(0x3E00, 0x00); // Bit 8 clear: RAM disabled again
+    assert_eq!(bus.read_byte(0xA000), 0xFF);
+}
+
+#[test]
+fn mbc2_ram_reads_back_with_the_upper_nibble_set() {
+    let mut bus = bus_with(&cartridge(0x05, 0x01, 0x00, 4));
+    bus.write_byte(0x0000, 0x0A);
+    for (written, read) in [(0x0F, 0xFF), (0xFF, 0xFF), (0xF0, 0xF0), (0x3C, 0xFC)] {
+        bus.write_byte(0xA1FF, written);
+        assert_eq!(bus.read_byte(0xA1FF), read, "wrote {:02X}", written);
+    }
+}
+
+#[test]
+fn mbc5_selects_all_512_rom_banks_and_16_ram_banks() {
+    let mut bus = bus_with(&cartridge(0x1B, 0x08, 0x04, 512));
+    for bank in [0x000, 0x001, 0x0FF, 0x100, 0x1A5, 0x1FF] {
+        bus.write_byte(0x2000, bank as u8);
+        bus.write_byte(0x3000, (bank >> 8) as u8);
+        assert_eq!(mapped_rom_bank(&bus), bank, "bank {:03X}", bank);
+    }
+    // Changing the low byte keeps bit 8, and vice versa
+    bus.write_byte(0x2FFF, 0x42);
+    assert_eq!(mapped_rom_bank(&bus), 0x142);
+    bus.write_byte(0x3FFF, 0x00);
+    assert_eq!(mapped_rom_bank(&bus), 0x042);
+
+    bus.write_byte(0x0000, 0x0A);
+    for bank in 0..16 {
+        bus.write_byte(0x4000, bank);
+        bus.write_byte(0xA123, 0x80 | bank);
+    }
+    for bank in 0..16 {
+        bus.write_byte(0x4000, bank);
+        assert_eq!(bus.read_byte(0xA123), 0x80 | bank, "RAM bank {}", bank);
+    }
+}
+
+const IF: u16 = 0xFF0F;
+const LCDC: u16 = 0xFF40;
+const STAT: u16 = 0xFF41;
+const VBK: u16 = 0xFF4F;
+const BCPS: u16 = 0xFF68;
+const BCPD: u16 = 0xFF69;
+const OCPS: u16 = 0xFF6A;
+const OCPD: u16 = 0xFF6B;
+const SVBK: u16 = 0xFF70;
+const IE: u16 = 0xFFFF;
+
+#[test]
+fn unusable_region_reads_ff_and_ignores_writes() {
+    let mut bus = MemoryBus::new();
+    bus.write_byte(LCDC, 0x00);
+    bus.write_byte(0xFE9F, 0x12); // Last OAM byte
+    bus.write_byte(0xFF01, 0x34); // SB, the first I/O register that stores a plain byte
+    for addr in 0xFEA0..=0xFEFF {
+        bus.write_byte(addr, 0x55);
+        assert_eq!(bus.read_byte(addr), 0xFF, "{:04X}", addr);
+    }
+    assert_eq!(bus.read_byte(0xFE9F), 0x12);
+    assert_eq!(bus.read_byte(0xFF01), 0x34);
+}
+
+#[test]
+fn unusable_region_can_read_like_dmg_or_cgb_hardware() {
+    let mut bus = MemoryBus::new();
+    bus.write_byte(LCDC, 0x00);
+    bus.set_not_usable_reads(NotUsableReads::Dmg);
+    assert_eq!(bus.read_byte(0xFEA0), 0x00);
+    bus.set_not_usable_reads(NotUsableReads::Cgb);
+    assert_eq!(bus.read_byte(0xFEA0), 0xAA);
+    assert_eq!(bus.read_byte(0xFEB5), 0xBB);
+    assert_eq!(bus.read_byte(0xFEFF), 0xFF);
+
+    // Both read 0xFF while the PPU holds OAM
+    bus.write_byte(LCDC, 0x80);
+    bus.set_io_reg_direct(STAT, 0x82);
+    for mode in [NotUsableReads::Dmg, NotUsableReads::Cgb] {
+        bus.set_not_usable_reads(mode);
+        assert_eq!(bus.read_byte(0xFEB5), 0xFF, "{:?}", mode);
+    }
+}
+
+#[test]
+fn size_accessors_report_the_cartridge_rom_and_ram_bytes() {
+    let bus = bus_with(&cartridge(0x03, 0x01, 0x02, 4)); // MBC1+RAM+BATT, 4 banks, 8 KiB
+    assert_eq!(bus.total_rom_size(), 65536);
+    assert_eq!(bus.total_ram_size(), 8192);
+
+    let bus = bus_with(&cartridge(0x00, 0x00, 0x00, 2));
+    assert_eq!(bus.total_rom_size(), 32768);
+    assert_eq!(bus.total_ram_size(), 0);
+}
+
+#[test]
+fn ie_keeps_all_eight_bits_but_only_bits_0_to_4_request_service() {
+    let mut bus = MemoryBus::new();
+    bus.write_byte(IE, 0xFF);
+    assert_eq!(bus.read_byte(IE), 0xFF);
+    bus.write_byte(IE, 0xE0);
+    assert_eq!(bus.read_byte(IE), 0xE0);
+
+    // EI; NOP; NOP with every IE bit set but only IF's always-set upper bits
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100] = 0xFB;
+    let mut emulator = Emulator::from_rom_bytes(&rom, true).unwrap();
+    emulator.memory_bus.write_byte(IE, 0xFF);
+    emulator.memory_bus.write_byte(IF, 0x00);
+    for _ in 0..3 {
+        emulator.step_instruction().unwrap();
+    }
+    assert_eq!(emulator.cpu.pc(), 0x0103, "no interrupt was serviced");
+}
+
+#[test]
+fn if_upper_bits_always_read_as_one() {
+    let mut bus = MemoryBus::new();
+    for (written, read) in [(0x00, 0xE0), (0x04, 0xE4), (0x1F, 0xFF), (0xE0, 0xE0)] {
+        bus.write_byte(IF, written);
+        assert_eq!(bus.read_byte(IF), read, "wrote {:02X}", written);
+    }
+}
+
+#[test]
+fn svbk_reads_the_bank_with_the_upper_bits_set_only_in_cgb_mode() {
+    let mut bus = plain_bus(true);
+    bus.write_byte(SVBK, 0x03);
+    assert_eq!(bus.read_byte(SVBK), 0xFB);
+
+    let mut bus = plain_bus(false);
+    bus.write_byte(SVBK, 0x03);
+    assert_eq!(bus.read_byte(SVBK), 0xFF);
+}
+
+#[test]
+fn wram_banks_1_to_7_are_separate_and_bank_0_selects_bank_1() {
+    let mut bus = plain_bus(true);
+    for bank in 1..=7 {
+        bus.write_byte(SVBK, bank);
+        bus.write_byte(0xD123, 0x10 + bank);
+    }
+    bus.write_byte(0xC123, 0xC0);
+    for bank in 1..=7 {
+        bus.write_byte(SVBK, bank);
+        assert_eq!(bus.read_byte(0xD123), 0x10 + bank, "bank {}", bank);
+        assert_eq!(bus.read_byte(0xF123), 0x10 + bank, "echo of bank {}", bank);
+        assert_eq!(
+            bus.read_byte(0xC123),
+            0xC0,
+            "fixed bank under bank {}",
+            bank
+        );
+    }
+    bus.write_byte(SVBK, 0);
+    assert_eq!(bus.read_byte(0xD123), 0x11);
+
+    // DMG mode always maps bank 1
+    let mut bus = plain_bus(false);
+    bus.write_byte(0xD123, 0x11);
+    bus.write_byte(SVBK, 5);
+    assert_eq!(bus.read_byte(0xD123), 0x11);
+}
+
+#[test]
+fn vram_banks_0_and_1_are_independent() {
+    let mut bus = plain_bus(true);
+    bus.write_byte(LCDC, 0x00);
+    bus.write_byte(VBK, 0);
+    bus.write_byte(0x9800, 0x12);
+    bus.write_byte(VBK, 1);
+    assert_eq!(bus.read_byte(VBK), 0xFF);
+    bus.write_byte(0x9800, 0x34);
+    assert_eq!(bus.read_byte(0x9800), 0x34);
+    bus.write_byte(VBK, 0);
+    assert_eq!(bus.read_byte(VBK), 0xFE);
+    assert_eq!(bus.read_byte(0x9800), 0x12);
+    assert_eq!(
+        (bus.peek_vram(0, 0x9800), bus.peek_vram(1, 0x9800)),
+        (0x12, 0x34)
+    );
+}
+
+#[test]
+fn palette_data_writes_auto_increment_and_wrap_at_3f() {
+    let mut bus = plain_bus(true);
+    bus.write_byte(LCDC, 0x00);
+    bus.write_byte(BCPS, 0x80 | 0x3E);
+    for value in [0x11, 0x22, 0x33] {
+        bus.write_byte(BCPD, value);
+    }
+    assert_eq!(bus.read_byte(BCPS), 0xC1, "wrapped past 0x3F");
+    assert_eq!(bus.bg_palette_color(7, 3), 0x2211);
+
+    // Without auto-increment, reads and writes stay on one entry
+    for (index, value) in [(0x3E, 0x11), (0x3F, 0x22), (0x00, 0x33)] {
+        bus.write_byte(BCPS, index);
+        assert_eq!(bus.read_byte(BCPD), value, "entry {:02X}", index);
+        assert_eq!(bus.read_byte(BCPD), value);
+    }
+
+    bus.write_byte(OCPS, 0x80 | 0x3F);
+    bus.write_byte(OCPD, 0x44);
+    bus.write_byte(OCPD, 0x55);
+    assert_eq!(bus.read_byte(OCPS), 0xC1);
+    bus.write_byte(OCPS, 0x00);
+    assert_eq!(bus.read_byte(OCPD), 0x55);
+}
+
+#[test]
+fn boot_ram_init_fills_wram_vram_oam_and_hram() {
+    let areas = [
+        0x8000..0xA000,
+        0xC000..0xE000,
+        0xFE00..0xFEA0,
+        0xFF80..0xFFFF,
+    ];
+    let mut bus = MemoryBus::new();
+    bus.set_boot_ram_init(BootRamInit::HardwarePattern);
+    for addr in areas.iter().cloned().flatten() {
+        // Every area starts 16-byte aligned: 8 bytes of 0x00, then 8 of 0xFF
+        let expected = if (addr / 8) % 2 == 0 { 0x00 } else { 0xFF };
+        assert_eq!(bus.peek_byte(addr), expected, "{:04X}", addr);
+    }
+
+    bus.set_boot_ram_init(BootRamInit::Zero);
+    for addr in areas.iter().cloned().flatten() {
+        assert_eq!(bus.peek_byte(addr), 0x00, "{:04X}", addr);
+    }
+}
+
+#[test]
+fn stat_mode_blocks_vram_in_mode_3_and_oam_in_modes_2_and_3() {
+    let mut bus = MemoryBus::new();
+    bus.write_byte(LCDC, 0x00);
+    bus.write_byte(0x8000, 0x11);
+    bus.write_byte(0xFE00, 0x22);
+    bus.write_byte(LCDC, 0x80);
+
+    for mode in 0..4u8 {
+        bus.set_io_reg_direct(STAT, 0x80 | mode);
+        let vram_blocked = mode == 3;
+        let oam_blocked = mode >= 2;
+        let vram = if vram_blocked { 0xFF } else { 0x11 };
+        let oam = if oam_blocked { 0xFF } else { 0x22 };
+        assert_eq!(bus.read_byte(0x8000), vram, "VRAM read in mode {}", mode);
+        assert_eq!(bus.read_byte(0xFE00), oam, "OAM read in mode {}", mode);
+
+        // Blocked writes are dropped
+        bus.write_byte(0x8000, 0x33);
+        bus.write_byte(0xFE00, 0x44);
+        let vram_after = if vram_blocked { 0x11 } else { 0x33 };
+        let oam_after = if oam_blocked { 0x22 } else { 0x44 };
+        assert_eq!(
+            bus.peek_byte(0x8000),
+            vram_after,
+            "VRAM write in mode {}",
+            mode
+        );
+        assert_eq!(
+            bus.peek_byte(0xFE00),
+            oam_after,
+            "OAM write in mode {}",
+            mode
+        );
+        bus.write_byte(LCDC, 0x00);
+        bus.write_byte(0x8000, 0x11);
+        bus.write_byte(0xFE00, 0x22);
+        bus.write_byte(LCDC, 0x80);
+    }
+
+    // With the LCD off nothing is blocked, whatever STAT says
+    bus.set_io_reg_direct(STAT, 0x83);
+    bus.write_byte(LCDC, 0x00);
+    assert_eq!(bus.read_byte(0x8000), 0x11);
+    assert_eq!(bus.read_byte(0xFE00), 0x22);
+}
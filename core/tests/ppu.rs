@@ -0,0 +1,632 @@
+use boba::emulator::Emulator;
+use boba::palette::{DEFAULT_PALETTE, Rgb, parse_palette, rgb555_to_rgb};
+use boba::ppu::{GB_HEIGHT, GB_WIDTH};
+
+const LCDC: u16 = 0xFF40;
+const STAT: u16 = 0xFF41;
+const SCX: u16 = 0xFF43;
+const LYC: u16 = 0xFF45;
+const IF: u16 = 0xFF0F;
+const BGP: u16 = 0xFF47;
+const OBP0: u16 = 0xFF48;
+const WY: u16 = 0xFF4A;
+const WX: u16 = 0xFF4B;
+const OAM: u16 = 0xFE00;
+
+/// LCDC bits on top of LCD enable: BG on, tile data at 0x8000.
+const LCDC_BG: u8 = 0x91;
+const LCDC_OBJ_ENABLE: u8 = 0x02;
+const LCDC_OBJ_8X16: u8 = 0x04;
+const LCDC_WINDOW: u8 = 0x20;
+
+const STAT_MODE_0_IE: u8 = 0x08;
+const STAT_LYC_IE: u8 = 0x40;
+const STAT_INTERRUPT: u8 = 0x02;
+
+/// An emulator with the LCD off, so VRAM, OAM and the PPU registers can be set up
+/// freely before `lcd_on` restarts the PPU at line 0.
+fn emulator_with_lcd_off() -> Emulator {
+    let mut emulator = Emulator::from_rom_bytes(&vec![0u8; 0x8000], true).unwrap();
+    emulator.memory_bus.write_byte(LCDC, 0x00);
+    step(&mut emulator, 4);
+    emulator.memory_bus.write_byte(BGP, 0xE4); // Identity palettes: shade = color index
+    emulator.memory_bus.write_byte(OBP0, 0xE4);
+    emulator
+}
+
+fn lcd_on(emulator: &mut Emulator, lcdc: u8) {
+    emulator.memory_bus.write_byte(LCDC, lcdc);
+}
+
+/// Steps the PPU by `dots`. Returns `true` if it entered VBlank.
+fn step(emulator: &mut Emulator, dots: u32) -> bool {
+    let Emulator {
+        ppu, memory_bus, ..
+    } = emulator;
+    ppu.step(dots, memory_bus)
+}
+
+/// Runs the PPU until it enters VBlank, i.e. the frame buffer holds a whole frame.
+fn run_frame(emulator: &mut Emulator) {
+    while !step(emulator, 4) {}
+}
+
+/// Writes an 8x8 tile whose pixels all have the given color index.
+fn fill_tile(emulator: &mut Emulator, tile: u8, color_index: u8) {
+    let (lo, hi) = (
+        if color_index & 1 != 0 { 0xFF } else { 0x00 },
+        if color_index & 2 != 0 { 0xFF } else { 0x00 },
+    );
+    let base = 0x8000 + tile as u16 * 16;
+    for row in 0..8 {
+        emulator.memory_bus.write_byte(base + row * 2, lo);
+        emulator.memory_bus.write_byte(base + row * 2 + 1, hi);
+    }
+}
+
+/// Places OAM entry `index` with its top-left corner at screen (`x`, `y`).
+fn place_sprite(emulator: &mut Emulator, index: u16, x: u8, y: u8, tile: u8) {
+    let entry = OAM + index * 4;
+    emulator.memory_bus.write_byte(entry, y + 16);
+    emulator.memory_bus.write_byte(entry + 1, x + 8);
+    emulator.memory_bus.write_byte(entry + 2, tile);
+    emulator.memory_bus.write_byte(entry + 3, 0x00);
+}
+
+/// Whether a STAT interrupt was requested since the last call; clears the request.
+fn take_stat_interrupt(emulator: &mut Emulator) -> bool {
+    let if_reg = emulator.memory_bus.read_byte(IF);
+    emulator.memory_bus.write_byte(IF, if_reg & !STAT_INTERRUPT);
+    if_reg & STAT_INTERRUPT != 0
+}
+
+fn pixel(emulator: &Emulator, x: usize, y: usize) -> u8 {
+    emulator.frame_buffer()[y * GB_WIDTH + x]
+}
+
+#[test]
+fn every_scanline_is_drawn_each_frame() {
+    let mut emulator = emulator_with_lcd_off();
+    lcd_on(&mut emulator, LCDC_BG);
+    // Line 0 right after turning the LCD on starts in HBlank and isn't drawn, so
+    // begin from the first full frame
+    run_frame(&mut emulator);
+
+    for color in [1, 2, 3] {
+        fill_tile(&mut emulator, 0x00, color);
+        run_frame(&mut emulator);
+        for y in 0..GB_HEIGHT {
+            let line = &emulator.frame_buffer()[y * GB_WIDTH..(y + 1) * GB_WIDTH];
+            assert!(line.iter().all(|&shade| shade == color), "line {}", y);
+        }
+    }
+}
+
+#[test]
+fn tall_sprite_draws_the_even_tile_above_the_odd_one() {
+    let mut emulator = emulator_with_lcd_off();
+    fill_tile(&mut emulator, 0x04, 1);
+    fill_tile(&mut emulator, 0x05, 2);
+    // Tile 0x05 names the 0x04/0x05 pair: bit 0 of the tile index is ignored
+    place_sprite(&mut emulator, 0, 20, 10, 0x05);
+    lcd_on(&mut emulator, LCDC_BG | LCDC_OBJ_ENABLE | LCDC_OBJ_8X16);
+    run_frame(&mut emulator);
+
+    for x in 20..28 {
+        assert_eq!(pixel(&emulator, x, 9), 0, "above the sprite, x={}", x);
+        for y in 10..18 {
+            assert_eq!(pixel(&emulator, x, y), 1, "top half ({}, {})", x, y);
+        }
+        for y in 18..26 {
+            assert_eq!(pixel(&emulator, x, y), 2, "bottom half ({}, {})", x, y);
+        }
+        assert_eq!(pixel(&emulator, x, 26), 0, "below the sprite, x={}", x);
+    }
+}
+
+#[test]
+fn only_the_first_10_sprites_in_oam_order_are_drawn_on_a_line() {
+    let mut emulator = emulator_with_lcd_off();
+    fill_tile(&mut emulator, 0x01, 3);
+    for index in 0..10 {
+        place_sprite(&mut emulator, index, 80 + 8 * index as u8, 16, 0x01);
+    }
+    // Further left, so they'd win on X priority if they were among the ten
+    place_sprite(&mut emulator, 10, 0, 16, 0x01);
+    place_sprite(&mut emulator, 11, 8, 16, 0x01);
+    lcd_on(&mut emulator, LCDC_BG | LCDC_OBJ_ENABLE);
+    run_frame(&mut emulator);
+
+    for y in 16..24 {
+        for index in 0..10 {
+            let x = 80 + 8 * index;
+            assert_eq!(pixel(&emulator, x, y), 3, "sprite {} on line {}", index, y);
+        }
+        assert_eq!(pixel(&emulator, 0, y), 0, "sprite 10 on line {}", y);
+        assert_eq!(pixel(&emulator, 8, y), 0, "sprite 11 on line {}", y);
+    }
+}
+
+#[test]
+fn sprites_past_the_tenth_stay_hidden_behind_transparent_ones() {
+    for transparent in [9, 10] {
+        let mut emulator = emulator_with_lcd_off();
+        fill_tile(&mut emulator, 0x01, 3);
+        fill_tile(&mut emulator, 0x02, 0);
+        // All stacked at one spot: the transparent ones first in OAM, then opaque ones
+        for index in 0..12 {
+            let tile = if index < transparent { 0x02 } else { 0x01 };
+            place_sprite(&mut emulator, index, 40, 16, tile);
+        }
+        lcd_on(&mut emulator, LCDC_BG | LCDC_OBJ_ENABLE);
+        run_frame(&mut emulator);
+
+        // With ten transparent sprites the 11th and 12th aren't drawn at all
+        let expected = if transparent == 10 { 0 } else { 3 };
+        assert_eq!(
+            pixel(&emulator, 40, 20),
+            expected,
+            "{} transparent",
+            transparent
+        );
+    }
+}
+
+#[test]
+fn lyc_stat_interrupt_fires_on_the_first_step_of_its_line() {
+    let mut emulator = emulator_with_lcd_off();
+    emulator.memory_bus.write_byte(LYC, 72);
+    emulator.memory_bus.write_byte(STAT, STAT_LYC_IE);
+    lcd_on(&mut emulator, LCDC_BG);
+    step(&mut emulator, 4);
+    take_stat_interrupt(&mut emulator);
+
+    while emulator.ppu.scanline() < 72 {
+        assert!(
+            !take_stat_interrupt(&mut emulator),
+            "early on line {}",
+            emulator.ppu.scanline()
+        );
+        step(&mut emulator, 4);
+    }
+    // Raised by the same step that moved LY to 72
+    assert!(take_stat_interrupt(&mut emulator));
+    assert_eq!(emulator.ppu.mode(), 2);
+
+    // And only once for the whole line
+    while emulator.ppu.scanline() == 72 {
+        step(&mut emulator, 4);
+        assert!(!take_stat_interrupt(&mut emulator));
+    }
+}
+
+#[test]
+fn stat_blocking_merges_hblank_and_lyc_into_one_interrupt() {
+    let mut emulator = emulator_with_lcd_off();
+    emulator.memory_bus.write_byte(LYC, 72);
+    emulator
+        .memory_bus
+        .write_byte(STAT, STAT_MODE_0_IE | STAT_LYC_IE);
+    lcd_on(&mut emulator, LCDC_BG);
+    while emulator.ppu.scanline() < 71 {
+        step(&mut emulator, 4);
+    }
+    take_stat_interrupt(&mut emulator);
+
+    // Line 71's HBlank raises the line; LY=LYC takes over from it as line 72 starts,
+    // so the line stays high through 72's HBlank. It only drops in line 73's Mode 2.
+    let mut interrupts = Vec::new();
+    while emulator.ppu.scanline() < 74 {
+        step(&mut emulator, 4);
+        if take_stat_interrupt(&mut emulator) {
+            interrupts.push((emulator.ppu.scanline(), emulator.ppu.mode()));
+        }
+    }
+    assert_eq!(interrupts, [(71, 0), (73, 0)]);
+}
+
+/// Runs the PPU a dot at a time to `line`'s HBlank and returns how long its Mode 3 was.
+fn mode3_dots_on_line(emulator: &mut Emulator, line: u8) -> u32 {
+    while emulator.ppu.scanline() != line || emulator.ppu.mode() != 0 {
+        step(emulator, 1);
+    }
+    emulator.ppu.mode3_dots()
+}
+
+/// Shortest Mode 3: 160 pixels plus the first tile fetch, which is thrown away.
+const MODE3_MIN_DOTS: u32 = 172;
+
+/// Shows the window from WX=87 on lines 5 and below. Line 0 after the LCD is turned
+/// on has no OAM scan to compare WY in, so the window can't start there.
+fn show_window(emulator: &mut Emulator) {
+    emulator.memory_bus.write_byte(WY, 5);
+    emulator.memory_bus.write_byte(WX, 87);
+}
+
+#[test]
+fn plain_line_has_the_shortest_mode_3() {
+    let mut emulator = emulator_with_lcd_off();
+    lcd_on(&mut emulator, LCDC_BG | LCDC_OBJ_ENABLE);
+    assert_eq!(mode3_dots_on_line(&mut emulator, 10), MODE3_MIN_DOTS);
+}
+
+#[test]
+fn fine_scroll_lengthens_mode_3_by_the_discarded_pixels() {
+    let mut emulator = emulator_with_lcd_off();
+    emulator.memory_bus.write_byte(SCX, 3);
+    lcd_on(&mut emulator, LCDC_BG);
+    assert_eq!(mode3_dots_on_line(&mut emulator, 10), MODE3_MIN_DOTS + 3);
+}
+
+#[test]
+fn window_lengthens_mode_3_by_a_tile_fetch() {
+    let mut emulator = emulator_with_lcd_off();
+    show_window(&mut emulator);
+    lcd_on(&mut emulator, LCDC_BG | LCDC_WINDOW);
+    assert_eq!(mode3_dots_on_line(&mut emulator, 4), MODE3_MIN_DOTS);
+    assert_eq!(mode3_dots_on_line(&mut emulator, 10), MODE3_MIN_DOTS + 6);
+}
+
+#[test]
+fn each_sprite_on_the_line_lengthens_mode_3() {
+    let mut previous = MODE3_MIN_DOTS;
+    for count in 1..=3 {
+        let mut emulator = emulator_with_lcd_off();
+        for index in 0..count {
+            place_sprite(&mut emulator, index, 40 + 20 * index as u8, 5, 0x00);
+        }
+        lcd_on(&mut emulator, LCDC_BG | LCDC_OBJ_ENABLE);
+        let dots = mode3_dots_on_line(&mut emulator, 10);
+        // Each sprite fetch stalls the pipeline for 6 to 11 dots
+        assert!(
+            (previous + 6..=previous + 11).contains(&dots),
+            "{} sprites: {} dots after {}",
+            count,
+            dots,
+            previous
+        );
+        previous = dots;
+    }
+}
+
+#[test]
+fn longer_mode_3_shortens_hblank_and_keeps_lines_456_dots() {
+    let mut emulator = emulator_with_lcd_off();
+    emulator.memory_bus.write_byte(SCX, 5);
+    show_window(&mut emulator);
+    for index in 0..10 {
+        place_sprite(&mut emulator, index, 8 * index as u8, 8, 0x00);
+    }
+    lcd_on(&mut emulator, LCDC_BG | LCDC_OBJ_ENABLE | LCDC_WINDOW);
+    while emulator.ppu.scanline() != 1 {
+        step(&mut emulator, 1);
+    }
+
+    // Lines 1-4 have only the fine scroll, 5-7 the window too, 8-15 the sprites as well
+    let mut mode3_lengths = Vec::new();
+    for line in 1..20u8 {
+        let mut dots = 0;
+        while emulator.ppu.scanline() == line {
+            step(&mut emulator, 1);
+            dots += 1;
+        }
+        assert_eq!(dots, 456, "line {}", line);
+        mode3_lengths.push(emulator.ppu.mode3_dots());
+    }
+    assert_eq!(mode3_lengths[0], MODE3_MIN_DOTS + 5);
+    assert!(mode3_lengths[4] > mode3_lengths[0]);
+    assert!(mode3_lengths[7] > mode3_lengths[4]);
+}
+
+/// Runs the PPU a dot at a time until it's in `mode` on `line`.
+fn run_to_mode(emulator: &mut Emulator, line: u8, mode: u8) {
+    while emulator.ppu.scanline() != line || emulator.ppu.mode() != mode {
+        step(emulator, 1);
+    }
+}
+
+#[test]
+fn cpu_reads_vram_as_ff_during_mode_3() {
+    let mut emulator = emulator_with_lcd_off();
+    emulator.memory_bus.write_byte(0x8010, 0x5A);
+    assert_eq!(emulator.memory_bus.read_byte(0x8010), 0x5A, "LCD off");
+    lcd_on(&mut emulator, LCDC_BG);
+
+    for mode in [2, 3, 0] {
+        run_to_mode(&mut emulator, 10, mode);
+        let expected = if mode == 3 { 0xFF } else { 0x5A };
+        assert_eq!(
+            emulator.memory_bus.read_byte(0x8010),
+            expected,
+            "mode {}",
+            mode
+        );
+    }
+    assert_eq!(
+        emulator.memory_bus.peek_byte(0x8010),
+        0x5A,
+        "the PPU's own reads"
+    );
+}
+
+/// Fills the window map (0x9C00) so each window line shows the window line counter
+/// as 8 pixels of color 0/1, most significant bit first. Tile `n` holds window lines
+/// 8n-8n+7, and every tile in map row `n` is tile `n`.
+fn window_shows_its_line_counter(emulator: &mut Emulator) {
+    for row in 0..18u16 {
+        for y in 0..8u16 {
+            let line = (row * 8 + y) as u8;
+            emulator
+                .memory_bus
+                .write_byte(0x8000 + row * 16 + y * 2, line);
+            emulator
+                .memory_bus
+                .write_byte(0x8000 + row * 16 + y * 2 + 1, 0x00);
+        }
+        for column in 0..32 {
+            emulator
+                .memory_bus
+                .write_byte(0x9C00 + row * 32 + column, row as u8);
+        }
+    }
+    emulator.memory_bus.write_byte(WX, 7);
+}
+
+/// The window line counter value drawn on screen line `y` by `window_shows_its_line_counter`.
+fn window_line_drawn(emulator: &Emulator, y: usize) -> u8 {
+    (0..8).fold(0, |value, x| value << 1 | (pixel(emulator, x, y) & 1))
+}
+
+const LCDC_WINDOW_MAP_9C00: u8 = 0x40;
+const LCDC_TILES_8000: u8 = 0x10;
+
+#[test]
+fn window_line_counter_pauses_while_the_window_is_off() {
+    let mut emulator = emulator_with_lcd_off();
+    window_shows_its_line_counter(&mut emulator);
+    emulator.memory_bus.write_byte(WY, 30);
+    let lcdc = LCDC_BG | LCDC_TILES_8000 | LCDC_WINDOW_MAP_9C00;
+    lcd_on(&mut emulator, lcdc | LCDC_WINDOW);
+
+    run_to_mode(&mut emulator, 51, 2);
+    lcd_on(&mut emulator, lcdc);
+    run_to_mode(&mut emulator, 71, 2);
+    lcd_on(&mut emulator, lcdc | LCDC_WINDOW);
+    run_frame(&mut emulator);
+
+    for y in 30..=50 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y - 30) as u8,
+            "line {}",
+            y
+        );
+    }
+    for y in 51..=70 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y % 8) as u8,
+            "background on line {}",
+            y
+        );
+    }
+    // Lines 30-50 drew window lines 0-20, so line 71 carries on with 21
+    for y in 71..144 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y - 50) as u8,
+            "line {}",
+            y
+        );
+    }
+}
+
+#[test]
+fn moving_wy_mid_frame_takes_effect_on_the_next_frame() {
+    let mut emulator = emulator_with_lcd_off();
+    window_shows_its_line_counter(&mut emulator);
+    emulator.memory_bus.write_byte(WY, 40);
+    lcd_on(
+        &mut emulator,
+        LCDC_BG | LCDC_TILES_8000 | LCDC_WINDOW_MAP_9C00 | LCDC_WINDOW,
+    );
+
+    // Once LY has matched WY the window stays on for the frame, counting on
+    run_to_mode(&mut emulator, 60, 2);
+    emulator.memory_bus.write_byte(WY, 100);
+    run_frame(&mut emulator);
+    for y in 0..40 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y % 8) as u8,
+            "frame 1, line {}",
+            y
+        );
+    }
+    for y in 40..144 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y - 40) as u8,
+            "frame 1, line {}",
+            y
+        );
+    }
+
+    // The next frame starts the window, and its line counter, at the new WY
+    run_to_mode(&mut emulator, 0, 2);
+    run_frame(&mut emulator);
+    for y in 0..100 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y % 8) as u8,
+            "frame 2, line {}",
+            y
+        );
+    }
+    for y in 100..144 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y - 100) as u8,
+            "frame 2, line {}",
+            y
+        );
+    }
+}
+
+#[test]
+fn window_pushed_off_screen_by_wx_does_not_advance_its_line_counter() {
+    let mut emulator = emulator_with_lcd_off();
+    window_shows_its_line_counter(&mut emulator);
+    emulator.memory_bus.write_byte(WY, 20);
+    lcd_on(
+        &mut emulator,
+        LCDC_BG | LCDC_TILES_8000 | LCDC_WINDOW_MAP_9C00 | LCDC_WINDOW,
+    );
+
+    run_to_mode(&mut emulator, 50, 2);
+    emulator.memory_bus.write_byte(WX, 200);
+    run_to_mode(&mut emulator, 60, 2);
+    emulator.memory_bus.write_byte(WX, 7);
+    run_frame(&mut emulator);
+
+    for y in 20..50 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y - 20) as u8,
+            "line {}",
+            y
+        );
+    }
+    for y in 50..60 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y % 8) as u8,
+            "background on line {}",
+            y
+        );
+    }
+    for y in 60..144 {
+        assert_eq!(
+            window_line_drawn(&emulator, y),
+            (y - 30) as u8,
+            "line {}",
+            y
+        );
+    }
+}
+
+#[test]
+fn oam_dma_leaves_io_hram_and_ie_reachable_and_restarts_when_rewritten() {
+    const DMA: u16 = 0xFF46;
+    const SCY: u16 = 0xFF42;
+    const IE: u16 = 0xFFFF;
+    let mut emulator = emulator_with_lcd_off();
+    let bus = &mut emulator.memory_bus;
+    for i in 0..160 {
+        bus.write_byte(0xC000 + i, i as u8);
+        bus.write_byte(0xC100 + i, 0x80 | i as u8);
+    }
+
+    bus.write_byte(DMA, 0xC0);
+    assert!(bus.dma_active());
+    assert_eq!(bus.read_byte(0xC000), 0xFF, "WRAM is on the DMA's bus");
+    bus.write_byte(0xFF80, 0x12);
+    bus.write_byte(SCY, 0x34);
+    bus.write_byte(IE, 0x05);
+    assert_eq!(bus.read_byte(0xFF80), 0x12);
+    assert_eq!(bus.read_byte(SCY), 0x34);
+    assert_eq!(bus.read_byte(IE), 0x05);
+
+    // 40 bytes in, a new source restarts the copy from its first byte
+    bus.tick_dma(40 * 4);
+    assert_eq!(bus.peek_byte(OAM + 39), 39);
+    bus.write_byte(DMA, 0xC1);
+    bus.tick_dma(159 * 4);
+    assert!(bus.dma_active());
+    bus.tick_dma(4);
+    assert!(!bus.dma_active());
+    for i in 0..160 {
+        assert_eq!(bus.peek_byte(OAM + i), 0x80 | i as u8, "OAM byte {}", i);
+    }
+}
+
+#[test]
+fn oam_writes_during_dma_are_dropped_and_the_copy_lands() {
+    const DMA: u16 = 0xFF46;
+    let mut emulator = emulator_with_lcd_off();
+    let bus = &mut emulator.memory_bus;
+    for i in 0..160 {
+        bus.write_byte(0xC000 + i, i as u8);
+    }
+
+    bus.write_byte(DMA, 0xC0);
+    bus.write_byte(OAM + 100, 0xAA);
+    bus.tick_dma(4);
+    bus.write_byte(OAM, 0xAA); // After the DMA copied byte 0
+    bus.write_byte(0xFF80, 0x12);
+    assert_eq!(bus.read_byte(0xFF80), 0x12, "HRAM stays reachable");
+    bus.tick_dma(159 * 4);
+    assert!(!bus.dma_active());
+    assert_eq!(bus.read_byte(OAM), 0x00);
+    assert_eq!(bus.read_byte(OAM + 100), 100);
+}
+
+#[test]
+fn oam_dma_takes_160_m_cycles_and_hides_everything_but_hram() {
+    const DMA: u16 = 0xFF46;
+    let mut emulator = emulator_with_lcd_off();
+    let bus = &mut emulator.memory_bus;
+    bus.write_byte(0x8000, 0x11);
+    bus.write_byte(0xC000, 0x22);
+    bus.write_byte(0xFF80, 0x33);
+
+    bus.write_byte(DMA, 0xC0);
+    for _ in 0..159 {
+        bus.tick_dma(4);
+        for addr in [0x0100, 0x4000, 0x8000, 0xA000, 0xC000, 0xE000, OAM] {
+            assert_eq!(bus.read_byte(addr), 0xFF, "{:04X} during DMA", addr);
+        }
+        assert_eq!(bus.read_byte(0xFF80), 0x33);
+    }
+    bus.tick_dma(3);
+    assert!(bus.dma_active(), "done before 160 M-cycles");
+    bus.tick_dma(1);
+    assert!(!bus.dma_active(), "still running after 160 M-cycles");
+    assert_eq!(bus.read_byte(0x8000), 0x11);
+    assert_eq!(bus.read_byte(0xC000), 0x22);
+}
+
+#[test]
+fn render_into_shows_shades_in_the_palette_colors() {
+    let palette = parse_palette("; dark first\n#000000\n555555\nAAAAAA\nFFFFFF\n").unwrap();
+    let mut emulator = emulator_with_lcd_off();
+    emulator.set_palette(palette);
+    fill_tile(&mut emulator, 0x01, 3);
+    // Line 0 isn't drawn in the first frame after the LCD is turned on: use the second row
+    emulator.memory_bus.write_byte(0x9820, 0x01);
+    lcd_on(&mut emulator, LCDC_BG);
+    run_frame(&mut emulator);
+
+    let mut rgba = vec![0u8; GB_WIDTH * GB_HEIGHT * 4];
+    emulator.render_into(&mut rgba);
+    let rgba_at = |x: usize, y: usize| &rgba[(y * GB_WIDTH + x) * 4..][..4];
+    assert_eq!(rgba_at(0, 8), [0xFF, 0xFF, 0xFF, 0xFF]); // Shade 3
+    assert_eq!(rgba_at(7, 15), [0xFF, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(rgba_at(8, 8), [0x00, 0x00, 0x00, 0xFF]); // Shade 0
+    assert_eq!(rgba_at(159, 143), [0x00, 0x00, 0x00, 0xFF]);
+}
+
+#[test]
+fn palette_files_need_exactly_four_hex_colors() {
+    assert_eq!(
+        parse_palette("9BBC0F\n8BAC0F\n306230\n0F380F").unwrap(),
+        DEFAULT_PALETTE
+    );
+    assert_eq!(
+        parse_palette("#FF0000\n").unwrap_err(),
+        "Palette needs exactly 4 colors, found 1"
+    );
+    assert!(parse_palette("FF0000\n00FF00\n0000FF\nnot hex\n").is_err());
+    assert_eq!(rgb555_to_rgb(0x7FFF), Rgb::new(0xFF, 0xFF, 0xFF));
+    assert_eq!(rgb555_to_rgb(0x001F), Rgb::new(0xFF, 0x00, 0x00));
+}
@@ -0,0 +1,171 @@
+use boba::emulator::{Emulator, EmulatorBuilder};
+use boba::rom_file::{extract_rom, is_rom_path, read_rom};
+use flate2::Compression;
+use flate2::Crc;
+use flate2::write::DeflateEncoder;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// A 32 KB ROM-only cartridge that counts up in A forever (INC A; JR -3 at 0x0100).
+fn counting_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0x3C, 0x18, 0xFD]);
+    rom
+}
+
+/// Builds a zip archive holding `files` as (name, contents, deflate?) entries.
+fn zip_archive(files: &[(&str, &[u8], bool)]) -> Vec<u8> {
+    let mut archive = Vec::new();
+    let mut central_directory = Vec::new();
+    for &(name, contents, deflate) in files {
+        let (method, data) = if deflate {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(contents).unwrap();
+            (8u16, encoder.finish().unwrap())
+        } else {
+            (0u16, contents.to_vec())
+        };
+        let mut crc = Crc::new();
+        crc.update(contents);
+        let local_header_offset = archive.len() as u32;
+
+        // Local header with zeroed CRC and sizes, as streaming zippers write them
+        archive.extend_from_slice(&0x0403_4B50u32.to_le_bytes());
+        archive.extend_from_slice(&[20, 0, 0x08, 0]); // Version, flags (data descriptor)
+        archive.extend_from_slice(&method.to_le_bytes());
+        archive.extend_from_slice(&[0; 16]); // Time, date, CRC, sizes
+        archive.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        archive.extend_from_slice(&[0, 0]);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&data);
+
+        central_directory.extend_from_slice(&0x0201_4B50u32.to_le_bytes());
+        central_directory.extend_from_slice(&[20, 0, 20, 0, 0x08, 0]);
+        central_directory.extend_from_slice(&method.to_le_bytes());
+        central_directory.extend_from_slice(&[0; 4]); // Time, date
+        central_directory.extend_from_slice(&crc.sum().to_le_bytes());
+        central_directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        central_directory.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        central_directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        central_directory.extend_from_slice(&[0; 12]); // Extra, comment, disk, attributes
+        central_directory.extend_from_slice(&local_header_offset.to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+    }
+
+    let central_directory_offset = archive.len() as u32;
+    archive.extend_from_slice(&central_directory);
+    archive.extend_from_slice(&0x0605_4B50u32.to_le_bytes());
+    archive.extend_from_slice(&[0; 4]); // Disk numbers
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&central_directory_offset.to_le_bytes());
+    archive.extend_from_slice(&[0, 0]); // Comment length
+    archive
+}
+
+#[test]
+fn gb_gbc_and_zip_files_are_accepted_as_roms() {
+    for name in ["game.gb", "game.gbc", "GAME.GBC", "game.zip", "Game.ZIP"] {
+        assert!(is_rom_path(Path::new(name)), "{}", name);
+    }
+    for name in ["game.sav", "game.gb.txt", "game", "gb"] {
+        assert!(!is_rom_path(Path::new(name)), "{}", name);
+    }
+}
+
+#[test]
+fn zipped_rom_is_the_first_gb_file_in_the_archive() {
+    let rom = counting_rom();
+    for deflate in [false, true] {
+        let archive = zip_archive(&[
+            ("readme.txt", b"Not a ROM", deflate),
+            ("roms/game.gb", &rom, deflate),
+            ("other.gbc", b"Second ROM", deflate),
+        ]);
+        assert_eq!(extract_rom(&archive).unwrap(), rom, "deflate: {}", deflate);
+    }
+
+    let no_rom = zip_archive(&[("readme.txt", b"Not a ROM", true)]);
+    assert!(extract_rom(&no_rom).is_err());
+    assert!(extract_rom(&rom).is_err(), "a plain ROM is not a zip");
+    let mut corrupt = zip_archive(&[("game.gb", &rom, false)]);
+    corrupt[0x40] ^= 0xFF;
+    assert!(extract_rom(&corrupt).is_err(), "CRC mismatch");
+}
+
+#[test]
+fn dropped_zip_loads_like_the_rom_inside() {
+    let path = std::env::temp_dir().join(format!("boba_drop_{}.zip", std::process::id()));
+    fs::write(&path, zip_archive(&[("game.gb", &counting_rom(), true)])).unwrap();
+    let rom = read_rom(&path);
+    fs::remove_file(&path).unwrap();
+
+    let mut emulator = Emulator::from_rom_bytes(&rom.unwrap(), true).unwrap();
+    emulator.step_instruction().unwrap();
+    assert_eq!(emulator.cpu.snapshot().a, 0x02);
+}
+
+#[test]
+fn bad_rom_drop_is_an_error_and_the_game_keeps_running() {
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    for _ in 0..10 {
+        emulator.step_instruction().unwrap();
+    }
+
+    let with_header = |offset: usize, value: u8| {
+        let mut rom = counting_rom();
+        rom[offset] = value;
+        rom
+    };
+    let bad_roms = [
+        ("no header", vec![0u8; 0x100]),
+        ("under 16 KB", vec![0u8; 0x3FFF]),
+        ("unknown cartridge type", with_header(0x0147, 0xEE)),
+        ("unknown ROM size", with_header(0x0148, 0x20)),
+        ("unknown RAM size", with_header(0x0149, 0x07)),
+    ];
+    for (what, rom) in bad_roms {
+        let before = emulator.cpu.snapshot();
+        assert!(emulator.load_rom(&rom).is_err(), "{}", what);
+
+        // Still the same game, right where it was
+        assert_eq!(emulator.cpu.snapshot(), before, "{}", what);
+        assert_eq!(emulator.memory_bus.read_byte(0x0100), 0x3C, "{}", what);
+        emulator.step_instruction().unwrap();
+        emulator.step_instruction().unwrap();
+        assert_eq!(
+            emulator.cpu.snapshot().a,
+            before.a.wrapping_add(1),
+            "{}",
+            what
+        );
+    }
+}
+
+#[test]
+fn file_size_policy_wraps_bank_selection_to_the_banks_present() {
+    // MBC1 header claiming 16 banks, but only 6 made it into the file
+    let mut rom = vec![0u8; 6 * 0x4000];
+    for bank in 0..6 {
+        rom[bank * 0x4000] = bank as u8;
+    }
+    rom[0x147] = 0x01;
+    rom[0x148] = 0x03;
+
+    let mut emulator = EmulatorBuilder::new()
+        .trust_rom_file_size(true)
+        .build(&rom)
+        .unwrap();
+    assert_eq!(emulator.memory_bus.total_rom_size(), rom.len());
+    for (selected, mapped) in [(1, 1), (5, 5), (6, 0), (7, 1), (11, 5), (15, 3)] {
+        emulator.memory_bus.write_byte(0x2000, selected);
+        assert_eq!(
+            emulator.memory_bus.read_byte(0x4000),
+            mapped,
+            "bank {}",
+            selected
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Runs blargg and mooneye test ROMs headlessly and reports which pass.
+//!
+//! ROMs are read from `test_roms/blargg/` and `test_roms/mooneye/` (searched recursively
+//! for `.gb`/`.gbc` files). The `test_roms` directory is gitignored; point
+//! `BOBA_TEST_ROMS` (or `GB_TEST_ROMS`) at another directory to use that instead.
+//! Without ROMs the tests pass after printing that they were skipped, so CI without
+//! ROMs stays green.
+
+use boba::emulator::Emulator;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Emulated frames a ROM may run before it counts as timed out. cpu_instrs, the slowest
+/// of the blargg ROMs, needs about 55 seconds.
+const TIMEOUT_FRAMES: u64 = 60 * 90;
+/// Instructions between checks of the blargg serial output and screen.
+const BLARGG_CHECK_INTERVAL: u32 = 10_000;
+/// LD B,B, which mooneye ROMs execute as a soft breakpoint once they're done.
+const MOONEYE_BREAKPOINT: u8 = 0x40;
+/// B, C, D, E, H, L at the breakpoint when a mooneye test passed (Fibonacci numbers).
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+/// B, C, D, E, H, L at the breakpoint when a mooneye test failed.
+const MOONEYE_FAIL: [u8; 6] = [0x42; 6];
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Passed,
+    Failed(String),
+    TimedOut,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "PASS"),
+            Outcome::Failed(reason) => write!(f, "FAIL ({})", reason),
+            Outcome::TimedOut => write!(f, "TIMEOUT"),
+        }
+    }
+}
+
+/// Environment variables that can point at the test ROM root, in priority order.
+const ROOT_VARS: [&str; 2] = ["BOBA_TEST_ROMS", "GB_TEST_ROMS"];
+
+/// The ROM directory for one suite, or `None` if it doesn't exist.
+fn suite_dir(suite: &str) -> Option<PathBuf> {
+    let root = match ROOT_VARS.iter().find_map(std::env::var_os) {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("test_roms"),
+    };
+    let dir = root.join(suite);
+    dir.is_dir().then_some(dir)
+}
+
+/// All `.gb`/`.gbc` files under `dir`, sorted so the report order is stable.
+fn collect_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_roms(&path, roms);
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("gb" | "gbc")
+        ) {
+            roms.push(path);
+        }
+    }
+    roms.sort();
+}
+
+fn load(rom_path: &Path) -> Result<Emulator, String> {
+    let rom_data = fs::read(rom_path).map_err(|e| e.to_string())?;
+    Emulator::from_rom_bytes(&rom_data, true)
+}
+
+/// The background tile map read as text: blargg's ROMs load their font at the ASCII
+/// codes, so tile indices are the characters on screen.
+fn screen_text(emulator: &Emulator) -> String {
+    (0x9800..0x9C00)
+        .map(|addr| emulator.memory_bus.peek_byte(addr))
+        .map(|tile| {
+            if tile.is_ascii_graphic() {
+                tile as char
+            } else {
+                ' '
+            }
+        })
+        .collect()
+}
+
+/// Runs a blargg ROM until "Passed" or "Failed" shows up on the serial port or screen.
+fn run_blargg(rom_path: &Path) -> Outcome {
+    let mut emulator = match load(rom_path) {
+        Ok(emulator) => emulator,
+        Err(e) => return Outcome::Failed(e),
+    };
+    let max_cycles = emulator.cycles_per_frame() as u64 * TIMEOUT_FRAMES;
+    let mut serial = String::new();
+    while emulator.cpu.total_cycles() < max_cycles {
+        for _ in 0..BLARGG_CHECK_INTERVAL {
+            if let Err(e) = emulator.step_instruction() {
+                return Outcome::Failed(e);
+            }
+        }
+        serial.push_str(&emulator.take_serial_output());
+        for text in [serial.as_str(), &screen_text(&emulator)] {
+            if text.contains("Passed") {
+                return Outcome::Passed;
+            }
+            if text.contains("Failed") {
+                return Outcome::Failed(text.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+        }
+    }
+    Outcome::TimedOut
+}
+
+/// Runs a mooneye ROM until it hits the LD B,B breakpoint, then checks the register
+/// fingerprint it leaves behind.
+fn run_mooneye(rom_path: &Path) -> Outcome {
+    let mut emulator = match load(rom_path) {
+        Ok(emulator) => emulator,
+        Err(e) => return Outcome::Failed(e),
+    };
+    let max_cycles = emulator.cycles_per_frame() as u64 * TIMEOUT_FRAMES;
+    while emulator.cpu.total_cycles() < max_cycles {
+        let opcode = emulator.memory_bus.peek_byte(emulator.cpu.pc());
+        if let Err(e) = emulator.step_instruction() {
+            return Outcome::Failed(e);
+        }
+        if opcode != MOONEYE_BREAKPOINT {
+            continue;
+        }
+        let (_, _, b, c, d, e, h, l) = emulator.cpu.registers();
+        let fingerprint = [b, c, d, e, h, l];
+        if fingerprint == MOONEYE_PASS {
+            return Outcome::Passed;
+        }
+        if fingerprint == MOONEYE_FAIL {
+            return Outcome::Failed("failure fingerprint".to_string());
+        }
+    }
+    Outcome::TimedOut
+}
+
+/// Runs every ROM of a suite, prints a summary and fails if any ROM didn't pass.
+fn run_suite(suite: &str, run: fn(&Path) -> Outcome) {
+    let Some(dir) = suite_dir(suite) else {
+        eprintln!("Skipping {} test ROMs: no '{}' directory", suite, suite);
+        return;
+    };
+    let mut roms = Vec::new();
+    collect_roms(&dir, &mut roms);
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for rom_path in &roms {
+        let name = rom_path.strip_prefix(&dir).unwrap_or(rom_path).display();
+        let outcome = run(rom_path);
+        println!("{:<8} {}", outcome.to_string(), name);
+        if outcome == Outcome::Passed {
+            passed += 1;
+        } else {
+            failures.push(format!("{}: {}", name, outcome));
+        }
+    }
+    println!("{}: {}/{} passed", suite, passed, roms.len());
+    assert!(
+        failures.is_empty(),
+        "{} failures:\n{}",
+        suite,
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn blargg() {
+    run_suite("blargg", run_blargg);
+}
+
+#[test]
+fn mooneye() {
+    run_suite("mooneye", run_mooneye);
+}
@@ -0,0 +1,195 @@
+use boba::emulator::Emulator;
+use boba::memory_bus::MemoryBus;
+use boba::timer::Timer;
+
+const DIV: u16 = 0xFF04;
+const TIMA: u16 = 0xFF05;
+const TMA: u16 = 0xFF06;
+const TAC: u16 = 0xFF07;
+const IF: u16 = 0xFF0F;
+const KEY1: u16 = 0xFF4D;
+const LY: u16 = 0xFF44;
+const TIMER_INTERRUPT: u8 = 0x04;
+
+/// A timer with TAC set to `tac`, the divider just reset to 0 and no interrupt pending.
+fn timer_with_tac(tac: u8) -> (Timer, MemoryBus) {
+    let (mut timer, mut bus) = (Timer::new(), MemoryBus::new());
+    bus.write_byte(TAC, tac);
+    bus.write_byte(DIV, 0x00);
+    timer.step(0, &mut bus);
+    bus.write_byte(IF, 0x00);
+    (timer, bus)
+}
+
+fn timer_interrupt_requested(bus: &MemoryBus) -> bool {
+    bus.read_byte(IF) & TIMER_INTERRUPT != 0
+}
+
+#[test]
+fn overflow_at_tac_05_reloads_tma_and_requests_the_interrupt() {
+    let (mut timer, mut bus) = timer_with_tac(0x05);
+    bus.write_byte(TMA, 0xAB);
+    bus.write_byte(TIMA, 0xFF);
+
+    timer.step(16, &mut bus);
+    assert_eq!(bus.read_byte(TIMA), 0x00);
+    assert!(!timer_interrupt_requested(&bus));
+    timer.step(4, &mut bus);
+    assert_eq!(bus.read_byte(TIMA), 0xAB);
+    assert!(timer_interrupt_requested(&bus));
+}
+
+/// Runs TIMA from 0xFF into an overflow, leaving the TMA reload pending.
+fn overflow(timer: &mut Timer, bus: &mut MemoryBus) {
+    bus.write_byte(TMA, 0x80);
+    bus.write_byte(TIMA, 0xFF);
+    timer.step(16, bus);
+}
+
+#[test]
+fn tima_reads_zero_for_four_cycles_before_the_reload() {
+    let (mut timer, mut bus) = timer_with_tac(0x05);
+    overflow(&mut timer, &mut bus);
+    for _ in 0..3 {
+        timer.step(1, &mut bus);
+        assert_eq!(bus.read_byte(TIMA), 0x00);
+        assert!(!timer_interrupt_requested(&bus));
+    }
+    timer.step(1, &mut bus);
+    assert_eq!(bus.read_byte(TIMA), 0x80);
+    assert!(timer_interrupt_requested(&bus));
+}
+
+#[test]
+fn writing_tima_during_the_reload_window_cancels_the_reload() {
+    let (mut timer, mut bus) = timer_with_tac(0x05);
+    overflow(&mut timer, &mut bus);
+    timer.step(2, &mut bus);
+    bus.write_byte(TIMA, 0x42);
+    timer.step(4, &mut bus);
+    assert_eq!(bus.read_byte(TIMA), 0x42);
+    assert!(!timer_interrupt_requested(&bus));
+}
+
+#[test]
+fn each_tac_rate_ticks_tima_at_its_frequency() {
+    const CPU_FREQ: u32 = 4_194_304;
+    for (tac, hz) in [
+        (0x04, 4096),
+        (0x05, 262_144),
+        (0x06, 65_536),
+        (0x07, 16_384),
+    ] {
+        let period = CPU_FREQ / hz;
+        let (mut timer, mut bus) = timer_with_tac(tac);
+        bus.write_byte(TIMA, 0x00);
+        timer.step(period * 10 - 1, &mut bus);
+        assert_eq!(bus.read_byte(TIMA), 9, "TAC {:02X}", tac);
+        timer.step(1, &mut bus);
+        assert_eq!(bus.read_byte(TIMA), 10, "TAC {:02X}", tac);
+    }
+}
+
+#[test]
+fn resetting_div_while_the_selected_bit_is_high_ticks_tima() {
+    let (mut timer, mut bus) = timer_with_tac(0x05);
+    bus.write_byte(TIMA, 0x00);
+    timer.step(8, &mut bus); // Divider bit 3 is now high
+    bus.write_byte(DIV, 0x00);
+    timer.step(0, &mut bus);
+    assert_eq!(bus.read_byte(TIMA), 1);
+
+    // With the bit low, a reset changes nothing
+    bus.write_byte(DIV, 0x00);
+    timer.step(0, &mut bus);
+    assert_eq!(bus.read_byte(TIMA), 1);
+}
+
+#[test]
+fn changing_tac_while_the_selected_bit_is_high_ticks_tima() {
+    // Switching to a rate whose bit is low, or disabling the timer, is a falling edge
+    for new_tac in [0x04, 0x01] {
+        let (mut timer, mut bus) = timer_with_tac(0x05);
+        bus.write_byte(TIMA, 0x00);
+        timer.step(8, &mut bus);
+        bus.write_byte(TAC, new_tac);
+        timer.step(0, &mut bus);
+        assert_eq!(bus.read_byte(TIMA), 1, "TAC 05 -> {:02X}", new_tac);
+    }
+
+    // Switching to a rate whose bit is high too is not
+    let (mut timer, mut bus) = timer_with_tac(0x05);
+    bus.write_byte(TIMA, 0x00);
+    timer.step(0x88, &mut bus); // Bits 3 and 7 high
+    bus.write_byte(TAC, 0x07);
+    timer.step(0, &mut bus);
+    assert_eq!(bus.read_byte(TIMA), 8);
+}
+
+/// Divider and TIMA (at 4096 Hz) increments while the PPU draws 10 lines, i.e. over
+/// the same stretch of real time at either CPU speed.
+fn timer_progress_over_10_lines(emulator: &mut Emulator) -> (u16, u8) {
+    let ly = emulator.memory_bus.read_byte(LY);
+    while emulator.memory_bus.read_byte(LY) == ly {
+        emulator.step_instruction().unwrap();
+    }
+    let start_ly = emulator.memory_bus.read_byte(LY);
+    let divider = emulator.timer.divider();
+    emulator.memory_bus.write_byte(TIMA, 0x00);
+    emulator.memory_bus.write_byte(TAC, 0x04);
+    while emulator.memory_bus.read_byte(LY) != start_ly + 10 {
+        emulator.step_instruction().unwrap();
+    }
+    (
+        emulator.timer.divider().wrapping_sub(divider),
+        emulator.memory_bus.read_byte(TIMA),
+    )
+}
+
+#[test]
+fn stop_with_key1_armed_doubles_the_timer_and_div_rate() {
+    const GO: u16 = 0xFF80;
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x143] = 0x80; // CGB-enhanced
+    // wait: LDH A,(GO); AND A; JR Z,wait; STOP; loop: INC B; JR loop
+    rom[0x100..0x10A]
+        .copy_from_slice(&[0xF0, 0x80, 0xA7, 0x28, 0xFB, 0x10, 0x00, 0x04, 0x18, 0xFD]);
+    let mut emulator = Emulator::from_rom_bytes(&rom, true).unwrap();
+    emulator.memory_bus.write_byte(GO, 0x00);
+    emulator.memory_bus.write_byte(KEY1, 0x01);
+    assert_eq!(
+        emulator.memory_bus.read_byte(KEY1),
+        0x7F,
+        "armed at normal speed"
+    );
+    let (normal_divider, normal_tima) = timer_progress_over_10_lines(&mut emulator);
+
+    emulator.memory_bus.write_byte(GO, 0x01);
+    while emulator.cpu.pc() != 0x0107 {
+        emulator.step_instruction().unwrap();
+    }
+    assert_eq!(
+        emulator.memory_bus.read_byte(KEY1),
+        0xFE,
+        "double speed, disarmed"
+    );
+    let (double_divider, double_tima) = timer_progress_over_10_lines(&mut emulator);
+
+    let cycles = 10 * 456;
+    assert!(
+        (cycles..cycles + 24).contains(&normal_divider),
+        "{}",
+        normal_divider
+    );
+    assert!(
+        (2 * cycles..2 * cycles + 48).contains(&double_divider),
+        "{}",
+        double_divider
+    );
+    assert!(
+        (2 * normal_tima..=2 * normal_tima + 1).contains(&double_tima),
+        "{} ticks at double speed, {} at normal",
+        double_tima,
+        normal_tima
+    );
+}
@@ -0,0 +1,86 @@
+use boba::cpu::TraceMode;
+use boba::emulator::Emulator;
+use std::fs;
+use std::path::PathBuf;
+
+/// A ROM that jumps to 0x0150 and counts up in A forever (INC A at 0x0150).
+fn counting_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]); // JP 0x0150
+    rom[0x150..0x153].copy_from_slice(&[0x3C, 0x18, 0xFD]); // INC A; JR -3
+    rom
+}
+
+fn trace_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("boba_{}_{}.log", name, std::process::id()))
+}
+
+#[test]
+fn trace_lines_show_registers_bytes_and_disassembly() {
+    let path = trace_file("trace_format");
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    emulator
+        .cpu
+        .start_trace(&path, TraceMode::Plain, None)
+        .unwrap();
+    for _ in 0..3 {
+        emulator.step_instruction().unwrap();
+    }
+    emulator.cpu.stop_trace().unwrap();
+
+    let trace = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 (cy: 0) |[C3 50 01] 0x0100: JP 0x0150",
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 (cy: 16) |[3C] 0x0150: INC A",
+            "A:02 F:10 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0151 (cy: 20) |[18 FD] 0x0151: JR 0x0150",
+        ]
+    );
+}
+
+#[test]
+fn trace_stops_at_its_limit() {
+    let path = trace_file("trace_limit");
+    let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+    emulator
+        .cpu
+        .start_trace(&path, TraceMode::Plain, Some(5))
+        .unwrap();
+    emulator.run_frame().unwrap();
+
+    assert_eq!(emulator.cpu.trace_entries(), None);
+    let trace = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(trace.lines().count(), 5);
+}
+
+#[test]
+fn ring_buffer_trace_is_dumped_when_a_panic_unwinds() {
+    let path = trace_file("trace_panic");
+    let result = std::panic::catch_unwind(|| {
+        let mut emulator = Emulator::from_rom_bytes(&counting_rom(), true).unwrap();
+        emulator
+            .cpu
+            .start_trace(&path, TraceMode::RingBuffer(2), None)
+            .unwrap();
+        for _ in 0..3 {
+            emulator.step_instruction().unwrap();
+        }
+        panic!("frontend bug with a trace running");
+    });
+    assert!(result.is_err());
+
+    let trace = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("|[3C] 0x0150: INC A"), "{}", lines[0]);
+    assert!(
+        lines[1].ends_with("|[18 FD] 0x0151: JR 0x0150"),
+        "{}",
+        lines[1]
+    );
+}
@@ -0,0 +1,57 @@
+use boba::emulator::{BreakReason, Emulator};
+use boba::memory_bus::{WatchHit, WatchKind};
+
+/// A ROM that jumps to 0x0150, stores 0x42 to WRAM at 0xC123, then spins.
+fn storing_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]); // JP 0x0150
+    rom[0x150..0x157].copy_from_slice(&[
+        0x3E, 0x42, // LD A,0x42
+        0xEA, 0x23, 0xC1, // LD (0xC123),A
+        0x18, 0xFE, // JR -2
+    ]);
+    rom
+}
+
+#[test]
+fn store_to_watched_address_pauses() {
+    let mut emulator = Emulator::from_rom_bytes(&storing_rom(), true).unwrap();
+    emulator.add_watchpoint(0xC123, WatchKind::Write);
+
+    let hit = WatchHit {
+        addr: 0xC123,
+        kind: WatchKind::Write,
+        value: 0x42,
+    };
+    assert_eq!(
+        emulator.run_frame_until_breakpoint(),
+        Ok(Some(BreakReason::Watchpoint(hit)))
+    );
+    assert_eq!(emulator.cpu.pc(), 0x0155); // Stopped right after the store
+    assert_eq!(emulator.memory_bus.peek_byte(0xC123), 0x42);
+}
+
+#[test]
+fn write_value_watchpoint_ignores_other_values() {
+    let mut emulator = Emulator::from_rom_bytes(&storing_rom(), true).unwrap();
+    emulator.add_watchpoint(0xC123, WatchKind::WriteValue(0x41));
+    emulator.add_watchpoint(0xC123, WatchKind::Read);
+
+    assert_eq!(emulator.run_frame_until_breakpoint(), Ok(None));
+}
+
+#[test]
+fn watch_range_reports_the_address_accessed() {
+    let mut emulator = Emulator::from_rom_bytes(&storing_rom(), true).unwrap();
+    emulator.add_watch_range(0xC100..=0xC1FF, WatchKind::Write);
+
+    let hit = WatchHit {
+        addr: 0xC123,
+        kind: WatchKind::Write,
+        value: 0x42,
+    };
+    assert_eq!(
+        emulator.run_frame_until_breakpoint(),
+        Ok(Some(BreakReason::Watchpoint(hit)))
+    );
+}